@@ -372,7 +372,7 @@ async function repayLoan(agentAddress) {
 
    
 
-    const args = [toScVal(agentAddress)];
+    const args = [toScVal(agentAddress), StellarSdk.xdr.ScVal.scvVoid()]; // payment_amount: None
 
     const tx = await buildContractTx(CONFIG.contracts.lendingDemo, 'repay_loan', args);
 
@@ -990,7 +990,7 @@ async function repayLoanForUser(userAddress) {
 
    
 
-    const args = [toScVal(userAddress)];
+    const args = [toScVal(userAddress), StellarSdk.xdr.ScVal.scvVoid()]; // payment_amount: None
 
     const tx = await buildContractTx(CONFIG.contracts.lendingDemo, 'repay_loan', args);
 
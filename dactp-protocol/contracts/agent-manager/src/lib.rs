@@ -1,6 +1,19 @@
 #![no_std]
 
-use soroban_sdk::{contract, contractimpl, contracttype, Address, Env, String, Vec};
+use soroban_sdk::{
+    contract, contractclient, contractimpl, contracttype, xdr::ToXdr, Address, BytesN, Env, String, Vec,
+};
+
+/// Per-owner agent caps, sized by the owner's reputation tier
+const DEFAULT_MAX_AGENTS_PER_OWNER: u32 = 5; // Used until a reputation manager is configured
+const TIER_0_MAX_AGENTS: u32 = 1;  // Reputation 0-49
+const TIER_1_MAX_AGENTS: u32 = 3;  // Reputation 50-59
+const TIER_2_MAX_AGENTS: u32 = 5;  // Reputation 60-74
+const TIER_3_MAX_AGENTS: u32 = 10; // Reputation 75-89
+const TIER_4_MAX_AGENTS: u32 = 20; // Reputation 90+
+
+/// Most scopes one agent may hold (default, admin-configurable); bounds `is_authorized`
+const DEFAULT_MAX_SCOPES: u32 = 32;
 
 /// Agent represents a delegated actor with bounded authority
 #[contracttype]
@@ -12,10 +25,66 @@ pub struct AgentInfo {
     pub revoked: bool,         // Whether this agent has been permanently disabled
 }
 
+/// An agent-initiated registration awaiting its owner's approval
+#[contracttype]
+#[derive(Clone)]
+pub struct PendingRegistration {
+    pub owner: Address,        // The owner asked to approve the agent
+    pub scopes: Vec<String>,   // Requested actions
+    pub max_amount: u64,       // Requested limit
+}
+
+/// M-of-N owner set for an institutionally owned agent
+#[contracttype]
+#[derive(Clone)]
+pub struct MultisigOwnership {
+    pub owners: Vec<Address>,  // Every co-owner; the first is recorded as the agent's owner
+    pub threshold: u32,        // Owner approvals required for sensitive operations
+}
+
+/// Off-chain delegation terms an owner signs for an unregistered agent
+#[contracttype]
+#[derive(Clone)]
+pub struct Delegation {
+    pub max_amount: u64,  // Maximum amount the agent can handle under this delegation
+    pub expiry: u64,      // Unix timestamp after which the delegation is no longer valid
+    pub nonce: u64,       // Must exceed the owner's last used nonce (replay protection)
+}
+
+/// Exact payload the owner signs with their delegation key
+#[contracttype]
+#[derive(Clone)]
+pub struct DelegationPayload {
+    pub contract: Address,  // This AgentManager, so signatures can't be replayed elsewhere
+    pub owner: Address,     // Signing owner, so a key shared across owners can't be reused
+    pub agent: Address,
+    pub action: String,
+    pub max_amount: u64,
+    pub expiry: u64,
+    pub nonce: u64,
+}
+
 /// Storage keys for agent data
 #[contracttype]
 pub enum DataKey {
-    Agent(Address), // Maps agent address -> AgentInfo
+    Agent(Address),                 // Maps agent address -> AgentInfo
+    OwnerAgents(Address),           // Maps owner address -> Vec<Address> of registered agents
+    ReputationManagerContract(()),  // Address of ReputationManager contract
+    Admin(()),                      // Admin address
+    DelegationKey(Address),         // Maps owner address -> ed25519 public key for signed delegations
+    DelegationNonce(Address),       // Maps owner address -> last consumed delegation nonce
+    Multisig(Address),              // Maps agent address -> MultisigOwnership (absent = single owner)
+    ScopeMinReputation(String),     // Maps scope -> minimum reputation required to use it
+    Initialized(()),                // Set once `initialize` has run
+    PendingRegistration(Address),   // Maps agent address -> PendingRegistration
+    OwnerBudget(Address),           // Maps owner address -> total borrowing budget across its agents
+    MaxScopes(()),                  // Most scopes one agent may hold
+}
+
+/// ReputationManager contract trait for cross-contract calls
+#[contractclient(name = "ReputationManagerClient")]
+pub trait ReputationManagerInterface {
+    fn get_score(env: Env, agent: Address) -> u32;
 }
 
 #[contract]
@@ -23,6 +92,25 @@ pub struct AgentManagerContract;
 
 #[contractimpl]
 impl AgentManagerContract {
+    /// Initialize the agent manager with a reference to the ReputationManager
+    /// The reputation manager is used to size each owner's agent cap
+    pub fn initialize(env: Env, admin: Address, reputation_manager_contract: Address) {
+        admin.require_auth();
+
+        if env.storage().persistent().has(&DataKey::Initialized(())) {
+            panic!("Contract already initialized");
+        }
+        env.storage().persistent().set(&DataKey::Initialized(()), &true);
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::ReputationManagerContract(()), &reputation_manager_contract);
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::Admin(()), &admin);
+    }
+
     /// Register a new agent with specific permissions and limits
     /// Only the owner can register an agent
     pub fn register_agent(
@@ -35,6 +123,27 @@ impl AgentManagerContract {
         // Require owner authorization - this ensures only the owner can register agents
         owner.require_auth();
 
+        let scopes = normalize_scopes(&env, scopes);
+        let key = DataKey::Agent(agent.clone());
+        let existing: Option<AgentInfo> = env.storage().persistent().get(&key);
+
+        // Re-registering an agent updates it in place; a new agent counts against the owner's cap.
+        // Revocation is permanent, so a revoked agent cannot be brought back this way
+        match existing {
+            Some(info) => {
+                if info.owner != owner {
+                    panic!("Agent already registered to another owner");
+                }
+                if info.revoked {
+                    panic!("Agent has been revoked");
+                }
+                if env.storage().persistent().has(&DataKey::Multisig(agent.clone())) {
+                    panic!("Multisig agent: update it with owner approvals");
+                }
+            }
+            None => add_owner_agent(&env, &owner, &agent),
+        }
+
         // Create agent info
         let agent_info = AgentInfo {
             owner: owner.clone(),
@@ -44,15 +153,112 @@ impl AgentManagerContract {
         };
 
         // Store agent info in persistent storage
+        env.storage().persistent().set(&key, &agent_info);
+    }
+
+    /// Ask an owner to register this agent with the given permissions and limits
+    /// Only the agent can request; the request authorizes nothing until the owner
+    /// approves it with `approve_registration`. A new request replaces a pending one.
+    pub fn request_registration(
+        env: Env,
+        agent: Address,
+        owner: Address,
+        scopes: Vec<String>,
+        max_amount: u64,
+    ) {
+        agent.require_auth();
+
+        if env.storage().persistent().has(&DataKey::Agent(agent.clone())) {
+            panic!("Agent already registered");
+        }
+
+        let scopes = normalize_scopes(&env, scopes);
+        let pending = PendingRegistration { owner, scopes, max_amount };
+        env.storage()
+            .persistent()
+            .set(&DataKey::PendingRegistration(agent), &pending);
+    }
+
+    /// Approve an agent's pending registration request, registering it as requested
+    /// Only the owner named in the request can approve
+    pub fn approve_registration(env: Env, owner: Address, agent: Address) {
+        let key = DataKey::PendingRegistration(agent.clone());
+        let pending: PendingRegistration = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("No pending registration");
+
+        if pending.owner != owner {
+            panic!("Unauthorized: registration was requested from another owner");
+        }
+
+        env.storage().persistent().remove(&key);
+        Self::register_agent(env, owner, agent, pending.scopes, pending.max_amount);
+    }
+
+    /// Get an agent's registration request awaiting approval, if any
+    pub fn get_pending_registration(env: Env, agent: Address) -> Option<PendingRegistration> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PendingRegistration(agent))
+    }
+
+    /// Register a new agent owned by several keys, M-of-N of which must approve
+    /// sensitive operations (revoking, changing scopes, transferring ownership)
+    /// Every listed owner must authorize the registration. The first owner is
+    /// recorded as the agent's owner and the agent counts against its cap.
+    pub fn register_multisig_agent(
+        env: Env,
+        owners: Vec<Address>,
+        threshold: u32,
+        agent: Address,
+        scopes: Vec<String>,
+        max_amount: u64,
+    ) {
+        if threshold == 0 || threshold > owners.len() {
+            panic!("Threshold must be between 1 and the number of owners");
+        }
+
+        let mut seen = Vec::new(&env);
+        for owner in owners.iter() {
+            if seen.contains(&owner) {
+                panic!("Duplicate owner");
+            }
+            owner.require_auth();
+            seen.push_back(owner);
+        }
+
         let key = DataKey::Agent(agent.clone());
+        if env.storage().persistent().has(&key) {
+            panic!("Agent already registered");
+        }
+        let scopes = normalize_scopes(&env, scopes);
+
+        let primary_owner = owners.get(0).unwrap();
+        add_owner_agent(&env, &primary_owner, &agent);
+
+        let agent_info = AgentInfo {
+            owner: primary_owner,
+            scopes,
+            max_amount,
+            revoked: false,
+        };
+
         env.storage().persistent().set(&key, &agent_info);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Multisig(agent), &MultisigOwnership { owners, threshold });
     }
 
     /// Revoke an agent permanently - only callable by the owner
+    /// Multisig agents need `revoke_agent_multisig` unless their threshold is 1
     pub fn revoke_agent(env: Env, owner: Address, agent: Address) {
-        // Require owner authorization
-        owner.require_auth();
+        Self::revoke_agent_multisig(env.clone(), Vec::from_array(&env, [owner]), agent);
+    }
 
+    /// Revoke an agent permanently with the approval of enough of its owners
+    pub fn revoke_agent_multisig(env: Env, signers: Vec<Address>, agent: Address) {
         // Get existing agent info
         let key = DataKey::Agent(agent.clone());
         let mut agent_info: AgentInfo = env
@@ -61,10 +267,8 @@ impl AgentManagerContract {
             .get(&key)
             .expect("Agent not found");
 
-        // Verify the caller is the actual owner
-        if agent_info.owner != owner {
-            panic!("Unauthorized: only the agent owner can revoke");
-        }
+        // Require authorization from enough of the actual owners
+        require_owner_quorum(&env, &agent, &agent_info, &signers);
 
         // Mark as revoked
         agent_info.revoked = true;
@@ -73,6 +277,106 @@ impl AgentManagerContract {
         env.storage().persistent().set(&key, &agent_info);
     }
 
+    /// Revoke every agent an owner controls in one call, e.g. during a security incident
+    /// Needs a single owner authorization and skips agents that are already revoked.
+    /// Multisig agents needing more than one approval still go through `revoke_agent_multisig`.
+    pub fn revoke_all_agents(env: Env, owner: Address) {
+        owner.require_auth();
+
+        for agent in Self::get_owner_agents(env.clone(), owner.clone()).iter() {
+            let key = DataKey::Agent(agent.clone());
+            let Some(mut agent_info) = env.storage().persistent().get::<_, AgentInfo>(&key) else {
+                continue;
+            };
+            if agent_info.revoked {
+                continue;
+            }
+
+            let multisig: Option<MultisigOwnership> =
+                env.storage().persistent().get(&DataKey::Multisig(agent));
+            let owner_may_revoke = match multisig {
+                Some(ownership) => ownership.threshold <= 1 && ownership.owners.contains(&owner),
+                None => agent_info.owner == owner,
+            };
+            if !owner_may_revoke {
+                continue;
+            }
+
+            agent_info.revoked = true;
+            env.storage().persistent().set(&key, &agent_info);
+        }
+    }
+
+    /// Revoke an agent whose reputation has fallen to zero (frozen or defaulted to the floor)
+    /// Callable by anyone, e.g. a keeper. Returns whether the agent is now revoked.
+    pub fn sync_revocation(env: Env, agent: Address) -> bool {
+        let key = DataKey::Agent(agent.clone());
+        let mut agent_info: AgentInfo = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("Agent not found");
+
+        if agent_info.revoked {
+            return true;
+        }
+
+        let rep_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ReputationManagerContract(()))
+            .expect("Contract not initialized");
+
+        if ReputationManagerClient::new(&env, &rep_mgr_addr).get_score(&agent) > 0 {
+            return false;
+        }
+
+        agent_info.revoked = true;
+        env.storage().persistent().set(&key, &agent_info);
+        true
+    }
+
+    /// Replace an agent's permitted actions with the approval of enough of its owners
+    pub fn update_scopes(env: Env, signers: Vec<Address>, agent: Address, scopes: Vec<String>) {
+        let key = DataKey::Agent(agent.clone());
+        let mut agent_info: AgentInfo = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("Agent not found");
+
+        require_owner_quorum(&env, &agent, &agent_info, &signers);
+
+        agent_info.scopes = normalize_scopes(&env, scopes);
+        env.storage().persistent().set(&key, &agent_info);
+    }
+
+    /// Hand an agent to a single new owner with the approval of enough of its owners
+    /// The new owner must accept, and the agent counts against the new owner's cap
+    pub fn transfer_ownership(env: Env, signers: Vec<Address>, agent: Address, new_owner: Address) {
+        let key = DataKey::Agent(agent.clone());
+        let mut agent_info: AgentInfo = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .expect("Agent not found");
+
+        require_owner_quorum(&env, &agent, &agent_info, &signers);
+        new_owner.require_auth();
+
+        remove_owner_agent(&env, &agent_info.owner, &agent);
+        add_owner_agent(&env, &new_owner, &agent);
+
+        agent_info.owner = new_owner;
+        env.storage().persistent().set(&key, &agent_info);
+        env.storage().persistent().remove(&DataKey::Multisig(agent));
+    }
+
+    /// Get the co-owners and threshold of a multisig agent (None for single-owner agents)
+    pub fn get_multisig_ownership(env: Env, agent: Address) -> Option<MultisigOwnership> {
+        env.storage().persistent().get(&DataKey::Multisig(agent))
+    }
+
     /// Check if an agent is authorized to perform an action with a specific amount
     /// This is called by consumer contracts to verify agent permissions
     /// Returns true if authorized, false otherwise
@@ -109,18 +413,381 @@ impl AgentManagerContract {
         }
     }
 
+    /// Check an agent's authorization for a batch of (action, amount) pairs
+    /// Returns true only if every pair passes `is_authorized`.
+    pub fn is_authorized_all(env: Env, agent: Address, checks: Vec<(String, u64)>) -> bool {
+        for (action, amount) in checks.iter() {
+            if !Self::is_authorized(env.clone(), agent.clone(), action, amount) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Require a minimum reputation for an action, on top of the scope being granted
+    /// A minimum of 0 removes the requirement. Only callable by admin.
+    pub fn set_scope_min_reputation(env: Env, admin: Address, scope: String, min_score: u32) {
+        require_admin(&env, &admin);
+
+        let key = DataKey::ScopeMinReputation(scope);
+        if min_score == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &min_score);
+        }
+    }
+
+    /// Set the most scopes a single agent may hold
+    /// Applies when scopes are next registered or updated; existing agents keep theirs.
+    /// Only callable by admin.
+    pub fn set_max_scopes(env: Env, admin: Address, max_scopes: u32) {
+        require_admin(&env, &admin);
+
+        if max_scopes == 0 {
+            panic!("Agents must be allowed at least one scope");
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::MaxScopes(()), &max_scopes);
+    }
+
+    /// Get the most scopes a single agent may hold
+    pub fn get_max_scopes(env: Env) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::MaxScopes(()))
+            .unwrap_or(DEFAULT_MAX_SCOPES)
+    }
+
+    /// Get the minimum reputation an action requires (0 if none)
+    pub fn get_scope_min_reputation(env: Env, scope: String) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::ScopeMinReputation(scope))
+            .unwrap_or(0)
+    }
+
+    /// Check an agent's authorization like `is_authorized`, also requiring `score` to
+    /// meet the action's minimum reputation
+    pub fn is_authorized_with_reputation(
+        env: Env,
+        agent: Address,
+        action: String,
+        amount: u64,
+        score: u32,
+    ) -> bool {
+        score >= Self::get_scope_min_reputation(env.clone(), action.clone())
+            && Self::is_authorized(env, agent, action, amount)
+    }
+
+    /// Set the ed25519 public key an owner uses to sign off-chain delegations
+    pub fn set_delegation_key(env: Env, owner: Address, public_key: BytesN<32>) {
+        owner.require_auth();
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::DelegationKey(owner), &public_key);
+    }
+
+    /// Check an agent's authorization from an owner-signed delegation instead of `AgentInfo`
+    /// The owner signs the XDR of a `DelegationPayload` off-chain; the agent needs no registration.
+    /// Each accepted delegation consumes its nonce, so a replayed signature returns false;
+    /// only the delegated agent can present it. Panics if the signature does not verify
+    /// against the owner's delegation key.
+    pub fn is_authorized_signed(
+        env: Env,
+        owner: Address,
+        agent: Address,
+        action: String,
+        amount: u64,
+        delegation: Delegation,
+        signature: BytesN<64>,
+    ) -> bool {
+        agent.require_auth();
+
+        let public_key: BytesN<32> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::DelegationKey(owner.clone()))
+            .expect("Owner has no delegation key");
+
+        let payload = DelegationPayload {
+            contract: env.current_contract_address(),
+            owner: owner.clone(),
+            agent,
+            action,
+            max_amount: delegation.max_amount,
+            expiry: delegation.expiry,
+            nonce: delegation.nonce,
+        };
+        env.crypto()
+            .ed25519_verify(&public_key, &payload.to_xdr(&env), &signature);
+
+        // Reject replayed or stale nonces
+        let nonce_key = DataKey::DelegationNonce(owner);
+        let last_nonce: Option<u64> = env.storage().persistent().get(&nonce_key);
+        if let Some(last_nonce) = last_nonce {
+            if delegation.nonce <= last_nonce {
+                return false;
+            }
+        }
+
+        if env.ledger().timestamp() > delegation.expiry {
+            return false;
+        }
+
+        if amount > delegation.max_amount {
+            return false;
+        }
+
+        env.storage().persistent().set(&nonce_key, &delegation.nonce);
+        true
+    }
+
+    /// Check an agent's authorization and read its reputation in one call
+    /// Lets consumer contracts replace separate `is_authorized` and `get_score`
+    /// calls with a single cross-contract hop. The action's minimum reputation, if any,
+    /// is enforced. Returns (authorized, score).
+    pub fn authorize_and_score(env: Env, agent: Address, action: String, amount: u64) -> (bool, u32) {
+        let rep_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ReputationManagerContract(()))
+            .expect("Contract not initialized");
+
+        let rep_mgr_client = ReputationManagerClient::new(&env, &rep_mgr_addr);
+        let score = rep_mgr_client.get_score(&agent);
+        let authorized = Self::is_authorized_with_reputation(env.clone(), agent, action, amount, score);
+
+        (authorized, score)
+    }
+
+    /// Get the ReputationManager agents are scored with (None until initialized)
+    pub fn get_reputation_manager(env: Env) -> Option<Address> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::ReputationManagerContract(()))
+    }
+
     /// Get agent information (for UI display)
     pub fn get_agent_info(env: Env, agent: Address) -> Option<AgentInfo> {
         let key = DataKey::Agent(agent);
         env.storage().persistent().get(&key)
     }
+
+    /// Get the owner of an agent (the first owner for multisig agents)
+    pub fn get_owner(env: Env, agent: Address) -> Option<Address> {
+        Self::get_agent_info(env, agent).map(|info| info.owner)
+    }
+
+    /// Get all agents registered by an owner (including revoked ones)
+    pub fn get_owner_agents(env: Env, owner: Address) -> Vec<Address> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::OwnerAgents(owner))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Set the total an owner's agents may have borrowed at once, or None for no budget
+    /// Consumer contracts enforce it across all of the owner's agents. Only the owner can set it.
+    pub fn set_owner_budget(env: Env, owner: Address, budget: Option<u64>) {
+        owner.require_auth();
+
+        let key = DataKey::OwnerBudget(owner);
+        match budget {
+            Some(budget) => env.storage().persistent().set(&key, &budget),
+            None => env.storage().persistent().remove(&key),
+        }
+    }
+
+    /// Get an owner's borrowing budget across all its agents (None if unlimited)
+    pub fn get_owner_budget(env: Env, owner: Address) -> Option<u64> {
+        env.storage().persistent().get(&DataKey::OwnerBudget(owner))
+    }
+
+    /// Get the maximum number of active agents an owner may manage
+    /// Scales with the owner's reputation once a reputation manager is configured
+    pub fn get_agent_cap(env: Env, owner: Address) -> u32 {
+        let rep_mgr_addr: Option<Address> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ReputationManagerContract(()));
+
+        match rep_mgr_addr {
+            None => DEFAULT_MAX_AGENTS_PER_OWNER,
+            Some(addr) => {
+                let rep_mgr_client = ReputationManagerClient::new(&env, &addr);
+                calculate_agent_cap(rep_mgr_client.get_score(&owner))
+            }
+        }
+    }
+}
+
+/// Calculate the per-owner agent cap based on reputation score (tiered system)
+fn calculate_agent_cap(reputation_score: u32) -> u32 {
+    match reputation_score {
+        0..=49 => TIER_0_MAX_AGENTS,
+        50..=59 => TIER_1_MAX_AGENTS,
+        60..=74 => TIER_2_MAX_AGENTS,
+        75..=89 => TIER_3_MAX_AGENTS,
+        _ => TIER_4_MAX_AGENTS,
+    }
+}
+
+/// Require `admin` to be the stored admin and to have authorized the call
+fn require_admin(env: &Env, admin: &Address) {
+    admin.require_auth();
+
+    let stored_admin: Address = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Admin(()))
+        .expect("Contract not initialized");
+
+    if stored_admin != *admin {
+        panic!("Unauthorized: only admin can perform this action");
+    }
+}
+
+/// Require authorization from enough distinct owners of an agent
+/// A single-owner agent is treated as a 1-of-1 multisig.
+fn require_owner_quorum(env: &Env, agent: &Address, agent_info: &AgentInfo, signers: &Vec<Address>) {
+    let multisig: Option<MultisigOwnership> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Multisig(agent.clone()));
+
+    let (owners, threshold) = match multisig {
+        Some(ownership) => (ownership.owners, ownership.threshold),
+        None => (Vec::from_array(env, [agent_info.owner.clone()]), 1),
+    };
+
+    let mut approvals = Vec::new(env);
+    for signer in signers.iter() {
+        if !owners.contains(&signer) {
+            panic!("Unauthorized: only the agent owner can perform this action");
+        }
+        if approvals.contains(&signer) {
+            panic!("Duplicate signer");
+        }
+        signer.require_auth();
+        approvals.push_back(signer);
+    }
+
+    if approvals.len() < threshold {
+        panic!("Unauthorized: not enough owner approvals");
+    }
+}
+
+/// Reject an empty or oversized scope list and drop repeated scopes, keeping first
+/// occurrences in order
+fn normalize_scopes(env: &Env, scopes: Vec<String>) -> Vec<String> {
+    if scopes.is_empty() {
+        panic!("Agent must have at least one scope");
+    }
+
+    let mut unique = Vec::new(env);
+    for scope in scopes.iter() {
+        if !unique.contains(&scope) {
+            unique.push_back(scope);
+        }
+    }
+
+    if unique.len() > AgentManagerContract::get_max_scopes(env.clone()) {
+        panic!("Too many scopes for one agent");
+    }
+    unique
+}
+
+/// Add a new agent to an owner's index, enforcing the owner's agent cap
+fn add_owner_agent(env: &Env, owner: &Address, agent: &Address) {
+    let active_agents = count_active_agents(env, owner);
+    if active_agents >= AgentManagerContract::get_agent_cap(env.clone(), owner.clone()) {
+        panic!("Owner has reached the maximum number of agents for their reputation");
+    }
+
+    let owner_key = DataKey::OwnerAgents(owner.clone());
+    let mut agents: Vec<Address> = env
+        .storage()
+        .persistent()
+        .get(&owner_key)
+        .unwrap_or(Vec::new(env));
+    agents.push_back(agent.clone());
+    env.storage().persistent().set(&owner_key, &agents);
+}
+
+/// Remove an agent from an owner's index
+fn remove_owner_agent(env: &Env, owner: &Address, agent: &Address) {
+    let owner_key = DataKey::OwnerAgents(owner.clone());
+    let mut agents: Vec<Address> = env
+        .storage()
+        .persistent()
+        .get(&owner_key)
+        .unwrap_or(Vec::new(env));
+    if let Some(index) = agents.first_index_of(agent) {
+        agents.remove(index);
+        env.storage().persistent().set(&owner_key, &agents);
+    }
+}
+
+/// Count an owner's agents that have not been revoked
+fn count_active_agents(env: &Env, owner: &Address) -> u32 {
+    let agents: Vec<Address> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::OwnerAgents(owner.clone()))
+        .unwrap_or(Vec::new(env));
+
+    let mut count = 0;
+    for agent in agents.iter() {
+        let info: Option<AgentInfo> = env.storage().persistent().get(&DataKey::Agent(agent));
+        if let Some(info) = info {
+            if !info.revoked {
+                count += 1;
+            }
+        }
+    }
+    count
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use soroban_sdk::{vec, Env};
+    use soroban_sdk::{contract, contractimpl, vec, Bytes, Env};
     use soroban_sdk::testutils::Address as _;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    /// Minimal reputation manager returning a stored score per agent
+    #[contract]
+    pub struct MockReputationManager;
+
+    #[contractimpl]
+    impl MockReputationManager {
+        pub fn set_score(env: Env, agent: Address, score: u32) {
+            env.storage().persistent().set(&agent, &score);
+        }
+
+        pub fn get_score(env: Env, agent: Address) -> u32 {
+            env.storage().persistent().get(&agent).unwrap_or(50)
+        }
+    }
+
+    fn register_many(
+        env: &Env,
+        client: &AgentManagerContractClient,
+        owner: &Address,
+        count: u32,
+    ) -> u32 {
+        let scopes = vec![env, String::from_str(env, "borrow")];
+        let mut registered = 0;
+        for _ in 0..count {
+            let agent = Address::generate(env);
+            if client.try_register_agent(owner, &agent, &scopes, &1000).is_ok() {
+                registered += 1;
+            }
+        }
+        registered
+    }
 
     #[test]
     fn test_register_and_check_agent() {
@@ -148,6 +815,74 @@ mod test {
         assert!(!client.is_authorized(&agent, &String::from_str(&env, "borrow"), &100));
     }
 
+    #[test]
+    fn test_get_owner() {
+        let env = Env::default();
+        let contract_id = env.register(AgentManagerContract, ());
+        let client = AgentManagerContractClient::new(&env, &contract_id);
+
+        let owner = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.register_agent(&owner, &agent, &vec![&env, String::from_str(&env, "repay_loan")], &1000);
+        assert_eq!(client.get_owner(&agent), Some(owner));
+        assert_eq!(client.get_owner(&Address::generate(&env)), None);
+    }
+
+    #[test]
+    fn test_is_authorized_all() {
+        let env = Env::default();
+        let contract_id = env.register(AgentManagerContract, ());
+        let client = AgentManagerContractClient::new(&env, &contract_id);
+
+        let owner = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        let repay = String::from_str(&env, "repay_loan");
+        client.register_agent(&owner, &agent, &vec![&env, repay.clone()], &1000);
+
+        // Every pair must pass
+        let ok = vec![&env, (repay.clone(), 100u64), (repay.clone(), 1000u64)];
+        assert!(client.is_authorized_all(&agent, &ok));
+        let one_bad = vec![&env, (repay.clone(), 100u64), (String::from_str(&env, "borrow"), 100u64)];
+        assert!(!client.is_authorized_all(&agent, &one_bad));
+        let over_limit = vec![&env, (repay, 2000u64)];
+        assert!(!client.is_authorized_all(&agent, &over_limit));
+    }
+
+    #[test]
+    fn test_agent_requested_registration_needs_owner_approval() {
+        let env = Env::default();
+        let contract_id = env.register(AgentManagerContract, ());
+        let client = AgentManagerContractClient::new(&env, &contract_id);
+
+        let owner = Address::generate(&env);
+        let stranger = Address::generate(&env);
+        let agent = Address::generate(&env);
+        let borrow = String::from_str(&env, "borrow");
+
+        env.mock_all_auths();
+
+        client.request_registration(&agent, &owner, &vec![&env, borrow.clone()], &1000);
+        assert!(client.get_pending_registration(&agent).is_some());
+
+        // Pending: nothing is authorized yet
+        assert!(!client.is_authorized(&agent, &borrow, &500));
+        assert!(client.get_agent_info(&agent).is_none());
+
+        // Only the named owner can approve
+        assert!(client.try_approve_registration(&stranger, &agent).is_err());
+        client.approve_registration(&owner, &agent);
+
+        assert!(client.is_authorized(&agent, &borrow, &500));
+        assert_eq!(client.get_owner(&agent), Some(owner));
+        assert!(client.get_pending_registration(&agent).is_none());
+    }
+
     #[test]
     fn test_revoke_agent() {
         let env = Env::default();
@@ -172,4 +907,330 @@ mod test {
         // Verify it's no longer authorized
         assert!(!client.is_authorized(&agent, &String::from_str(&env, "repay_loan"), &500));
     }
+
+    #[test]
+    fn test_revoke_all_agents() {
+        let env = Env::default();
+        let contract_id = env.register(AgentManagerContract, ());
+        let client = AgentManagerContractClient::new(&env, &contract_id);
+
+        let owner = Address::generate(&env);
+        let other_owner = Address::generate(&env);
+        let bystander = Address::generate(&env);
+        let repay = String::from_str(&env, "repay_loan");
+        let scopes = vec![&env, repay.clone()];
+
+        env.mock_all_auths();
+
+        let agents = [Address::generate(&env), Address::generate(&env), Address::generate(&env)];
+        for agent in agents.iter() {
+            client.register_agent(&owner, agent, &scopes, &1000);
+        }
+        client.register_agent(&other_owner, &bystander, &scopes, &1000);
+
+        // One agent is already revoked; the bulk revoke still succeeds
+        client.revoke_agent(&owner, &agents[0]);
+        client.revoke_all_agents(&owner);
+
+        for agent in agents.iter() {
+            assert!(!client.is_authorized(agent, &repay, &500));
+        }
+        assert!(client.is_authorized(&bystander, &repay, &500));
+    }
+
+    #[test]
+    fn test_sync_revocation_revokes_zero_reputation_agents() {
+        let env = Env::default();
+        let contract_id = env.register(AgentManagerContract, ());
+        let client = AgentManagerContractClient::new(&env, &contract_id);
+        let rep_id = env.register(MockReputationManager, ());
+        let rep_client = MockReputationManagerClient::new(&env, &rep_id);
+
+        let admin = Address::generate(&env);
+        let owner = Address::generate(&env);
+        let healthy = Address::generate(&env);
+        let zeroed = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &rep_id);
+        let scopes = vec![&env, String::from_str(&env, "borrow")];
+        client.register_agent(&owner, &healthy, &scopes, &1000);
+        client.register_agent(&owner, &zeroed, &scopes, &1000);
+        rep_client.set_score(&zeroed, &0);
+
+        assert!(!client.sync_revocation(&healthy));
+        assert!(!client.get_agent_info(&healthy).unwrap().revoked);
+
+        assert!(client.sync_revocation(&zeroed));
+        assert!(client.get_agent_info(&zeroed).unwrap().revoked);
+        assert!(!client.is_authorized(&zeroed, &String::from_str(&env, "borrow"), &100));
+    }
+
+    #[test]
+    fn test_agent_cap_scales_with_owner_reputation() {
+        let env = Env::default();
+        let contract_id = env.register(AgentManagerContract, ());
+        let client = AgentManagerContractClient::new(&env, &contract_id);
+        let rep_id = env.register(MockReputationManager, ());
+        let rep_client = MockReputationManagerClient::new(&env, &rep_id);
+
+        let admin = Address::generate(&env);
+        let low_owner = Address::generate(&env);
+        let high_owner = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &rep_id);
+        assert!(client.try_initialize(&low_owner, &rep_id).is_err());
+        rep_client.set_score(&low_owner, &40);
+        rep_client.set_score(&high_owner, &95);
+
+        assert_eq!(client.get_agent_cap(&low_owner), TIER_0_MAX_AGENTS);
+        assert_eq!(client.get_agent_cap(&high_owner), TIER_4_MAX_AGENTS);
+
+        // Low-reputation owner is stopped at the lower cap
+        assert_eq!(register_many(&env, &client, &low_owner, 3), TIER_0_MAX_AGENTS);
+        assert_eq!(client.get_owner_agents(&low_owner).len(), TIER_0_MAX_AGENTS);
+
+        // High-reputation owner can register well past it
+        assert_eq!(register_many(&env, &client, &high_owner, 3), 3);
+    }
+
+    /// Sign a delegation payload the way an owner's wallet would off-chain
+    fn sign_delegation(env: &Env, key: &SigningKey, payload: &DelegationPayload) -> BytesN<64> {
+        let message: Bytes = payload.clone().to_xdr(env);
+        let mut buf = [0u8; 512];
+        let len = message.len() as usize;
+        message.copy_into_slice(&mut buf[..len]);
+        BytesN::from_array(env, &key.sign(&buf[..len]).to_bytes())
+    }
+
+    #[test]
+    fn test_signed_delegation_and_replay() {
+        let env = Env::default();
+        let contract_id = env.register(AgentManagerContract, ());
+        let client = AgentManagerContractClient::new(&env, &contract_id);
+
+        let owner = Address::generate(&env);
+        let agent = Address::generate(&env);
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+
+        env.mock_all_auths();
+        client.set_delegation_key(
+            &owner,
+            &BytesN::from_array(&env, &signing_key.verifying_key().to_bytes()),
+        );
+
+        let action = String::from_str(&env, "repay_loan");
+        let delegation = Delegation { max_amount: 1000, expiry: 3600, nonce: 1 };
+        let signature = sign_delegation(
+            &env,
+            &signing_key,
+            &DelegationPayload {
+                contract: contract_id.clone(),
+                owner: owner.clone(),
+                agent: agent.clone(),
+                action: action.clone(),
+                max_amount: 1000,
+                expiry: 3600,
+                nonce: 1,
+            },
+        );
+
+        // Only the agent can present the delegation and consume its nonce
+        env.set_auths(&[]);
+        assert!(client
+            .try_is_authorized_signed(&owner, &agent, &action, &500, &delegation, &signature)
+            .is_err());
+        env.mock_all_auths();
+
+        // Valid delegation authorizes the unregistered agent
+        assert!(client.get_agent_info(&agent).is_none());
+        assert!(client.is_authorized_signed(&owner, &agent, &action, &500, &delegation, &signature));
+
+        // The signature is bound to its owner, even one sharing the same key
+        let other_owner = Address::generate(&env);
+        client.set_delegation_key(
+            &other_owner,
+            &BytesN::from_array(&env, &signing_key.verifying_key().to_bytes()),
+        );
+        assert!(client
+            .try_is_authorized_signed(&other_owner, &agent, &action, &500, &delegation, &signature)
+            .is_err());
+
+        // Replaying the same nonce is rejected
+        assert!(!client.is_authorized_signed(&owner, &agent, &action, &500, &delegation, &signature));
+
+        // Tampered terms fail signature verification
+        let inflated = Delegation { max_amount: 5000, expiry: 3600, nonce: 2 };
+        assert!(client
+            .try_is_authorized_signed(&owner, &agent, &action, &500, &inflated, &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn test_authorize_and_score() {
+        let env = Env::default();
+        let contract_id = env.register(AgentManagerContract, ());
+        let client = AgentManagerContractClient::new(&env, &contract_id);
+        let rep_id = env.register(MockReputationManager, ());
+        let rep_client = MockReputationManagerClient::new(&env, &rep_id);
+
+        let admin = Address::generate(&env);
+        let owner = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &rep_id);
+        rep_client.set_score(&owner, &70);
+        rep_client.set_score(&agent, &82);
+
+        let scopes = vec![&env, String::from_str(&env, "borrow")];
+        client.register_agent(&owner, &agent, &scopes, &1000);
+
+        let borrow = String::from_str(&env, "borrow");
+        assert_eq!(client.authorize_and_score(&agent, &borrow, &500), (true, 82));
+        assert_eq!(client.authorize_and_score(&agent, &borrow, &5000), (false, 82));
+
+        // Unregistered agents are unauthorized but still scored
+        let stranger = Address::generate(&env);
+        assert_eq!(client.authorize_and_score(&stranger, &borrow, &1), (false, 50));
+
+        // Gate "borrow" on reputation 90: the scope alone no longer suffices
+        client.set_scope_min_reputation(&admin, &borrow, &90);
+        assert!(!client.is_authorized_with_reputation(&agent, &borrow, &500, &82));
+        assert!(client.is_authorized_with_reputation(&agent, &borrow, &500, &90));
+        assert_eq!(client.authorize_and_score(&agent, &borrow, &500), (false, 82));
+        assert!(client.is_authorized(&agent, &borrow, &500));
+    }
+
+    #[test]
+    fn test_multisig_agent_requires_quorum() {
+        let env = Env::default();
+        let contract_id = env.register(AgentManagerContract, ());
+        let client = AgentManagerContractClient::new(&env, &contract_id);
+
+        let (a, b, c) = (Address::generate(&env), Address::generate(&env), Address::generate(&env));
+        let stranger = Address::generate(&env);
+        let agent = Address::generate(&env);
+        let repay = String::from_str(&env, "repay_loan");
+
+        env.mock_all_auths();
+
+        let scopes = vec![&env, repay.clone()];
+        client.register_multisig_agent(&vec![&env, a.clone(), b.clone(), c.clone()], &2, &agent, &scopes, &1000);
+        assert_eq!(client.get_agent_info(&agent).unwrap().owner, a);
+
+        // One owner alone, or an owner plus a stranger, cannot revoke
+        assert!(client.try_revoke_agent(&a, &agent).is_err());
+        assert!(client.try_revoke_agent_multisig(&vec![&env, a.clone(), stranger], &agent).is_err());
+        assert!(client.try_revoke_agent_multisig(&vec![&env, a.clone(), a.clone()], &agent).is_err());
+        assert!(client.try_register_agent(&a, &agent, &scopes, &1000).is_err());
+        assert!(client.is_authorized(&agent, &repay, &500));
+
+        client.revoke_agent_multisig(&vec![&env, a.clone(), c.clone()], &agent);
+        assert!(!client.is_authorized(&agent, &repay, &500));
+    }
+
+    #[test]
+    fn test_register_agent_validates_scopes() {
+        let env = Env::default();
+        let contract_id = env.register(AgentManagerContract, ());
+        let client = AgentManagerContractClient::new(&env, &contract_id);
+        env.mock_all_auths();
+
+        let owner = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        // An agent that can do nothing is a misconfiguration
+        assert!(client.try_register_agent(&owner, &agent, &Vec::new(&env), &1000).is_err());
+        assert!(client.get_agent_info(&agent).is_none());
+
+        // Repeated scopes are collapsed to their first occurrence
+        let repay = String::from_str(&env, "repay_loan");
+        let borrow = String::from_str(&env, "borrow");
+        let scopes = vec![&env, repay.clone(), borrow.clone(), repay.clone(), borrow.clone()];
+        client.register_agent(&owner, &agent, &scopes, &1000);
+        let expected = vec![&env, repay.clone(), borrow.clone()];
+        assert_eq!(client.get_agent_info(&agent).unwrap().scopes, expected);
+
+        // Every other way of setting scopes validates them the same way
+        let signers = vec![&env, owner.clone()];
+        assert!(client.try_update_scopes(&signers, &agent, &Vec::new(&env)).is_err());
+        client.update_scopes(&signers, &agent, &vec![&env, borrow.clone(), borrow.clone()]);
+        assert_eq!(client.get_agent_info(&agent).unwrap().scopes, vec![&env, borrow.clone()]);
+
+        let multisig_agent = Address::generate(&env);
+        let owners = vec![&env, owner.clone(), Address::generate(&env)];
+        let empty = Vec::new(&env);
+        assert!(client
+            .try_register_multisig_agent(&owners, &2, &multisig_agent, &empty, &1000)
+            .is_err());
+        client.register_multisig_agent(&owners, &2, &multisig_agent, &scopes, &1000);
+        assert_eq!(client.get_agent_info(&multisig_agent).unwrap().scopes, expected);
+
+        let requester = Address::generate(&env);
+        assert!(client.try_request_registration(&requester, &owner, &empty, &1000).is_err());
+    }
+
+    #[test]
+    fn test_scope_count_is_limited() {
+        let env = Env::default();
+        let contract_id = env.register(AgentManagerContract, ());
+        let client = AgentManagerContractClient::new(&env, &contract_id);
+        env.mock_all_auths();
+
+        let admin = Address::generate(&env);
+        let rep_id = env.register(MockReputationManager, ());
+        client.initialize(&admin, &rep_id);
+        assert_eq!(client.get_max_scopes(), DEFAULT_MAX_SCOPES);
+        client.set_max_scopes(&admin, &3);
+
+        let owner = Address::generate(&env);
+        let agent = Address::generate(&env);
+        let scope = |name: &str| String::from_str(&env, name);
+        let too_many = vec![&env, scope("a"), scope("b"), scope("c"), scope("d")];
+        assert!(client.try_register_agent(&owner, &agent, &too_many, &1000).is_err());
+
+        // Duplicates don't count against the limit
+        let at_limit = vec![&env, scope("a"), scope("b"), scope("a"), scope("c")];
+        client.register_agent(&owner, &agent, &at_limit, &1000);
+        assert!(client.is_authorized(&agent, &scope("c"), &100));
+
+        // Updating can't grow the list past the limit either
+        let signers = vec![&env, owner.clone()];
+        assert!(client.try_update_scopes(&signers, &agent, &too_many).is_err());
+        assert_eq!(client.get_agent_info(&agent).unwrap().scopes.len(), 3);
+    }
+
+    #[test]
+    fn test_revoked_agent_cannot_be_re_registered() {
+        let env = Env::default();
+        let contract_id = env.register(AgentManagerContract, ());
+        let client = AgentManagerContractClient::new(&env, &contract_id);
+        env.mock_all_auths();
+
+        let admin = Address::generate(&env);
+        let rep_id = env.register(MockReputationManager, ());
+        let rep = MockReputationManagerClient::new(&env, &rep_id);
+        client.initialize(&admin, &rep_id);
+
+        // A tier-0 owner may run a single agent
+        let owner = Address::generate(&env);
+        rep.set_score(&owner, &10);
+        assert_eq!(client.get_agent_cap(&owner), 1);
+
+        let first = Address::generate(&env);
+        let second = Address::generate(&env);
+        let scopes = vec![&env, String::from_str(&env, "borrow")];
+        client.register_agent(&owner, &first, &scopes, &1000);
+        client.revoke_agent(&owner, &first);
+        client.register_agent(&owner, &second, &scopes, &1000);
+
+        // Re-registering the revoked agent would sidestep both the revocation and the cap
+        assert!(client.try_register_agent(&owner, &first, &scopes, &1000).is_err());
+        assert!(client.get_agent_info(&first).unwrap().revoked);
+    }
 }
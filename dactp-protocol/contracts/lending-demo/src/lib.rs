@@ -1,42 +1,372 @@
 #![no_std]
+// `initialize` takes the full deployment wiring, which the generated client mirrors
+#![allow(clippy::too_many_arguments)]
 
-use soroban_sdk::{contract, contractclient, contractimpl, contracttype, token, Address, Env, String, Vec};
+use soroban_sdk::{
+    contract, contractclient, contracterror, contractevent, contractimpl, contracttype,
+    panic_with_error, token, Address, Env, String, Vec,
+};
 
 /// ENHANCED RISK-BASED LENDING ALGORITHM
-/// Base reputation thresholds
-const MIN_REPUTATION_THRESHOLD: u32 = 60;
-const BOOTSTRAP_LOAN_THRESHOLD: u32 = 50;
-const MAX_BOOTSTRAP_LOAN_AMOUNT: u64 = 1_000_000; // 0.1 XLM in stroops
-
 /// Dynamic loan limits based on reputation tiers
 const TIER_1_MAX_LOAN: u64 = 5_000_000;   // 0.5 XLM for reputation 50-59
 const TIER_2_MAX_LOAN: u64 = 20_000_000;  // 2.0 XLM for reputation 60-74
 const TIER_3_MAX_LOAN: u64 = 50_000_000;  // 5.0 XLM for reputation 75-89
 const TIER_4_MAX_LOAN: u64 = 100_000_000; // 10.0 XLM for reputation 90+
+const REPUTATION_TIER_COUNT: u32 = 4;
+
+/// The same tier limits in USD cents, used instead when a price oracle is configured
+/// (equal to the stroop limits at 0.10 USD per XLM)
+const TIER_1_MAX_LOAN_USD_CENTS: u64 = 5;
+const TIER_2_MAX_LOAN_USD_CENTS: u64 = 20;
+const TIER_3_MAX_LOAN_USD_CENTS: u64 = 50;
+const TIER_4_MAX_LOAN_USD_CENTS: u64 = 100;
+const PRICE_UNITS_PER_USD_CENT: u128 = 100_000; // Oracle prices are in 1e-7 USD
+
+/// Smooth cap growth for agents in sustained good standing
+const GOOD_STANDING_BONUS_PER_DAY: u64 = 200_000; // 0.02 XLM of extra cap per day without default
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
 
 /// Risk-adjusted reputation updates
 const REPUTATION_INCREASE_ON_TIME: i32 = 8;     // Bonus for on-time payment
 const REPUTATION_INCREASE_EARLY: i32 = 12;      // Bonus for early payment
 const REPUTATION_DECREASE_LATE: i32 = -5;       // Penalty for late payment
 const REPUTATION_DECREASE_DEFAULT: i32 = -25;   // Heavy penalty for default
+const REPUTATION_DECREASE_SECOND_DEFAULT: i32 = -35; // Default escalation schedule (default)
+const REPUTATION_DECREASE_REPEAT_DEFAULT: i32 = -50;
+const DEFAULT_COUNT_RESET_SECONDS: u64 = 180 * 24 * 60 * 60; // Good standing that clears prior defaults
+
+/// Endorsements: established agents vouch for newcomers with their own reputation
+const ENDORSER_MIN_REPUTATION: u32 = 75;                  // Endorser must be tier 3 or better
+const ENDORSEMENT_BOOST: u32 = 5;                         // Temporary boost to the endorsee's score
+const ENDORSEMENT_DURATION_SECONDS: u64 = 30 * 24 * 60 * 60; // Boost lasts 30 days
+const MAX_ACTIVE_ENDORSEMENTS: u32 = 3;                   // Per endorser
+const REPUTATION_DECREASE_ENDORSER: i32 = -10;            // Endorser's share of an endorsee default
+
+/// Per-period withdrawal limits for depositors, as a share of their position by
+/// reputation tier; tiers 3 and 4 withdraw freely
+const WITHDRAWAL_PERIOD_SECONDS: u64 = 24 * 60 * 60;
+const TIER_0_WITHDRAWAL_BPS: u32 = 1_000; // Reputation below 50
+const TIER_1_WITHDRAWAL_BPS: u32 = 2_500; // Reputation 50-59
+const TIER_2_WITHDRAWAL_BPS: u32 = 5_000; // Reputation 60-74
+
+/// Keeper incentive for reporting defaults, a small share of the principal so a
+/// defaulter reporting itself through another address recovers little of the pool's loss
+const DEFAULT_REPORT_BOUNTY_BPS: u32 = 100;          // 1% of the defaulted principal, paid from the pool
+const MAX_DEFAULT_REPORT_BOUNTY: u64 = 10_000_000;   // 1 XLM cap per reported default
+const MAX_DEFAULTED_AGENTS: u32 = 200;       // Recovery worklist size (oldest entries are dropped first)
+
+/// Rewards for keeping the credit line (the agent's loan limit) lightly drawn
+const CREDIT_DISCIPLINE_TARGET_BPS: u32 = 5_000;                // Peak draw must stay at or below 50%
+const CREDIT_DISCIPLINE_PERIOD_SECONDS: u64 = 30 * 24 * 60 * 60; // At most one reward per 30 days
+const REPUTATION_INCREASE_CREDIT_DISCIPLINE: i32 = 2;
+
+/// Interest rebates for well-reputed borrowers
+const REBATE_PERIOD_SECONDS: u64 = 30 * 24 * 60 * 60; // One distribution per 30 days (default)
+
+/// Rewards for supplying pool liquidity, proportional to size and time deposited
+const LIQUIDITY_LOCK_SECONDS: u64 = 7 * 24 * 60 * 60;  // Deposit must stay put this long per reward
+const LIQUIDITY_BONUS_STROOP_SECONDS: u128 = 100_000_000 * LIQUIDITY_LOCK_SECONDS as u128; // 1 point per 10 XLM-week
+const MAX_LIQUIDITY_BONUS: u32 = 3;
+
+/// Reputation bounds mirrored from ReputationManager, used when replaying loan history
+const REPUTATION_BASELINE: u32 = 50; // Score of an agent with no history, if the manager can't say
+const REPUTATION_MIN: u32 = 0;
+const REPUTATION_MAX: u32 = 100;
 
 /// Time-based risk factors
-const DEFAULT_LOAN_DURATION_SECONDS: u64 = 7 * 24 * 60 * 60; // 7 days
-const GRACE_PERIOD_SECONDS: u64 = 24 * 60 * 60; // 1 day grace period
-const EARLY_PAYMENT_THRESHOLD: u64 = 12 * 60 * 60; // 12 hours early bonus
+const MAX_LOAN_DURATION_SECONDS: u64 = 365 * 24 * 60 * 60; // Longest loan term (default)
+const GRACE_PERIOD_SECONDS: u64 = 24 * 60 * 60; // 1 day on-time grace period (default)
+const DEFAULT_AFTER_SECONDS: u64 = 24 * 60 * 60; // Default declared 1 day past due (default)
+const EARLY_PAYMENT_THRESHOLD: u64 = 12 * 60 * 60; // 12 hours early bonus (default for every tier)
+
+/// XLM amount formatting (1 XLM = 10^7 stroops)
+const STROOPS_PER_XLM: u64 = 10_000_000;
+const XLM_DECIMALS: u32 = 7;
+const MAX_XLM_STRING_LEN: usize = 32; // Longer inputs are rejected rather than parsed
+
+/// Smallest loan worth disbursing when auto-sizing a request
+const MIN_LOAN_AMOUNT: u64 = 100_000; // 0.01 XLM
 
-/// Utilization-based risk adjustment
-const MAX_POOL_UTILIZATION: u32 = 80; // Max 80% of pool can be lent out
+/// Interest charged on a loan's principal at repayment: flat, or per period when
+/// an interest period is configured
+const LOAN_INTEREST_BPS: u64 = 500; // 5% of principal (per period)
+const MAX_COMPOUNDING_PERIODS: u64 = 3_650; // Dormant loans stop compounding after this many periods
+const GROWTH_SCALE: u128 = 1_000_000_000_000_000_000; // Fixed-point scale of compounding growth factors
+const MAX_INSTALLMENTS: u32 = 365;           // Longest amortization schedule that can be previewed
+
+/// Loan-to-value allowed against collateral per reputation tier (defaults, admin-configurable)
+const TIER_1_LTV_BPS: u32 = 5_000; // Reputation 50-59 posts 2x the loan
+const TIER_2_LTV_BPS: u32 = 6_500; // Reputation 60-74
+const TIER_3_LTV_BPS: u32 = 8_000; // Reputation 75-89
+const TIER_4_LTV_BPS: u32 = 9_000; // Reputation 90+
+
+/// Utilization-based risk adjustment (thresholds are configured at initialization)
+const BPS_DENOMINATOR: u32 = 10_000; // 100% in basis points
+const UTILIZATION_HISTORY_SIZE: u32 = 48; // Samples kept for charts (default, admin-configurable)
 
 /// Loan represents an active loan with due date tracking
 #[contracttype]
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Loan {
+    pub loan_id: u64,        // Stable, monotonically increasing identifier
     pub agent: Address,      // The agent that took the loan
     pub amount: u64,         // Loan amount in XLM (stroops)
     pub repaid: bool,        // Whether the loan has been repaid
     pub due_date: u64,       // Unix timestamp when loan is due
     pub created_at: u64,     // Unix timestamp when loan was created
+    pub tier: u32,           // Reputation tier (1-4) when the loan was issued
+    pub terms: LoanTerms,    // Terms fixed at issuance; repayment never re-reads them
+    pub booked_interest: u64, // Interest counted in the pool's outstanding interest for this loan
+}
+
+/// Terms a loan was issued under, snapshotted so later reputation or config
+/// changes can't alter the obligation
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoanTerms {
+    pub max_loan: u64,                // The agent's reputation-based limit at issuance
+    pub tier_limit: u64,              // Base limit of the loan's tier, used to scale default penalties
+    pub interest_bps: u32,            // Interest rate per period (0 for the interest-free first loan)
+    pub min_interest: u64,            // Least interest owed, however briefly the loan is held
+    pub grace_period_seconds: u64,    // Past the due date, repayment still counts as on time
+    pub default_after_seconds: u64,   // Past the due date, the loan is in default
+    pub early_payment_threshold: u64, // Lead time before the due date that earns the early bonus
+}
+
+/// Details of a newly issued loan, returned by `request_loan`
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoanReceipt {
+    pub loan_id: u64,       // Identifier of the new loan
+    pub amount: u64,        // XLM disbursed to the agent (stroops)
+    pub due_date: u64,      // Unix timestamp when the loan is due
+    pub interest_bps: u32,  // Interest rate locked in for the loan (0 for the interest-free first loan)
+}
+
+/// How a loan was closed out, as recorded in the agent's loan history
+#[contracttype]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoanOutcome {
+    Early,   // Repaid before the early-payment threshold
+    OnTime,  // Repaid by the due date (including grace period)
+    Late,    // Repaid after the due date
+    Default, // Penalized as a default
+    Recovered, // A defaulted loan repaid in full after all
+}
+
+/// One payment of an amortization schedule
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Installment {
+    pub due_date: u64,   // Unix timestamp when the installment is due
+    pub principal: u64,  // Share of the principal repaid (stroops)
+    pub interest: u64,   // Share of the interest paid (stroops)
+    pub amount: u64,     // principal + interest
+}
+
+/// LoanRecord is an entry in an agent's authoritative loan history
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoanRecord {
+    pub loan_id: u64,          // Identifier of the closed-out loan
+    pub amount: u64,           // Loan amount in XLM (stroops)
+    pub created_at: u64,       // Unix timestamp when loan was created
+    pub due_date: u64,         // Unix timestamp when loan was due
+    pub closed_at: u64,        // Unix timestamp of the repayment or default
+    pub outcome: LoanOutcome,  // Outcome that drove the reputation update
+    pub reputation_delta: i32, // Reputation change applied for this outcome
+}
+
+/// A reputation score and when it took effect, as reported by the ReputationManager
+#[contracttype]
+#[derive(Clone)]
+pub struct ScoreSample {
+    pub timestamp: u64,
+    pub score: u32,
+}
+
+/// How `owner_reputation` combines the scores of an owner's agents
+#[contracttype]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AggregateMode {
+    Average,
+    Min,
+    Max,
+}
+
+/// Per-agent lending state that outlives individual loans
+#[contracttype]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AgentState {
+    pub had_first_loan: bool,               // Whether the agent has used its interest-free first loan
+    pub good_standing_since: Option<u64>,  // Start of the current default-free run (set on first borrow)
+    pub default_count: u32,                 // Defaults since the last long good-standing run
+    pub peak_utilization_bps: u32,          // Highest draw on the loan limit since the last discipline reward
+    pub last_discipline_reward: Option<u64>, // When the agent last earned a credit discipline reward
+    pub had_late_payment: bool,             // Whether the agent's one penalty-free late repayment is used
+    pub last_loan_closed_at: Option<u64>,   // When the agent's latest loan was repaid
+}
+
+/// XLM a depositor has withdrawn in the current withdrawal period
+#[contracttype]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WithdrawalWindow {
+    pub period_start: u64,  // Unix timestamp the current period began
+    pub withdrawn: u64,     // XLM withdrawn since then
+}
+
+/// A vouch from an established agent, recorded against the endorsee
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endorsement {
+    pub endorser: Address,  // Agent lending its reputation
+    pub expires_at: u64,    // Unix timestamp when the boost and liability end
+}
+
+/// Aggregate net position of the lending pool
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoolStatus {
+    pub total_liquidity: u64,    // XLM currently held by the contract
+    pub total_outstanding: u64,  // Principal lent out and not yet repaid
+    pub accrued_interest: u64,   // Interest owed on outstanding loans, not yet paid
+    pub reserve_balance: u64,    // Reserve set aside from interest income, not lendable
+    pub utilization_bps: u32,    // Outstanding principal as a share of the whole pool
+}
+
+/// The contract addresses set at initialization, None where missing
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Wiring {
+    pub agent_manager: Option<Address>,
+    pub reputation_manager: Option<Address>,
+    pub xlm_token: Option<Address>,
+    pub admin: Option<Address>,
+}
+
+/// How proceeds recovered from a defaulted loan are divided, in basis points summing to 10000
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoverySplit {
+    pub pool_bps: u32,      // Stays in the pool as lendable liquidity
+    pub reserve_bps: u32,   // Added to the reserve
+    pub treasury_bps: u32,  // Added to the protocol fees withdrawn by the admin
+}
+
+/// Share of paid interest returned to well-reputed borrowers each period
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RebatePolicy {
+    pub rebate_bps: u32,        // Share of the period's paid interest returned (0 = no rebates)
+    pub min_reputation: u32,    // Agents below this score at distribution get nothing
+    pub period_seconds: u64,    // Minimum time between two distributions
+}
+
+/// Typed errors for failures callers are expected to handle
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum LendingError {
+    ReputationUnavailable = 1, // The agent's score could not be fetched (strict mode)
+    Misconfigured = 2,         // A protocol contract is unset, mis-wired or not responding
+    AmountOverflow = 3,        // An amount owed does not fit in a u64
+}
+
+/// Admin-configured lending parameters
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LendingConfig {
+    pub utilization_warning_bps: u32,   // Borrowing above this emits a high-utilization event
+    pub utilization_hard_cap_bps: u32,  // Default utilization ceiling for every reputation tier
+    pub tier_utilization_caps_bps: Vec<u32>, // Borrowing above these is rejected, tiers 1-4
+    pub min_pool_balance: u64,          // Loans never take the pool's balance below this
+    pub max_single_loan_bps: u32,       // Largest share of lendable liquidity one loan may take
+    pub max_total_outstanding: u64,     // Ceiling on principal lent out across the pool (0 = no cap)
+    pub reputation_twap_window_seconds: u64, // Size loans off a time-weighted score (0 = spot score)
+    pub max_loan_duration_seconds: u64, // Longest term a loan can be issued for
+    pub min_seconds_between_loans: u64, // Cooldown after a loan closes before the agent can borrow again
+    pub grace_period_seconds: u64,      // Past the due date, repayment still counts as on time
+    pub default_after_seconds: u64,     // Past the due date, the loan is in default
+    pub default_penalty_schedule: Vec<i32>, // Penalty for the 1st, 2nd, ... default; last entry repeats
+    pub scale_default_penalty: bool,    // Scale default penalties by principal relative to the tier limit
+    pub default_recovery_points: u32,   // Reputation restored when a defaulted loan is repaid after all
+    pub lenient_reputation_fallback: bool, // Lend at the baseline score when reputation is unavailable
+    pub interest_compounding: bool,     // Compound interest each period instead of accruing it linearly
+    pub interest_period_seconds: u64,   // Period the interest rate applies to (0 = flat interest per loan)
+    pub min_interest: u64,              // Floor on the interest of interest-bearing loans
+    pub protocol_fee_bps: u32,          // Share of repaid interest set aside as protocol fees
+    pub early_fee_waiver_bps: u32,      // Share of the protocol fee waived for early repayments
+    pub reserve_target: u64,            // Reserve size at which interest stops topping it up
+    pub reserve_fill_bps: u32,          // Share of repaid interest routed to the reserve below target
+    pub recovery_split: RecoverySplit,  // Division of funds recovered from defaulted loans
+    pub rebate_policy: RebatePolicy,    // Interest returned to well-reputed borrowers
+    pub utilization_history_size: u32,  // Utilization samples kept before the oldest are dropped
+    pub bonus_decay_window_seconds: u64, // Window for counting recent repayment bonuses (0 = no decay)
+    pub bonus_decay_full_count: u32,    // Bonuses per window awarded in full before they diminish
+    pub collateral_ltv_bps: Vec<u32>,   // Allowed loan-to-value for reputation tiers 1 through 4
+    pub early_payment_thresholds: Vec<u64>, // Lead time before the due date that earns the early bonus, tiers 1-4
+}
+
+/// Per-agent records of one borrower (its loan is exported separately)
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AgentSnapshot {
+    pub agent: Address,
+    pub state: AgentState,
+    pub history: Vec<LoanRecord>,
+    pub penalty_applied: bool,
+    pub default_reporter: Option<Address>, // Keeper paid for reporting its most recent default
+    pub recent_bonuses: Vec<u64>,          // Times of its repayment bonuses still counted for decay
+    pub reminder_offset: Option<u64>,      // Seconds before the due date to remind it
+    pub interest_paid: u64,                // Interest paid since the last rebate distribution
+}
+
+/// Pool position of one lender
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LenderSnapshot {
+    pub lender: Address,
+    pub shares: u64,
+    pub liquidity_since: Option<u64>, // Start of its unbroken, unrewarded deposit
+    pub withdrawal_window: WithdrawalWindow, // Default when it has never withdrawn
+}
+
+/// Exported lending state: config, pool counters, every borrower's records and lender positions
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateBlob {
+    pub config: LendingConfig,
+    pub total_outstanding: u64,
+    pub outstanding_interest: u64,
+    pub next_loan_id: u64,
+    pub loans: Vec<Loan>,             // Latest loan of each borrower that has one
+    pub agents: Vec<AgentSnapshot>,
+    pub endorsements: Vec<(Address, Endorsement)>, // (endorsee, endorsement) of every stored vouch
+    pub total_shares: u64,
+    pub lenders: Vec<LenderSnapshot>,
+    pub accrued_fees: u64,
+    pub reserve_balance: u64,
+    pub utilization_history: Vec<(u64, u32)>,
+    pub last_rebate_distribution: Option<u64>,
+    pub reputation_adjustments: Vec<(Address, Vec<(u64, i32)>)>, // Off-loan reputation changes per agent
+}
+
+/// Emitted instead of a penalty when an agent repays late for the first time
+#[contractevent(topics = ["loan", "late_warning"])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LateWarning {
+    pub agent: Address,
+    pub loan_id: u64,
+}
+
+/// Emitted when a loan pushes pool utilization into the warning zone
+#[contractevent(topics = ["pool", "high_utilization"])]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HighUtilization {
+    pub agent: Address,
+    pub utilization_bps: u32,  // Pool utilization after the loan
+    pub warning_bps: u32,      // Configured warning threshold
 }
 
 /// Storage keys
@@ -47,13 +377,63 @@ pub enum DataKey {
     ReputationManagerContract(()),    // Address of ReputationManager contract
     XlmTokenContract(()),             // Address of XLM token contract
     Admin(()),                        // Admin address for liquidity management
-    PenaltyApplied(Address),          // Tracks if penalty was already applied for an agent
+    PenaltyApplied(u64),              // Tracks if the default penalty was already applied for a loan id
+    LoanHistory(Address),             // Maps agent address -> Vec<LoanRecord>
+    Config(()),                       // LendingConfig
+    TotalOutstanding(()),             // Sum of principal currently lent out
+    AgentState(Address),              // Maps agent address -> AgentState
+    OutstandingInterest(()),          // Interest owed on loans currently lent out
+    DefaultReporter(Address),         // Maps agent address -> keeper paid for reporting its default
+    Endorsement(Address),             // Maps endorsee address -> Endorsement
+    EndorsedAgents(Address),          // Maps endorser address -> Vec<Address> of endorsees
+    Endorsers(()),                    // Vec<Address> of every agent that has endorsed another
+    Borrowers(()),                    // Vec<Address> of every agent that has taken a loan
+    NextLoanId(()),                   // Counter for the next loan identifier
+    LoanById(u64),                    // Maps loan id -> Loan
+    AllowedToken(Address),            // Maps token address -> bool (may be lent from a pool)
+    ActiveLoans(()),                  // Vec<Address> of agents with an unrepaid loan
+    ApprovedKeeper(Address),          // Maps keeper address -> bool (may run keeper operations)
+    LenderShares(Address),            // Maps lender address -> pool shares held
+    RecentBonuses(Address),           // Maps agent address -> Vec<u64> of recent repayment bonus times
+    TotalShares(()),                  // Pool shares issued to all lenders
+    Lenders(()),                      // Vec<Address> of every lender that has held pool shares
+    Initialized(()),                  // Set once `initialize` has run
+    PrivilegedBorrower(Address),      // Maps agent address -> bool (exempt from the utilization cap)
+    BorrowBlocked(Address),           // Maps agent address -> bool (barred from new loans)
+    ReminderOffset(Address),          // Maps agent address -> seconds before the due date to remind it
+    WithdrawalWindow(Address),        // Maps lender address -> WithdrawalWindow
+    LiquiditySince(Address),          // Maps lender address -> start of its unbroken, unrewarded deposit
+    AccruedFees(()),                  // Protocol fees held by the contract, not lendable
+    ReserveBalance(()),               // Reserve held by the contract, not lendable
+    PriceOracle(()),                  // Address of the XLM/USD price oracle (absent = fixed stroop limits)
+    UtilizationHistory(()),           // Vec<(u64, u32)> of (timestamp, utilization bps) samples, oldest first
+    DefaultedAgents(()),              // Vec<Address> of agents with a penalized, unrepaid loan, oldest first
+    InterestPaid(Address),            // Maps agent address -> interest paid since the last rebate distribution
+    RebatePayers(()),                 // Vec<Address> of agents that paid interest since the last distribution
+    LastRebateDistribution(()),       // Time of the last rebate distribution
+    ReputationAdjustments(Address),   // Maps agent address -> Vec<(u64, i32)> of off-loan reputation changes
+}
+
+/// Agent registration as reported by the AgentManager
+#[contracttype]
+#[derive(Clone)]
+pub struct AgentInfo {
+    pub owner: Address,
+    pub scopes: Vec<String>,
+    pub max_amount: u64,
+    pub revoked: bool,
 }
 
 /// AgentManager contract trait for cross-contract calls
 #[contractclient(name = "AgentManagerClient")]
 pub trait AgentManagerInterface {
     fn is_authorized(env: Env, agent: Address, action: String, amount: u64) -> bool;
+    fn register_agent(env: Env, owner: Address, agent: Address, scopes: Vec<String>, max_amount: u64);
+    fn authorize_and_score(env: Env, agent: Address, action: String, amount: u64) -> (bool, u32);
+    fn get_agent_info(env: Env, agent: Address) -> Option<AgentInfo>;
+    fn get_owner_agents(env: Env, owner: Address) -> Vec<Address>;
+    fn get_owner_budget(env: Env, owner: Address) -> Option<u64>;
+    fn get_reputation_manager(env: Env) -> Option<Address>;
 }
 
 /// ReputationManager contract trait for cross-contract calls
@@ -61,6 +441,16 @@ pub trait AgentManagerInterface {
 pub trait ReputationManagerInterface {
     fn get_score(env: Env, agent: Address) -> u32;
     fn update_score(env: Env, caller: Address, agent: Address, delta: i32);
+    fn get_score_history(env: Env, agent: Address) -> Vec<ScoreSample>;
+    fn log_action(env: Env, caller: Address, agent: Address, action: String, amount: u64);
+    fn get_default_score(env: Env) -> u32;
+}
+
+/// Price oracle trait for cross-contract calls
+#[contractclient(name = "PriceOracleClient")]
+pub trait PriceOracleInterface {
+    /// USD value of 1 XLM in 1e-7 USD (e.g. 1_000_000 = 0.10 USD)
+    fn xlm_usd_price(env: Env) -> u64;
 }
 
 #[contract]
@@ -69,15 +459,35 @@ pub struct LendingDemoContract;
 #[contractimpl]
 impl LendingDemoContract {
     /// Initialize the lending demo with references to DACTP protocol contracts
+    /// Utilization thresholds are in basis points: loans above the warning level
+    /// still go through but emit an event, loans above the hard cap are rejected.
+    /// `min_pool_balance` is a liquidity floor loans can never disburse below.
+    /// `price_oracle` optionally holds tier limits in USD (see `set_price_oracle`).
     pub fn initialize(
         env: Env,
         admin: Address,
         agent_manager_contract: Address,
         reputation_manager_contract: Address,
         xlm_token_contract: Address,
+        utilization_warning_bps: u32,
+        utilization_hard_cap_bps: u32,
+        min_pool_balance: u64,
+        price_oracle: Option<Address>,
     ) {
         admin.require_auth();
 
+        if env.storage().persistent().has(&DataKey::Initialized(())) {
+            panic!("Contract already initialized");
+        }
+        env.storage().persistent().set(&DataKey::Initialized(()), &true);
+
+        if utilization_hard_cap_bps > BPS_DENOMINATOR {
+            panic!("Utilization hard cap cannot exceed 100%");
+        }
+        if utilization_warning_bps > utilization_hard_cap_bps {
+            panic!("Utilization warning level cannot exceed the hard cap");
+        }
+
         env.storage()
             .persistent()
             .set(&DataKey::AgentManagerContract(()), &agent_manager_contract);
@@ -89,403 +499,5580 @@ impl LendingDemoContract {
         env.storage()
             .persistent()
             .set(&DataKey::XlmTokenContract(()), &xlm_token_contract);
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::AllowedToken(xlm_token_contract), &true);
             
         env.storage()
             .persistent()
             .set(&DataKey::Admin(()), &admin);
+
+        if let Some(price_oracle) = price_oracle {
+            env.storage().persistent().set(&DataKey::PriceOracle(()), &price_oracle);
+        }
+
+        let config = LendingConfig {
+            utilization_warning_bps,
+            utilization_hard_cap_bps,
+            tier_utilization_caps_bps: Vec::from_array(
+                &env,
+                [utilization_hard_cap_bps; REPUTATION_TIER_COUNT as usize],
+            ),
+            min_pool_balance,
+            max_single_loan_bps: BPS_DENOMINATOR,
+            max_total_outstanding: 0,
+            reputation_twap_window_seconds: 0,
+            max_loan_duration_seconds: MAX_LOAN_DURATION_SECONDS,
+            min_seconds_between_loans: 0,
+            grace_period_seconds: GRACE_PERIOD_SECONDS,
+            default_after_seconds: DEFAULT_AFTER_SECONDS,
+            default_penalty_schedule: Vec::from_array(
+                &env,
+                [
+                    REPUTATION_DECREASE_DEFAULT,
+                    REPUTATION_DECREASE_SECOND_DEFAULT,
+                    REPUTATION_DECREASE_REPEAT_DEFAULT,
+                ],
+            ),
+            scale_default_penalty: false,
+            default_recovery_points: 0,
+            lenient_reputation_fallback: false,
+            interest_compounding: false,
+            interest_period_seconds: 0,
+            min_interest: 0,
+            protocol_fee_bps: 0,
+            early_fee_waiver_bps: 0,
+            reserve_target: 0,
+            reserve_fill_bps: 0,
+            recovery_split: RecoverySplit {
+                pool_bps: BPS_DENOMINATOR,
+                reserve_bps: 0,
+                treasury_bps: 0,
+            },
+            rebate_policy: RebatePolicy {
+                rebate_bps: 0,
+                min_reputation: 0,
+                period_seconds: REBATE_PERIOD_SECONDS,
+            },
+            utilization_history_size: UTILIZATION_HISTORY_SIZE,
+            bonus_decay_window_seconds: 0,
+            bonus_decay_full_count: 0,
+            collateral_ltv_bps: Vec::from_array(
+                &env,
+                [TIER_1_LTV_BPS, TIER_2_LTV_BPS, TIER_3_LTV_BPS, TIER_4_LTV_BPS],
+            ),
+            early_payment_thresholds: Vec::from_array(
+                &env,
+                [EARLY_PAYMENT_THRESHOLD; REPUTATION_TIER_COUNT as usize],
+            ),
+        };
+        env.storage().persistent().set(&DataKey::Config(()), &config);
     }
 
-    /// Get the current XLM liquidity in the contract
-    /// This is simply the contract's XLM token balance
-    /// Note: To fund this contract, simply send XLM to the contract address
-    pub fn get_liquidity(env: Env) -> i128 {
-        let xlm_token: Address = env
-            .storage()
+    /// Get the lending configuration set at initialization
+    pub fn get_config(env: Env) -> LendingConfig {
+        env.storage()
             .persistent()
-            .get(&DataKey::XlmTokenContract(()))
-            .expect("Contract not initialized");
+            .get(&DataKey::Config(()))
+            .expect("Contract not initialized")
+    }
 
-        let xlm_client = token::Client::new(&env, &xlm_token);
-        xlm_client.balance(&env.current_contract_address())
+    /// Get which of the configured addresses are set, to verify a deployment in one call
+    /// Never panics, so a partially initialized contract shows exactly what is missing.
+    pub fn get_wiring(env: Env) -> Wiring {
+        let storage = env.storage().persistent();
+        Wiring {
+            agent_manager: storage.get(&DataKey::AgentManagerContract(())),
+            reputation_manager: storage.get(&DataKey::ReputationManagerContract(())),
+            xlm_token: storage.get(&DataKey::XlmTokenContract(())),
+            admin: storage.get(&DataKey::Admin(())),
+        }
     }
 
-    /// Request a loan as an agent with custom duration
-    /// This demonstrates REAL reputation-gated lending with ACTUAL XLM TRANSFERS
-    /// 
-    /// Requirements:
-    /// 1. Agent must be authorized via AgentManager
-    /// 2. Agent must have reputation >= 60 via ReputationManager
-    /// 3. Amount must be within agent's limits
-    /// 4. Contract must have sufficient XLM liquidity
-    pub fn request_loan(
+    /// Set the on-time grace and the default threshold, both measured from the due date
+    /// Repayment within the grace is on time, after it is late, and after the default
+    /// threshold it is penalized as a default. Only callable by admin.
+    pub fn set_default_windows(
         env: Env,
-        agent: Address,
-        amount: u64,
-        duration_seconds: u64, // Custom loan duration in seconds
-    ) -> bool {
-        // Get DACTP contract addresses
-        let agent_mgr_addr: Address = env
-            .storage()
-            .persistent()
-            .get(&DataKey::AgentManagerContract(()))
-            .expect("Contract not initialized");
+        admin: Address,
+        grace_period_seconds: u64,
+        default_after_seconds: u64,
+    ) {
+        require_admin(&env, &admin);
 
-        let rep_mgr_addr: Address = env
-            .storage()
-            .persistent()
-            .get(&DataKey::ReputationManagerContract(()))
-            .expect("Contract not initialized");
+        if default_after_seconds < grace_period_seconds {
+            panic!("Default threshold cannot be shorter than the grace period");
+        }
 
-        let xlm_token: Address = env
-            .storage()
-            .persistent()
-            .get(&DataKey::XlmTokenContract(()))
-            .expect("Contract not initialized");
+        let mut config = Self::get_config(env.clone());
+        config.grace_period_seconds = grace_period_seconds;
+        config.default_after_seconds = default_after_seconds;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
 
-        // Create clients for cross-contract calls
-        let agent_mgr_client = AgentManagerClient::new(&env, &agent_mgr_addr);
-        let rep_mgr_client = ReputationManagerClient::new(&env, &rep_mgr_addr);
-        let xlm_client = token::Client::new(&env, &xlm_token);
+    /// Set the longest term a loan can be issued for
+    /// Only callable by admin.
+    pub fn set_max_loan_duration(env: Env, admin: Address, max_loan_duration_seconds: u64) {
+        require_admin(&env, &admin);
 
-        // STEP 1: Basic authorization check
-        let action = String::from_str(&env, "borrow");
-        let is_authorized = agent_mgr_client.is_authorized(&agent, &action, &amount);
-        
-        if !is_authorized {
-            panic!("Agent not authorized or amount exceeds delegation limit");
+        if max_loan_duration_seconds == 0 {
+            panic!("Maximum loan duration must be positive");
         }
 
-        // STEP 2: Get reputation score and calculate risk tier
-        let reputation_score = rep_mgr_client.get_score(&agent);
-        
-        // STEP 3: ENHANCED RISK ASSESSMENT - Calculate maximum allowed loan
-        let max_allowed_loan = calculate_max_loan_amount(reputation_score);
-        
-        if amount > max_allowed_loan {
-            panic!("Loan amount exceeds reputation-based limit");
-        }
+        let mut config = Self::get_config(env.clone());
+        config.max_loan_duration_seconds = max_loan_duration_seconds;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
 
-        // STEP 4: Pool utilization check (prevent over-lending)
-        let total_liquidity = xlm_client.balance(&env.current_contract_address()) as u64;
-        let current_utilization = calculate_pool_utilization(&env, total_liquidity);
-        
-        if current_utilization > MAX_POOL_UTILIZATION {
-            panic!("Lending pool utilization too high - try again later");
-        }
+    /// Set how long an agent must wait after a loan closes before borrowing again
+    /// Stops agents churning loans to farm repayment bonuses or probe limits; 0 disables
+    /// the cooldown. Only callable by admin.
+    pub fn set_loan_cooldown(env: Env, admin: Address, min_seconds_between_loans: u64) {
+        require_admin(&env, &admin);
 
-        // STEP 5: Check for existing active loans
-        let loan_key = DataKey::Loan(agent.clone());
-        let existing_loan: Option<Loan> = env.storage().persistent().get(&loan_key);
-        
-        if let Some(loan) = existing_loan {
-            if !loan.repaid {
-                panic!("Agent already has an active loan");
+        let mut config = Self::get_config(env.clone());
+        config.min_seconds_between_loans = min_seconds_between_loans;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Get the earliest time the agent may take its next loan under the cooldown
+    /// A time at or before now means it can borrow already (0 if it never repaid a loan).
+    pub fn next_borrow_time(env: Env, agent: Address) -> u64 {
+        match Self::get_agent_state(env.clone(), agent).last_loan_closed_at {
+            Some(closed_at) => {
+                closed_at.saturating_add(Self::get_config(env).min_seconds_between_loans)
             }
+            None => 0,
         }
+    }
 
-        // STEP 6: Final liquidity check
-        if total_liquidity < amount {
-            panic!("Insufficient liquidity in lending pool");
+    /// Cap any single loan at a share of the pool's lendable liquidity
+    /// Applies below the utilization cap too, so one loan can't dominate the pool.
+    /// Only callable by admin.
+    pub fn set_max_single_loan_bps(env: Env, admin: Address, max_single_loan_bps: u32) {
+        require_admin(&env, &admin);
+
+        if max_single_loan_bps == 0 || max_single_loan_bps > BPS_DENOMINATOR {
+            panic!("Single loan share must be between 1 and 10000 basis points");
         }
 
-        // STEP 7: Create loan with enhanced tracking
-        let current_time = env.ledger().timestamp();
-        let due_date = current_time + duration_seconds;
-        
-        let loan = Loan {
-            agent: agent.clone(),
-            amount,
-            repaid: false,
-            due_date,
-            created_at: current_time,
-        };
+        let mut config = Self::get_config(env.clone());
+        config.max_single_loan_bps = max_single_loan_bps;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
 
-        env.storage().persistent().set(&loan_key, &loan);
+    /// Cap the principal lent out across the whole pool, regardless of its size
+    /// A risk limit for early operation; 0 removes the cap. Only callable by admin.
+    pub fn set_max_total_outstanding(env: Env, admin: Address, max_total_outstanding: u64) {
+        require_admin(&env, &admin);
 
-        // STEP 8: Execute the loan transfer
-        xlm_client.transfer(&env.current_contract_address(), &agent, &(amount as i128));
+        let mut config = Self::get_config(env.clone());
+        config.max_total_outstanding = max_total_outstanding;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
 
-        true
+    /// Get the largest amount a single loan may take from the pool right now
+    pub fn max_single_loan(env: Env) -> u64 {
+        let bps = Self::get_config(env.clone()).max_single_loan_bps;
+        let free_liquidity = Self::get_lendable_liquidity(env);
+        ((free_liquidity as u128) * (bps as u128) / (BPS_DENOMINATOR as u128)) as u64
     }
 
-    /// Enhanced repay loan with automatic default checking
-    /// This implements realistic lending incentives with automatic penalty detection
-    pub fn repay_loan(env: Env, agent: Address) {
-        // Get DACTP contract addresses
-        let agent_mgr_addr: Address = env
-            .storage()
-            .persistent()
-            .get(&DataKey::AgentManagerContract(()))
-            .expect("Contract not initialized");
+    /// Set the XLM/USD price oracle, or None to go back to fixed stroop limits
+    /// With an oracle, tier limits are held in USD cents and converted to stroops at
+    /// the current price, so they keep their real value as XLM moves. Only callable by admin.
+    pub fn set_price_oracle(env: Env, admin: Address, oracle: Option<Address>) {
+        require_admin(&env, &admin);
 
-        let rep_mgr_addr: Address = env
-            .storage()
-            .persistent()
-            .get(&DataKey::ReputationManagerContract(()))
-            .expect("Contract not initialized");
+        match oracle {
+            Some(oracle) => env.storage().persistent().set(&DataKey::PriceOracle(()), &oracle),
+            None => env.storage().persistent().remove(&DataKey::PriceOracle(())),
+        }
+    }
 
-        let xlm_token: Address = env
-            .storage()
-            .persistent()
-            .get(&DataKey::XlmTokenContract(()))
-            .expect("Contract not initialized");
+    /// Get the XLM/USD price oracle, if one is configured
+    pub fn get_price_oracle(env: Env) -> Option<Address> {
+        env.storage().persistent().get(&DataKey::PriceOracle(()))
+    }
 
-        // Create clients for cross-contract calls
-        let agent_mgr_client = AgentManagerClient::new(&env, &agent_mgr_addr);
-        let rep_mgr_client = ReputationManagerClient::new(&env, &rep_mgr_addr);
-        let xlm_client = token::Client::new(&env, &xlm_token);
+    /// Size loans off the time-weighted average reputation over a trailing window
+    /// Lending uses the lower of the spot score and the average, so a sudden spike
+    /// does not immediately unlock a larger loan. A window of 0 uses the spot score.
+    /// Only callable by admin.
+    pub fn set_reputation_twap_window(env: Env, admin: Address, window_seconds: u64) {
+        require_admin(&env, &admin);
 
-        // Get loan information
-        let loan_key = DataKey::Loan(agent.clone());
-        let mut loan: Loan = env
-            .storage()
-            .persistent()
-            .get(&loan_key)
-            .expect("No active loan found");
+        let mut config = Self::get_config(env.clone());
+        config.reputation_twap_window_seconds = window_seconds;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
 
-        if loan.repaid {
-            panic!("Loan already repaid");
-        }
+    /// Set the escalating reputation penalties for an agent's successive defaults
+    /// Entry N applies to the (N+1)th default; the last entry applies to every later one.
+    /// Only callable by admin.
+    pub fn set_default_penalty_schedule(env: Env, admin: Address, schedule: Vec<i32>) {
+        require_admin(&env, &admin);
 
-        // DACTP CHECK: Verify agent is authorized for "repay_loan" action
-        let action = String::from_str(&env, "repay_loan");
-        let is_authorized = agent_mgr_client.is_authorized(&agent, &action, &loan.amount);
-        
-        if !is_authorized {
-            panic!("Agent not authorized to repay");
+        if schedule.is_empty() {
+            panic!("Penalty schedule cannot be empty");
+        }
+        for penalty in schedule.iter() {
+            if penalty > 0 {
+                panic!("Default penalties cannot be positive");
+            }
         }
 
-        // ACTUAL XLM TRANSFER: Receive XLM repayment from agent to contract
-        agent.require_auth();
-        xlm_client.transfer(&agent, &env.current_contract_address(), &(loan.amount as i128));
+        let mut config = Self::get_config(env.clone());
+        config.default_penalty_schedule = schedule;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
 
-        // Mark loan as repaid
-        loan.repaid = true;
-        env.storage().persistent().set(&loan_key, &loan);
+    /// Restore some reputation when an agent repays a defaulted loan after all
+    /// Must be smaller than the first default penalty, so repaying never fully erases a
+    /// default. 0 disables recovery. Only callable by admin.
+    pub fn set_default_recovery(env: Env, admin: Address, points: u32) {
+        require_admin(&env, &admin);
 
-        // ENHANCED REPUTATION UPDATE: Time-based bonuses/penalties with automatic default detection
-        let current_time = env.ledger().timestamp();
-        let contract_addr = env.current_contract_address();
-        
-        let reputation_delta = if current_time > loan.due_date + GRACE_PERIOD_SECONDS {
-            // AUTOMATIC DEFAULT PENALTY: Loan was overdue beyond grace period
-            REPUTATION_DECREASE_DEFAULT // -25 reputation
-        } else if current_time <= loan.due_date - EARLY_PAYMENT_THRESHOLD {
-            // Early payment bonus
-            REPUTATION_INCREASE_EARLY // +12 reputation
-        } else if current_time <= loan.due_date + GRACE_PERIOD_SECONDS {
-            // On-time payment (including grace period)
-            REPUTATION_INCREASE_ON_TIME // +8 reputation
-        } else {
-            // Late payment penalty (within grace period)
-            REPUTATION_DECREASE_LATE // -5 reputation
-        };
+        let mut config = Self::get_config(env.clone());
+        let first_penalty = config.default_penalty_schedule.get(0).unwrap_or(0);
+        if points > 0 && points as i64 >= -(first_penalty as i64) {
+            panic!("Recovery must be smaller than the default penalty");
+        }
 
-        rep_mgr_client.update_score(&contract_addr, &agent, &reputation_delta);
+        config.default_recovery_points = points;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
     }
 
-    /// Report a loan default (missed repayment beyond grace period)
-    /// This triggers a REAL reputation penalty (-25) - much harsher than before
-    /// 
-    /// ✅ NEW: Can be called by anyone (not just admin) for automatic penalty system
-    /// ✅ NEW: Prevents duplicate penalties with tracking
-    pub fn report_default(env: Env, agent: Address) {
-        let rep_mgr_addr: Address = env
-            .storage()
-            .persistent()
-            .get(&DataKey::ReputationManagerContract(()))
-            .expect("Contract not initialized");
+    /// Set the loan-to-value allowed against collateral for reputation tiers 1 through 4
+    /// Only callable by admin.
+    pub fn set_collateral_ltv_schedule(env: Env, admin: Address, ltv_bps: Vec<u32>) {
+        require_admin(&env, &admin);
 
-        let rep_mgr_client = ReputationManagerClient::new(&env, &rep_mgr_addr);
+        if ltv_bps.len() != REPUTATION_TIER_COUNT {
+            panic!("LTV schedule needs one entry per reputation tier");
+        }
+        for ltv in ltv_bps.iter() {
+            if ltv == 0 || ltv > BPS_DENOMINATOR {
+                panic!("LTV must be between 1 and 10000 basis points");
+            }
+        }
 
-        // Verify loan exists and is overdue
-        let loan_key = DataKey::Loan(agent.clone());
-        let loan: Loan = env
-            .storage()
-            .persistent()
-            .get(&loan_key)
-            .expect("No loan found for agent");
+        let mut config = Self::get_config(env.clone());
+        config.collateral_ltv_bps = ltv_bps;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
 
-        if loan.repaid {
+    /// Set how long before the due date repayment earns the early bonus, for tiers 1 through 4
+    /// A shorter lead time widens the early-bonus window, e.g. as a perk for higher tiers.
+    /// A loan uses the threshold of the tier it was issued at. Only callable by admin.
+    pub fn set_early_payment_thresholds(env: Env, admin: Address, thresholds: Vec<u64>) {
+        require_admin(&env, &admin);
+
+        if thresholds.len() != REPUTATION_TIER_COUNT {
+            panic!("Early payment thresholds need one entry per reputation tier");
+        }
+
+        let mut config = Self::get_config(env.clone());
+        config.early_payment_thresholds = thresholds;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Set the pool utilization each reputation tier may borrow up to, for tiers 1 through 4
+    /// Lets high-reputation agents keep borrowing from a busier pool than low-reputation
+    /// ones. Every tier starts at the hard cap set at initialization. Only callable by admin.
+    pub fn set_tier_utilization_caps(env: Env, admin: Address, caps_bps: Vec<u32>) {
+        require_admin(&env, &admin);
+
+        if caps_bps.len() != REPUTATION_TIER_COUNT {
+            panic!("Utilization caps need one entry per reputation tier");
+        }
+        for cap in caps_bps.iter() {
+            if cap > BPS_DENOMINATOR {
+                panic!("Utilization cap cannot exceed 100%");
+            }
+        }
+
+        let mut config = Self::get_config(env.clone());
+        config.tier_utilization_caps_bps = caps_bps;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Allow a token contract to be used for lending
+    /// Only callable by admin.
+    pub fn allow_token(env: Env, admin: Address, token: Address) {
+        require_admin(&env, &admin);
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::AllowedToken(token), &true);
+    }
+
+    /// Stop a token contract from being used for lending
+    /// Outstanding loans can still be repaid. Only callable by admin.
+    pub fn disallow_token(env: Env, admin: Address, token: Address) {
+        require_admin(&env, &admin);
+
+        env.storage()
+            .persistent()
+            .remove(&DataKey::AllowedToken(token));
+    }
+
+    /// Check whether a token contract may be used for lending
+    pub fn is_token_allowed(env: Env, token: Address) -> bool {
+        env.storage()
+            .persistent()
+            .get(&DataKey::AllowedToken(token))
+            .unwrap_or(false)
+    }
+
+    /// Exempt a critical system agent from the pool utilization cap
+    /// Liquidity, pool floor and tier limits still apply. Only callable by admin.
+    pub fn add_privileged_borrower(env: Env, admin: Address, agent: Address) {
+        require_admin(&env, &admin);
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::PrivilegedBorrower(agent), &true);
+    }
+
+    /// Subject a previously privileged agent to the utilization cap again
+    /// Only callable by admin.
+    pub fn remove_privileged_borrower(env: Env, admin: Address, agent: Address) {
+        require_admin(&env, &admin);
+
+        env.storage()
+            .persistent()
+            .remove(&DataKey::PrivilegedBorrower(agent));
+    }
+
+    /// Check whether an agent may borrow past the pool utilization cap
+    pub fn is_privileged_borrower(env: Env, agent: Address) -> bool {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PrivilegedBorrower(agent))
+            .unwrap_or(false)
+    }
+
+    /// Bar an agent from taking new loans, or lift the bar
+    /// Only lending is affected: the agent keeps its AgentManager authority and can
+    /// still repay an open loan. Only callable by admin.
+    pub fn set_borrow_block(env: Env, admin: Address, agent: Address, blocked: bool) {
+        require_admin(&env, &admin);
+
+        let key = DataKey::BorrowBlocked(agent);
+        if blocked {
+            env.storage().persistent().set(&key, &true);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+    }
+
+    /// Check whether an agent is barred from taking new loans
+    pub fn is_borrow_blocked(env: Env, agent: Address) -> bool {
+        env.storage()
+            .persistent()
+            .get(&DataKey::BorrowBlocked(agent))
+            .unwrap_or(false)
+    }
+
+    /// Scale default penalties by the defaulted principal relative to the loan's tier limit
+    /// Defaulting the full tier limit costs the full scheduled penalty, smaller
+    /// defaults proportionally less (but at least 1 point). Only callable by admin.
+    pub fn set_default_penalty_scaling(env: Env, admin: Address, enabled: bool) {
+        require_admin(&env, &admin);
+
+        let mut config = Self::get_config(env.clone());
+        config.scale_default_penalty = enabled;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Choose how `request_loan` behaves when the agent's reputation cannot be fetched
+    /// Strict (the default) reverts with `LendingError::ReputationUnavailable`. Lenient
+    /// checks authorization alone and treats the agent as having the baseline score.
+    /// Only callable by admin.
+    pub fn set_reputation_fallback(env: Env, admin: Address, lenient: bool) {
+        require_admin(&env, &admin);
+
+        let mut config = Self::get_config(env.clone());
+        config.lenient_reputation_fallback = lenient;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Choose how interest accrues on loans
+    /// With a period of 0 every loan pays flat interest (the default); otherwise the rate
+    /// applies per period, accruing linearly or compounding each whole period.
+    /// Only callable by admin.
+    pub fn set_interest_mode(env: Env, admin: Address, compounding: bool, period_seconds: u64) {
+        require_admin(&env, &admin);
+
+        let mut config = Self::get_config(env.clone());
+        config.interest_compounding = compounding;
+        config.interest_period_seconds = period_seconds;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Set the least interest an interest-bearing loan owes
+    /// Stops small or very short loans whose interest would round down to nothing from
+    /// being effectively free. Applies to loans issued from now on; 0 disables the floor.
+    /// Only callable by admin.
+    pub fn set_min_interest(env: Env, admin: Address, min_interest: u64) {
+        require_admin(&env, &admin);
+
+        let mut config = Self::get_config(env.clone());
+        config.min_interest = min_interest;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Get the least interest a newly issued interest-bearing loan owes
+    pub fn min_interest(env: Env) -> u64 {
+        Self::get_config(env).min_interest
+    }
+
+    /// Set the share of repaid interest kept as protocol fees
+    /// Fees stay in the contract but are excluded from lendable liquidity until
+    /// withdrawn with `withdraw_fees`. Only callable by admin.
+    pub fn set_protocol_fee(env: Env, admin: Address, fee_bps: u32) {
+        require_admin(&env, &admin);
+
+        if fee_bps > BPS_DENOMINATOR {
+            panic!("Protocol fee cannot exceed 100%");
+        }
+
+        let mut config = Self::get_config(env.clone());
+        config.protocol_fee_bps = fee_bps;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Waive a share of the protocol fee for repayments that earn the early bonus
+    /// The waived fee comes off what the agent pays; the pool's share of the interest
+    /// is unaffected. Only callable by admin.
+    pub fn set_early_fee_waiver(env: Env, admin: Address, waiver_bps: u32) {
+        require_admin(&env, &admin);
+
+        if waiver_bps > BPS_DENOMINATOR {
+            panic!("Fee waiver cannot exceed 100%");
+        }
+
+        let mut config = Self::get_config(env.clone());
+        config.early_fee_waiver_bps = waiver_bps;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Set how repayments of defaulted loans are divided between the pool, reserve and treasury
+    /// Applies to what remains after the usual fee and reserve share of the interest;
+    /// the reserve share is added regardless of the reserve target. Only callable by admin.
+    pub fn set_recovery_split(
+        env: Env,
+        admin: Address,
+        pool_bps: u32,
+        reserve_bps: u32,
+        treasury_bps: u32,
+    ) {
+        require_admin(&env, &admin);
+
+        if pool_bps as u64 + reserve_bps as u64 + treasury_bps as u64 != BPS_DENOMINATOR as u64 {
+            panic!("Recovery split must sum to 10000 basis points");
+        }
+
+        let mut config = Self::get_config(env.clone());
+        config.recovery_split = RecoverySplit { pool_bps, reserve_bps, treasury_bps };
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Return `rebate_bps` of the interest borrowers paid over each period to those whose
+    /// reputation is at least `min_reputation` when rebates are distributed
+    /// A rebate share of 0 turns rebates off. Only callable by admin.
+    pub fn set_rebate_policy(
+        env: Env,
+        admin: Address,
+        rebate_bps: u32,
+        min_reputation: u32,
+        period_seconds: u64,
+    ) {
+        require_admin(&env, &admin);
+
+        if rebate_bps > BPS_DENOMINATOR {
+            panic!("Rebate share cannot exceed 10000 basis points");
+        }
+
+        let mut config = Self::get_config(env.clone());
+        config.rebate_policy = RebatePolicy { rebate_bps, min_reputation, period_seconds };
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Get the interest an agent has paid since the last rebate distribution
+    pub fn get_interest_paid(env: Env, agent: Address) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::InterestPaid(agent))
+            .unwrap_or(0)
+    }
+
+    /// Top the reserve up from interest income until it reaches `target`
+    /// Each repayment routes `fill_bps` of its interest (after protocol fees) to the
+    /// reserve; once the target is met the interest stays in the pool. Only callable by admin.
+    pub fn set_reserve_policy(env: Env, admin: Address, target: u64, fill_bps: u32) {
+        require_admin(&env, &admin);
+
+        if fill_bps > BPS_DENOMINATOR {
+            panic!("Reserve fill share cannot exceed 100%");
+        }
+
+        let mut config = Self::get_config(env.clone());
+        config.reserve_target = target;
+        config.reserve_fill_bps = fill_bps;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Get the reserve's progress toward its target in basis points (10000 once met)
+    pub fn reserve_fill_progress(env: Env) -> u32 {
+        let target = Self::get_config(env.clone()).reserve_target;
+        if target == 0 {
+            return BPS_DENOMINATOR;
+        }
+        calculate_pool_utilization_bps(get_reserve_balance(&env).min(target), target)
+    }
+
+    /// Pay out all accrued protocol fees to `to`
+    /// Only callable by admin. Returns the amount paid.
+    pub fn withdraw_fees(env: Env, admin: Address, to: Address) -> u64 {
+        require_admin(&env, &admin);
+
+        let fees = Self::get_accrued_fees(env.clone());
+        if fees == 0 {
+            return 0;
+        }
+
+        let xlm_token: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::XlmTokenContract(()))
+            .expect("Contract not initialized");
+
+        env.storage().persistent().set(&DataKey::AccruedFees(()), &0u64);
+        token::Client::new(&env, &xlm_token).transfer(
+            &env.current_contract_address(),
+            &to,
+            &(fees as i128),
+        );
+
+        fees
+    }
+
+    /// Get the protocol fees accrued and not yet withdrawn
+    pub fn get_accrued_fees(env: Env) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::AccruedFees(()))
+            .unwrap_or(0)
+    }
+
+    /// Diminish repayment bonuses for agents that collect them faster than a set rate
+    /// Within any `window_seconds`, the first `full_count` bonuses are awarded in full;
+    /// the n-th after that is scaled by full_count / n. A window of 0 disables decay.
+    /// Only callable by admin.
+    pub fn set_bonus_decay(env: Env, admin: Address, window_seconds: u64, full_count: u32) {
+        require_admin(&env, &admin);
+
+        let mut config = Self::get_config(env.clone());
+        config.bonus_decay_window_seconds = window_seconds;
+        config.bonus_decay_full_count = full_count;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+    }
+
+    /// Get the current XLM liquidity in the contract
+    /// This is simply the contract's XLM token balance
+    /// Note: To fund this contract, simply send XLM to the contract address
+    pub fn get_liquidity(env: Env) -> i128 {
+        let xlm_token: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::XlmTokenContract(()))
+            .expect("Contract not initialized");
+
+        let xlm_client = token::Client::new(&env, &xlm_token);
+        xlm_client.balance(&env.current_contract_address())
+    }
+
+    /// Get the liquidity available to lend: the balance minus accrued protocol fees,
+    /// the reserve and the minimum pool balance
+    pub fn get_lendable_liquidity(env: Env) -> u64 {
+        let xlm_token: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::XlmTokenContract(()))
+            .expect("Contract not initialized");
+
+        let xlm_client = token::Client::new(&env, &xlm_token);
+        pool_balance(&env, &xlm_client).saturating_sub(Self::get_config(env).min_pool_balance)
+    }
+
+    /// Deposit XLM into the pool in exchange for shares of its value
+    /// Shares are priced at the pool's current value (liquidity plus principal lent out),
+    /// so repaid interest raises what every share redeems for. Liquidity present before
+    /// the first deposit is credited to the admin as shares. Returns the shares minted.
+    pub fn deposit(env: Env, lender: Address, amount: u64) -> u64 {
+        lender.require_auth();
+
+        if amount == 0 {
+            panic!("Deposit amount must be positive");
+        }
+
+        let mut total_shares = get_total_shares(&env);
+        let pool_value = get_pool_value(&env);
+        if total_shares == 0 && pool_value > 0 {
+            let admin: Address = env
+                .storage()
+                .persistent()
+                .get(&DataKey::Admin(()))
+                .expect("Contract not initialized");
+            set_lender_shares(&env, &admin, Self::get_lender_shares(env.clone(), admin.clone()) + pool_value);
+            total_shares = pool_value;
+        }
+
+        let minted = if total_shares == 0 {
+            amount
+        } else {
+            ((amount as u128) * (total_shares as u128) / (pool_value as u128)) as u64
+        };
+
+        let xlm_token: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::XlmTokenContract(()))
+            .expect("Contract not initialized");
+        token::Client::new(&env, &xlm_token).transfer(
+            &lender,
+            env.current_contract_address(),
+            &(amount as i128),
+        );
+
+        // Every deposit restarts the liquidity bonus clock, so a top-up cannot inherit
+        // the holding time of an earlier, smaller deposit
+        let held = Self::get_lender_shares(env.clone(), lender.clone());
+        env.storage()
+            .persistent()
+            .set(&DataKey::LiquiditySince(lender.clone()), &env.ledger().timestamp());
+        set_lender_shares(&env, &lender, held + minted);
+        env.storage()
+            .persistent()
+            .set(&DataKey::TotalShares(()), &(total_shares + minted));
+
+        minted
+    }
+
+    /// Redeem pool shares for their current value in XLM
+    /// Only idle liquidity can be paid out; principal lent out returns as loans are repaid.
+    /// Lower-reputation lenders are limited per period: a larger redemption is capped at
+    /// `max_withdrawal` and only the shares needed for it are burned. Returns the XLM paid.
+    pub fn withdraw(env: Env, lender: Address, shares: u64) -> u64 {
+        lender.require_auth();
+
+        let held = Self::get_lender_shares(env.clone(), lender.clone());
+        if shares == 0 || shares > held {
+            panic!("Invalid share amount");
+        }
+
+        let total_shares = get_total_shares(&env);
+        let pool_value = get_pool_value(&env);
+        let mut shares = shares;
+        let mut amount = ((shares as u128) * (pool_value as u128) / (total_shares as u128)) as u64;
+
+        let (allowance, mut window) = withdrawal_allowance(&env, &lender);
+        if amount > allowance {
+            if allowance == 0 {
+                panic!("Withdrawal limit for this period reached");
+            }
+            amount = allowance;
+            shares = ((amount as u128) * (total_shares as u128))
+                .div_ceil(pool_value as u128)
+                .min(shares as u128) as u64;
+        }
+        window.withdrawn += amount;
+        env.storage()
+            .persistent()
+            .set(&DataKey::WithdrawalWindow(lender.clone()), &window);
+
+        let xlm_token: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::XlmTokenContract(()))
+            .expect("Contract not initialized");
+        let xlm_client = token::Client::new(&env, &xlm_token);
+        if pool_balance(&env, &xlm_client) < amount {
+            panic!("Insufficient liquidity to withdraw");
+        }
+
+        set_lender_shares(&env, &lender, held - shares);
+
+        // Any withdrawal restarts the liquidity reward clock
+        let since_key = DataKey::LiquiditySince(lender.clone());
+        if held == shares {
+            env.storage().persistent().remove(&since_key);
+        } else {
+            env.storage().persistent().set(&since_key, &env.ledger().timestamp());
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::TotalShares(()), &(total_shares - shares));
+        xlm_client.transfer(&env.current_contract_address(), &lender, &(amount as i128));
+
+        amount
+    }
+
+    /// Get the most XLM a lender can withdraw right now
+    /// Lenders below reputation tier 3 may withdraw only a share of their position
+    /// (counting what they already withdrew) each period; others their whole position.
+    pub fn max_withdrawal(env: Env, provider: Address) -> u64 {
+        withdrawal_allowance(&env, &provider).0
+    }
+
+    /// Get the pool shares a lender holds
+    pub fn get_lender_shares(env: Env, lender: Address) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::LenderShares(lender))
+            .unwrap_or(0)
+    }
+
+    /// Request a loan as an agent with custom duration
+    /// This demonstrates REAL reputation-gated lending with ACTUAL XLM TRANSFERS
+    /// 
+    /// Requirements:
+    /// 1. Agent must be authorized via AgentManager
+    /// 2. Agent must have reputation >= 60 via ReputationManager
+    /// 3. Amount must be within agent's limits
+    /// 4. Contract must have sufficient XLM liquidity
+    ///
+    /// Returns a receipt with the new loan's id, disbursed amount, due date and rate
+    pub fn request_loan(
+        env: Env,
+        agent: Address,
+        amount: u64,
+        duration_seconds: u64, // Custom loan duration in seconds
+    ) -> LoanReceipt {
+        reject_protocol_address(&env, &agent);
+
+        // Get DACTP contract addresses, refusing to lend through a broken wiring
+        let (agent_mgr_addr, rep_mgr_addr, xlm_token) = require_wiring(&env);
+
+        if !Self::is_token_allowed(env.clone(), xlm_token.clone()) {
+            panic!("Token not allowed for lending");
+        }
+
+        // Create clients for cross-contract calls
+        let agent_mgr_client = AgentManagerClient::new(&env, &agent_mgr_addr);
+        let xlm_client = token::Client::new(&env, &xlm_token);
+
+        // STEP 1: Basic authorization check (the AgentManager also returns the score)
+        let action = String::from_str(&env, "borrow");
+        let scored = agent_mgr_client.try_authorize_and_score(&agent, &action, &amount);
+        let (is_authorized, score) = match scored {
+            Ok(Ok(result)) => result,
+            // An AgentManager with no ReputationManager is a wiring problem, not an outage
+            _ if !matches!(agent_mgr_client.try_get_reputation_manager(), Ok(Ok(Some(_)))) => {
+                panic_with_error!(&env, LendingError::Misconfigured)
+            }
+            _ if Self::get_config(env.clone()).lenient_reputation_fallback => (
+                agent_mgr_client.is_authorized(&agent, &action, &amount),
+                match ReputationManagerClient::new(&env, &rep_mgr_addr).try_get_default_score() {
+                    Ok(Ok(default_score)) => default_score,
+                    _ => REPUTATION_BASELINE,
+                },
+            ),
+            _ => panic_with_error!(&env, LendingError::ReputationUnavailable),
+        };
+        
+        if !is_authorized {
+            panic!("Agent not authorized or amount exceeds delegation limit");
+        }
+
+        // Re-check the live registration too, so a disabled agent is refused even if
+        // the authorization logic drifts
+        let agent_info = agent_mgr_client
+            .get_agent_info(&agent)
+            .expect("Agent not registered");
+        if agent_info.revoked {
+            panic!("Agent has been revoked");
+        }
+
+        if Self::is_borrow_blocked(env.clone(), agent.clone()) {
+            panic!("Agent is blocked from borrowing");
+        }
+
+        // The owner's budget is shared by all its agents
+        if amount > Self::owner_remaining_budget(env.clone(), agent_info.owner) {
+            panic!("Loan would exceed the owner's borrowing budget");
+        }
+
+        // STEP 2: Smooth the score, apply any endorsement boost and calculate risk tier
+        let reputation_score = lending_score(&env, &agent, score);
+        
+        // STEP 3: ENHANCED RISK ASSESSMENT - Calculate maximum allowed loan
+        let mut agent_state = Self::get_agent_state(env.clone(), agent.clone());
+        let max_allowed_loan = calculate_max_loan_amount(
+            &env,
+            reputation_score,
+            good_standing_duration(&env, &agent_state),
+        );
+        
+        if amount > max_allowed_loan {
+            panic!("Loan amount exceeds reputation-based limit");
+        }
+
+        // STEP 4: Pool utilization check (prevent over-lending)
+        // Warn above the soft threshold, only reject loans that would take utilization past
+        // the agent's tier cap; landing exactly on it is allowed (privileged system agents
+        // are exempt from the cap)
+        let config = Self::get_config(env.clone());
+        let utilization_cap = tier_utilization_cap(&config, reputation_tier(reputation_score));
+        let total_liquidity = pool_balance(&env, &xlm_client);
+        let outstanding = get_total_outstanding(&env);
+        let projected_utilization =
+            calculate_pool_utilization_bps(outstanding + amount, total_liquidity + outstanding);
+
+        // Compared unrounded, so a loan a fraction of a basis point past the cap is refused
+        let crosses_cap = ((outstanding + amount) as u128) * (BPS_DENOMINATOR as u128)
+            > (utilization_cap as u128) * ((total_liquidity + outstanding) as u128);
+        if crosses_cap
+            && !Self::is_privileged_borrower(env.clone(), agent.clone())
+        {
+            panic!("Lending pool utilization too high - try again later");
+        }
+
+        if config.max_total_outstanding > 0 && outstanding + amount > config.max_total_outstanding {
+            panic!("Loan would exceed the pool's total outstanding cap");
+        }
+
+        if projected_utilization > config.utilization_warning_bps {
+            HighUtilization {
+                agent: agent.clone(),
+                utilization_bps: projected_utilization,
+                warning_bps: config.utilization_warning_bps,
+            }
+            .publish(&env);
+        }
+
+        // STEP 5: Check for existing active loans
+        let loan_key = DataKey::Loan(agent.clone());
+        let existing_loan: Option<Loan> = env.storage().persistent().get(&loan_key);
+        
+        if let Some(loan) = existing_loan {
+            if !loan.repaid {
+                panic!("Agent already has an active loan");
+            }
+        }
+
+        if env.ledger().timestamp() < Self::next_borrow_time(env.clone(), agent.clone()) {
+            panic!("Loan cooldown has not elapsed since the previous loan");
+        }
+
+        // STEP 6: Final liquidity check
+        if total_liquidity < amount {
+            panic!("Insufficient liquidity in lending pool");
+        }
+
+        if total_liquidity - amount < config.min_pool_balance {
+            panic!("Loan would take the pool below its minimum balance");
+        }
+
+        if amount > Self::max_single_loan(env.clone()) {
+            panic!("Loan exceeds the per-loan share of pool liquidity");
+        }
+
+        // HOLD: Reserve the amount against outstanding as soon as the request is eligible,
+        // so any later liquidity check sees the reduced headroom. If the transfer below
+        // fails, the whole invocation reverts and the hold is released with it.
+        set_total_outstanding(&env, outstanding + amount);
+
+        // STEP 7: Create loan with enhanced tracking
+        let current_time = env.ledger().timestamp();
+        let due_date = loan_due_date(&env, &config, duration_seconds);
+        
+        let loan_id = next_loan_id(&env);
+        env.storage()
+            .persistent()
+            .set(&DataKey::NextLoanId(()), &(loan_id + 1));
+
+        // Interest is only owed once the agent has used its interest-free first loan
+        let pays_interest = agent_state.had_first_loan;
+        let tier = reputation_tier(reputation_score);
+        let mut loan = Loan {
+            loan_id,
+            agent: agent.clone(),
+            amount,
+            repaid: false,
+            due_date,
+            created_at: current_time,
+            tier,
+            terms: LoanTerms {
+                max_loan: max_allowed_loan,
+                tier_limit: tier_max_loan(&env, tier),
+                interest_bps: if pays_interest { LOAN_INTEREST_BPS as u32 } else { 0 },
+                min_interest: config.min_interest,
+                grace_period_seconds: config.grace_period_seconds,
+                default_after_seconds: config.default_after_seconds,
+                early_payment_threshold: early_payment_threshold(&config, tier),
+            },
+            booked_interest: 0,
+        };
+        loan.booked_interest = interest_at_due_date(&env, &loan);
+
+        let mut borrowers = get_borrowers(&env);
+        if !borrowers.contains(&agent) {
+            borrowers.push_back(agent.clone());
+            env.storage().persistent().set(&DataKey::Borrowers(()), &borrowers);
+        }
+
+        save_loan(&env, &loan);
+        set_outstanding_interest(&env, get_outstanding_interest(&env) + loan.booked_interest);
+
+        // Start the good-standing clock on the agent's first borrow
+        if agent_state.good_standing_since.is_none() {
+            agent_state.good_standing_since = Some(current_time);
+        }
+
+        // Track the heaviest draw on the agent's credit line for discipline rewards
+        let drawn_bps = calculate_pool_utilization_bps(amount, max_allowed_loan);
+        agent_state.peak_utilization_bps = agent_state.peak_utilization_bps.max(drawn_bps);
+        set_agent_state(&env, &agent, &agent_state);
+
+        // STEP 8: Execute the loan transfer
+        xlm_client.transfer(&env.current_contract_address(), &agent, &(amount as i128));
+        log_action(&env, &agent, "borrow", amount);
+
+        LoanReceipt {
+            loan_id,
+            amount,
+            due_date,
+            interest_bps: loan.terms.interest_bps,
+        }
+    }
+
+    /// Request a loan sized down to what the pool can fund
+    /// Disburses min(requested, tier limit, free liquidity above the pool floor,
+    /// utilization headroom)
+    /// through the normal `request_loan` flow and returns the amount issued.
+    /// Rejects when the fundable amount is below the minimum loan size.
+    pub fn request_loan_best_effort(
+        env: Env,
+        agent: Address,
+        max_amount: u64,
+        duration_seconds: u64,
+    ) -> u64 {
+        let status = Self::get_pool_status(env.clone());
+        let config = Self::get_config(env.clone());
+
+        // Principal that can go out before utilization reaches the agent's tier cap
+        let tier = reputation_tier(Self::get_effective_reputation(env.clone(), agent.clone()));
+        let total_pool = (status.total_liquidity + status.total_outstanding) as u128;
+        let max_outstanding = (total_pool * tier_utilization_cap(&config, tier) as u128
+            / BPS_DENOMINATOR as u128) as u64;
+        let mut utilization_headroom = max_outstanding.saturating_sub(status.total_outstanding);
+        if config.max_total_outstanding > 0 {
+            utilization_headroom = utilization_headroom
+                .min(config.max_total_outstanding.saturating_sub(status.total_outstanding));
+        }
+
+        let amount = max_amount
+            .min(Self::get_max_loan_for_agent(env.clone(), agent.clone()))
+            .min(Self::owner_remaining_budget(env.clone(), get_agent_owner(&env, &agent)))
+            .min(status.total_liquidity.saturating_sub(config.min_pool_balance))
+            .min(utilization_headroom)
+            .min(Self::max_single_loan(env.clone()));
+
+        if amount < MIN_LOAN_AMOUNT {
+            panic!("Fundable loan amount is below the minimum");
+        }
+
+        Self::request_loan(env, agent, amount, duration_seconds);
+        amount
+    }
+
+    /// Register an agent with the AgentManager and open its first loan in one transaction
+    /// Requires the owner's authorization for the registration. If any step fails
+    /// the whole call reverts, so an agent is never left registered without its loan.
+    /// Returns the new loan's id.
+    pub fn onboard_and_borrow(
+        env: Env,
+        owner: Address,
+        agent: Address,
+        scopes: Vec<String>,
+        max_amount: u64,
+        loan_amount: u64,
+        duration_seconds: u64,
+    ) -> u64 {
+        // The owner authorizes this call; AgentManager's own owner check is satisfied by it
+        owner.require_auth();
+
+        let agent_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::AgentManagerContract(()))
+            .expect("Contract not initialized");
+
+        let agent_mgr_client = AgentManagerClient::new(&env, &agent_mgr_addr);
+        agent_mgr_client.register_agent(&owner, &agent, &scopes, &max_amount);
+
+        Self::request_loan(env, agent, loan_amount, duration_seconds).loan_id
+    }
+
+    /// Enhanced repay loan with automatic default checking
+    /// This implements realistic lending incentives with automatic penalty detection.
+    /// `payment_amount` must cover what is owed (`get_amount_due`); any excess is
+    /// refunded in the same call. `None` pays exactly what is owed.
+    pub fn repay_loan(env: Env, agent: Address, payment_amount: Option<u64>) {
+        reject_protocol_address(&env, &agent);
+
+        // Get DACTP contract addresses
+        let agent_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::AgentManagerContract(()))
+            .expect("Contract not initialized");
+
+        // Create clients for cross-contract calls
+        let agent_mgr_client = AgentManagerClient::new(&env, &agent_mgr_addr);
+
+        // Get loan information
+        let loan = get_active_loan(&env, &agent);
+
+        // DACTP CHECK: Verify agent is authorized for "repay_loan" action
+        let action = String::from_str(&env, "repay_loan");
+        let is_authorized = agent_mgr_client.is_authorized(&agent, &action, &loan.amount);
+        
+        if !is_authorized {
+            panic!("Agent not authorized to repay");
+        }
+
+        agent.require_auth();
+        settle_loan(&env, &agent, &agent, loan, false, payment_amount);
+    }
+
+    /// Repay an agent's loan from the token allowance it granted this contract
+    /// Lets a keeper run scheduled auto-repayment without the agent's live signature;
+    /// the agent still earns the reputation. Fails if the allowance doesn't cover
+    /// principal plus interest.
+    pub fn repay_via_allowance(env: Env, keeper: Address, agent: Address) {
+        require_keeper(&env, &keeper);
+        reject_protocol_address(&env, &agent);
+
+        let agent_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::AgentManagerContract(()))
+            .expect("Contract not initialized");
+
+        let loan = get_active_loan(&env, &agent);
+
+        // DACTP CHECK: The agent must still be authorized for "repay_loan"
+        let action = String::from_str(&env, "repay_loan");
+        if !AgentManagerClient::new(&env, &agent_mgr_addr).is_authorized(&agent, &action, &loan.amount) {
+            panic!("Agent not authorized to repay");
+        }
+
+        settle_loan(&env, &agent, &agent, loan, true, None);
+    }
+
+    /// Repay an agent's loan on its owner's behalf
+    /// The owner (verified against the AgentManager) pays; the agent earns the reputation
+    pub fn owner_repay(env: Env, owner: Address, agent: Address) {
+        owner.require_auth();
+        reject_protocol_address(&env, &agent);
+
+        if get_agent_owner(&env, &agent) != owner {
+            panic!("Unauthorized: only the agent's owner can repay on its behalf");
+        }
+
+        let loan = get_active_loan(&env, &agent);
+        settle_loan(&env, &owner, &agent, loan, false, None);
+    }
+
+    /// Set how long before its loan's due date an agent wants to be reminded
+    /// Off-chain keepers read the result through `reminder_time`. Only the agent's
+    /// owner (verified against the AgentManager) can set it.
+    pub fn set_reminder_offset(env: Env, owner: Address, agent: Address, offset_seconds: u64) {
+        owner.require_auth();
+
+        if get_agent_owner(&env, &agent) != owner {
+            panic!("Unauthorized: only the agent's owner can set its reminder");
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::ReminderOffset(agent), &offset_seconds);
+    }
+
+    /// Get when the agent should be reminded of its active loan (due date minus its offset)
+    /// None if the agent has no reminder offset or no active loan
+    pub fn reminder_time(env: Env, agent: Address) -> Option<u64> {
+        let offset: u64 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ReminderOffset(agent.clone()))?;
+
+        match Self::get_loan(env, agent) {
+            Some(loan) if !loan.repaid => Some(loan.due_date.saturating_sub(offset)),
+            _ => None,
+        }
+    }
+
+    /// Move an agent's active loan to another agent, e.g. when the agent is migrated
+    /// The new agent must be authorized to borrow the amount, within its reputation limit
+    /// and its owner's budget, and free of an active loan. Penalty tracking follows the
+    /// loan id, and the terms locked at issuance (rate included) travel with the loan, so
+    /// its booked interest is unchanged. Moving an interest-free first loan uses up the
+    /// original agent's free loan. The original agent is left with no loan. Only callable
+    /// by admin.
+    pub fn transfer_loan(env: Env, admin: Address, from_agent: Address, to_agent: Address) {
+        require_admin(&env, &admin);
+        reject_protocol_address(&env, &to_agent);
+
+        let mut loan = get_active_loan(&env, &from_agent);
+
+        let to_loan_key = DataKey::Loan(to_agent.clone());
+        let existing_loan: Option<Loan> = env.storage().persistent().get(&to_loan_key);
+        if existing_loan.is_some_and(|existing| !existing.repaid) {
+            panic!("Agent already has an active loan");
+        }
+
+        let agent_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::AgentManagerContract(()))
+            .expect("Contract not initialized");
+
+        let agent_mgr_client = AgentManagerClient::new(&env, &agent_mgr_addr);
+        let action = String::from_str(&env, "borrow");
+        if !agent_mgr_client.is_authorized(&to_agent, &action, &loan.amount) {
+            panic!("Agent not authorized or amount exceeds delegation limit");
+        }
+
+        let agent_info = agent_mgr_client
+            .get_agent_info(&to_agent)
+            .expect("Agent not registered");
+        if agent_info.revoked {
+            panic!("Agent has been revoked");
+        }
+
+        if loan.amount > Self::get_max_loan_for_agent(env.clone(), to_agent.clone()) {
+            panic!("Loan amount exceeds reputation-based limit");
+        }
+
+        // The loan already counts against the budget if both agents share an owner
+        let mut remaining_budget =
+            Self::owner_remaining_budget(env.clone(), agent_info.owner.clone());
+        let from_owner = agent_mgr_client.get_agent_info(&from_agent).map(|info| info.owner);
+        if from_owner == Some(agent_info.owner) {
+            remaining_budget = remaining_budget.saturating_add(loan.amount);
+        }
+        if loan.amount > remaining_budget {
+            panic!("Loan would exceed the owner's borrowing budget");
+        }
+
+        // Detach the loan from the original agent
+        let storage = env.storage().persistent();
+        storage.remove(&DataKey::Loan(from_agent.clone()));
+        let mut active = get_active_loans(&env);
+        if let Some(index) = active.first_index_of(&from_agent) {
+            active.remove(index);
+            storage.set(&DataKey::ActiveLoans(()), &active);
+        }
+
+        let mut borrowers = get_borrowers(&env);
+        if !borrowers.contains(&to_agent) {
+            borrowers.push_back(to_agent.clone());
+            storage.set(&DataKey::Borrowers(()), &borrowers);
+        }
+
+        // The original agent can't take a second interest-free loan after handing one off
+        let mut from_state = Self::get_agent_state(env.clone(), from_agent.clone());
+        if loan.terms.interest_bps == 0 && !from_state.had_first_loan {
+            from_state.had_first_loan = true;
+            set_agent_state(&env, &from_agent, &from_state);
+        }
+
+        // A defaulted loan stays on the recovery worklist under its new agent
+        if remove_defaulted_agent(&env, &from_agent) {
+            add_defaulted_agent(&env, &to_agent);
+        }
+
+        loan.agent = to_agent;
+        save_loan(&env, &loan);
+    }
+
+    /// Report a loan default (missed repayment beyond the default threshold)
+    /// This triggers a REAL reputation penalty (-25) - much harsher than before
+    /// 
+    /// ✅ NEW: Can be called by anyone (not just admin) for automatic penalty system
+    /// ✅ NEW: Prevents duplicate penalties with tracking
+    /// The reporter earns a bounty from the pool, paid once per defaulted loan
+    pub fn report_default(env: Env, reporter: Address, agent: Address) {
+        reporter.require_auth();
+
+        if reporter == agent {
+            panic!("Agents cannot report their own default");
+        }
+
+        // Nor can the agent's owner collect the bounty on its own agent's default
+        let agent_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::AgentManagerContract(()))
+            .expect("Contract not initialized");
+        let agent_info = AgentManagerClient::new(&env, &agent_mgr_addr).get_agent_info(&agent);
+        if agent_info.is_some_and(|info| info.owner == reporter) {
+            panic!("Owners cannot report their own agent's default");
+        }
+
+        // Verify loan exists and is overdue
+        let loan_key = DataKey::Loan(agent.clone());
+        let loan: Loan = env
+            .storage()
+            .persistent()
+            .get(&loan_key)
+            .expect("No loan found for agent");
+
+        if loan.repaid {
             panic!("Cannot report default on repaid loan");
         }
 
-        let current_time = env.ledger().timestamp();
-        if current_time <= loan.due_date + GRACE_PERIOD_SECONDS {
-            panic!("Loan is not yet in default - still within grace period");
+        let current_time = env.ledger().timestamp();
+        if current_time <= loan.due_date + loan.terms.default_after_seconds {
+            panic!("Loan is not yet in default - still within default window");
+        }
+
+        // ✅ NEW: Check if penalty already applied to prevent duplicate penalties
+        // (tracked per loan id, so an earlier loan's penalty never carries over)
+        if is_penalty_applied(&env, loan.loan_id) {
+            panic!("Penalty already applied for this loan");
+        }
+
+        // DACTP REPUTATION UPDATE: Heavy penalty for default
+        apply_default_penalty(&env, &agent, &loan);
+
+        // KEEPER BOUNTY: Paid from the pool, capped by what the pool holds
+        let xlm_token: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::XlmTokenContract(()))
+            .expect("Contract not initialized");
+
+        let xlm_client = token::Client::new(&env, &xlm_token);
+        let bounty = default_bounty(loan.amount).min(pool_balance(&env, &xlm_client)) as i128;
+        if bounty > 0 {
+            xlm_client.transfer(&env.current_contract_address(), &reporter, &bounty);
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::DefaultReporter(agent), &reporter);
+    }
+
+    /// Get the agents currently in default, oldest first, as a worklist for recovery
+    /// An agent is added when its default penalty is applied and removed once it repays.
+    /// At most MAX_DEFAULTED_AGENTS are kept.
+    pub fn get_defaulted_agents(env: Env) -> Vec<Address> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::DefaultedAgents(()))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Preview the reputation change `report_default` would apply to the agent's active loan
+    /// Reflects penalty escalation and principal scaling without changing any state.
+    /// Returns 0 if the loan's default penalty was already applied.
+    pub fn preview_default_penalty(env: Env, agent: Address) -> i32 {
+        let loan = get_active_loan(&env, &agent);
+        if is_penalty_applied(&env, loan.loan_id) {
+            return 0;
+        }
+
+        let agent_state = Self::get_agent_state(env.clone(), agent);
+        default_penalty(&env, &agent_state, &loan)
+    }
+
+    /// Get the most a keeper can earn for reporting a default
+    /// The bounty is DEFAULT_REPORT_BOUNTY_BPS of the defaulted principal, capped at this amount.
+    pub fn get_default_bounty(_env: Env) -> u64 {
+        MAX_DEFAULT_REPORT_BOUNTY
+    }
+
+    /// Get the keeper that reported an agent's most recent default, if any
+    pub fn get_default_reporter(env: Env, agent: Address) -> Option<Address> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::DefaultReporter(agent))
+    }
+
+    /// Get loan information
+    pub fn get_loan(env: Env, agent: Address) -> Option<Loan> {
+        let key = DataKey::Loan(agent);
+        env.storage().persistent().get(&key)
+    }
+
+    /// Delete an agent's repaid loan record to reclaim its storage rent
+    /// Its outcome stays in the agent's loan history. Refuses to close an unrepaid loan.
+    /// Callable by anyone, e.g. a keeper cleaning up after long-running operation.
+    pub fn close_loan(env: Env, agent: Address) {
+        let loan = Self::get_loan(env.clone(), agent.clone()).expect("No loan found for agent");
+        if !loan.repaid {
+            panic!("Cannot close an unrepaid loan");
+        }
+
+        let storage = env.storage().persistent();
+        storage.remove(&DataKey::Loan(agent));
+        storage.remove(&DataKey::LoanById(loan.loan_id));
+        storage.remove(&DataKey::PenaltyApplied(loan.loan_id));
+    }
+
+    /// Get what an agent must pay now to settle its active loan (principal plus accrued interest)
+    pub fn get_amount_due(env: Env, agent: Address) -> u64 {
+        let loan = get_active_loan(&env, &agent);
+        loan.amount.saturating_add(accrued_interest(&env, &loan))
+    }
+
+    /// Preview splitting a loan into `num_installments` equal payments due at even intervals
+    /// over `duration_seconds` from now. Interest for the full term follows the configured
+    /// interest mode at `rate_bps`; the last installment absorbs any rounding remainder, so
+    /// the installments always add up to the total owed. Stores nothing. Fails with
+    /// `AmountOverflow` if an installment's amount does not fit in a u64.
+    pub fn amortization_schedule(
+        env: Env,
+        amount: u64,
+        rate_bps: u32,
+        duration_seconds: u64,
+        num_installments: u32,
+    ) -> Vec<Installment> {
+        if num_installments == 0 || num_installments > MAX_INSTALLMENTS {
+            panic!("Installment count must be between 1 and 365");
+        }
+        if duration_seconds == 0 {
+            panic!("Loan duration must be positive");
+        }
+
+        let config = Self::get_config(env.clone());
+        let total_interest = calculate_accrued_interest(
+            amount,
+            rate_bps,
+            duration_seconds,
+            config.interest_period_seconds,
+            config.interest_compounding,
+        );
+
+        let count = num_installments as u64;
+        let start = env.ledger().timestamp();
+        let mut schedule = Vec::new(&env);
+        for i in 1..=count {
+            let (principal, interest) = if i == count {
+                (
+                    amount - amount / count * (count - 1),
+                    total_interest - total_interest / count * (count - 1),
+                )
+            } else {
+                (amount / count, total_interest / count)
+            };
+            let offset = ((duration_seconds as u128) * (i as u128) / (count as u128)) as u64;
+            let Some(installment_amount) = principal.checked_add(interest) else {
+                panic_with_error!(&env, LendingError::AmountOverflow);
+            };
+            schedule.push_back(Installment {
+                due_date: start.saturating_add(offset),
+                principal,
+                interest,
+                amount: installment_amount,
+            });
+        }
+        schedule
+    }
+
+    /// Get a loan by its id (including loans since replaced by the agent's later loans,
+    /// until they are closed)
+    pub fn get_loan_by_id(env: Env, loan_id: u64) -> Option<Loan> {
+        env.storage().persistent().get(&DataKey::LoanById(loan_id))
+    }
+
+    /// Get the agents whose unrepaid loans fall due within `seconds` (including overdue ones)
+    /// Scans only the index of active loans, so keepers can pull a worklist in one call
+    pub fn loans_due_within(env: Env, seconds: u64) -> Vec<Address> {
+        let horizon = env.ledger().timestamp().saturating_add(seconds);
+        let mut due = Vec::new(&env);
+        for agent in get_active_loans(&env).iter() {
+            if let Some(loan) = Self::get_loan(env.clone(), agent.clone()) {
+                if loan.due_date <= horizon {
+                    due.push_back(agent);
+                }
+            }
+        }
+        due
+    }
+
+    /// Get the maximum loan amount for a given reputation score
+    /// This implements the tiered lending system
+    pub fn get_max_loan_for_reputation(env: Env, reputation_score: u32) -> u64 {
+        calculate_max_loan_amount(&env, reputation_score, 0)
+    }
+
+    /// Get the maximum loan amount for an agent, including its good-standing bonus
+    pub fn get_max_loan_for_agent(env: Env, agent: Address) -> u64 {
+        let reputation_score = Self::get_effective_reputation(env.clone(), agent.clone());
+        let agent_state = Self::get_agent_state(env.clone(), agent);
+        calculate_max_loan_amount(&env, reputation_score, good_standing_duration(&env, &agent_state))
+    }
+
+    /// Get the combined reputation of every agent an owner controls
+    /// Scores come from the ReputationManager for the owner's agents registered with
+    /// the AgentManager. An owner without agents has the ReputationManager's default score.
+    pub fn owner_reputation(env: Env, owner: Address, mode: AggregateMode) -> u32 {
+        let agent_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::AgentManagerContract(()))
+            .expect("Contract not initialized");
+        let rep_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ReputationManagerContract(()))
+            .expect("Contract not initialized");
+
+        let agents = AgentManagerClient::new(&env, &agent_mgr_addr).get_owner_agents(&owner);
+        let rep_mgr_client = ReputationManagerClient::new(&env, &rep_mgr_addr);
+        if agents.is_empty() {
+            return rep_mgr_client.get_default_score();
+        }
+
+        let mut total: u64 = 0;
+        let mut min = REPUTATION_MAX;
+        let mut max = REPUTATION_MIN;
+        for agent in agents.iter() {
+            let score = rep_mgr_client.get_score(&agent);
+            total += score as u64;
+            min = min.min(score);
+            max = max.max(score);
+        }
+
+        match mode {
+            AggregateMode::Average => (total / agents.len() as u64) as u32,
+            AggregateMode::Min => min,
+            AggregateMode::Max => max,
+        }
+    }
+
+    /// Get how much more an owner's agents may borrow under its budget in the AgentManager
+    /// The budget covers outstanding principal across all the owner's agents; without a
+    /// budget this is u64::MAX.
+    pub fn owner_remaining_budget(env: Env, owner: Address) -> u64 {
+        let agent_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::AgentManagerContract(()))
+            .expect("Contract not initialized");
+        let agent_mgr_client = AgentManagerClient::new(&env, &agent_mgr_addr);
+
+        let Some(budget) = agent_mgr_client.get_owner_budget(&owner) else {
+            return u64::MAX;
+        };
+
+        let mut outstanding: u64 = 0;
+        for agent in agent_mgr_client.get_owner_agents(&owner).iter() {
+            if let Some(loan) = Self::get_loan(env.clone(), agent) {
+                if !loan.repaid {
+                    outstanding = outstanding.saturating_add(loan.amount);
+                }
+            }
+        }
+        budget.saturating_sub(outstanding)
+    }
+
+    /// Get the collateral an agent must post to borrow `amount`
+    /// Higher reputation tiers borrow at a higher loan-to-value, so they post less.
+    pub fn required_collateral(env: Env, agent: Address, amount: u64) -> u64 {
+        let reputation_score = Self::get_effective_reputation(env.clone(), agent);
+        let tier = reputation_tier(reputation_score);
+        if tier == 0 {
+            panic!("Reputation too low to borrow against collateral");
+        }
+
+        let ltv_bps = Self::get_config(env)
+            .collateral_ltv_bps
+            .get(tier - 1)
+            .expect("LTV schedule missing tier");
+        let collateral = (amount as u128 * BPS_DENOMINATOR as u128).div_ceil(ltv_bps as u128);
+        collateral as u64
+    }
+
+    /// Get current pool utilization percentage
+    pub fn get_pool_utilization(env: Env) -> u32 {
+        let xlm_token: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::XlmTokenContract(()))
+            .expect("Contract not initialized");
+
+        let xlm_client = token::Client::new(&env, &xlm_token);
+        let total_liquidity = pool_balance(&env, &xlm_client);
+        let outstanding = get_total_outstanding(&env);
+
+        calculate_pool_utilization_bps(outstanding, total_liquidity + outstanding) / 100
+    }
+
+    /// Get the pool's net position in one call (liquidity, outstanding, interest, reserve, utilization)
+    pub fn get_pool_status(env: Env) -> PoolStatus {
+        let xlm_token: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::XlmTokenContract(()))
+            .expect("Contract not initialized");
+
+        let xlm_client = token::Client::new(&env, &xlm_token);
+        let total_liquidity = pool_balance(&env, &xlm_client);
+        let total_outstanding = get_total_outstanding(&env);
+
+        PoolStatus {
+            total_liquidity,
+            total_outstanding,
+            accrued_interest: get_outstanding_interest(&env),
+            reserve_balance: get_reserve_balance(&env),
+            utilization_bps: calculate_pool_utilization_bps(
+                total_outstanding,
+                total_liquidity + total_outstanding,
+            ),
+        }
+    }
+
+    /// Append the current pool utilization to the bounded utilization history
+    /// Callable by an approved keeper. Once the history is full the oldest sample is dropped.
+    pub fn record_utilization_sample(env: Env, caller: Address) {
+        require_keeper(&env, &caller);
+
+        let utilization_bps = Self::get_pool_status(env.clone()).utilization_bps;
+        let mut history = Self::get_utilization_history(env.clone());
+        history.push_back((env.ledger().timestamp(), utilization_bps));
+        save_utilization_history(&env, history);
+    }
+
+    /// Get the recorded (timestamp, utilization bps) samples, oldest first
+    pub fn get_utilization_history(env: Env) -> Vec<(u64, u32)> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::UtilizationHistory(()))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Set how many utilization samples are kept; older samples beyond it are dropped
+    /// Only callable by admin.
+    pub fn set_utilization_history_size(env: Env, admin: Address, size: u32) {
+        require_admin(&env, &admin);
+
+        if size == 0 {
+            panic!("Utilization history size must be positive");
+        }
+
+        let mut config = Self::get_config(env.clone());
+        config.utilization_history_size = size;
+        env.storage().persistent().set(&DataKey::Config(()), &config);
+
+        save_utilization_history(&env, Self::get_utilization_history(env.clone()));
+    }
+
+    /// Check if a loan is currently overdue (past the default threshold)
+    /// ✅ NEW: Automatically applies penalty if overdue and not yet penalized
+    pub fn is_loan_overdue(env: Env, agent: Address) -> bool {
+        let loan_key = DataKey::Loan(agent.clone());
+        if let Some(loan) = env.storage().persistent().get::<DataKey, Loan>(&loan_key) {
+            if !loan.repaid {
+                let current_time = env.ledger().timestamp();
+                let default_deadline = loan.due_date + loan.terms.default_after_seconds;
+                let is_overdue = current_time > default_deadline;
+                
+                // ✅ NEW: Auto-apply penalty if overdue and not yet applied
+                if is_overdue && !is_penalty_applied(&env, loan.loan_id) {
+                    // Apply penalty automatically
+                    apply_default_penalty(&env, &agent, &loan);
+                }
+                
+                return is_overdue;
+            }
+        }
+        false
+    }
+
+    /// Get the lending state tracked for an agent across loans
+    pub fn get_agent_state(env: Env, agent: Address) -> AgentState {
+        env.storage()
+            .persistent()
+            .get(&DataKey::AgentState(agent))
+            .unwrap_or_default()
+    }
+
+    /// Approve a keeper to grant credit discipline and liquidity rewards, record utilization
+    /// samples and collect repayments from agents' allowances
+    /// Only callable by admin.
+    pub fn approve_keeper(env: Env, admin: Address, keeper: Address) {
+        require_admin(&env, &admin);
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::ApprovedKeeper(keeper), &true);
+    }
+
+    /// Reward an agent whose credit line stayed lightly drawn over the last period
+    /// Callable by an approved keeper at most once per period per agent. The agent's
+    /// peak draw on its loan limit since the last reward must be at or below the target.
+    pub fn reward_credit_discipline(env: Env, caller: Address, agent: Address) {
+        require_keeper(&env, &caller);
+
+        let mut agent_state = Self::get_agent_state(env.clone(), agent.clone());
+        let period_start = agent_state
+            .last_discipline_reward
+            .or(agent_state.good_standing_since)
+            .expect("Agent has no credit history");
+
+        let current_time = env.ledger().timestamp();
+        if current_time < period_start + CREDIT_DISCIPLINE_PERIOD_SECONDS {
+            panic!("Credit discipline reward already claimed this period");
+        }
+
+        if agent_state.peak_utilization_bps > CREDIT_DISCIPLINE_TARGET_BPS {
+            panic!("Credit line utilization above target");
+        }
+
+        // The next period starts from whatever is still drawn now
+        let still_drawn = match Self::get_loan(env.clone(), agent.clone()) {
+            Some(loan) if !loan.repaid => loan.amount,
+            _ => 0,
+        };
+        let limit = Self::get_max_loan_for_agent(env.clone(), agent.clone());
+        agent_state.peak_utilization_bps = if still_drawn == 0 {
+            0
+        } else {
+            calculate_pool_utilization_bps(still_drawn, limit)
+        };
+        agent_state.last_discipline_reward = Some(current_time);
+        set_agent_state(&env, &agent, &agent_state);
+
+        let rep_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ReputationManagerContract(()))
+            .expect("Contract not initialized");
+
+        let rep_mgr_client = ReputationManagerClient::new(&env, &rep_mgr_addr);
+        rep_mgr_client.update_score(
+            &env.current_contract_address(),
+            &agent,
+            &REPUTATION_INCREASE_CREDIT_DISCIPLINE,
+        );
+        record_reputation_adjustment(&env, &agent, REPUTATION_INCREASE_CREDIT_DISCIPLINE);
+    }
+
+    /// Reward a lender for keeping `amount` of liquidity in the pool
+    /// Callable by an approved keeper. The bonus grows with the amount and the time it
+    /// stayed deposited, up to a cap, and only finalizes once the deposit has been held
+    /// for the lock period. Depositing more, withdrawing or being rewarded restarts the clock.
+    /// Returns the reputation bonus granted.
+    pub fn reward_liquidity_provision(env: Env, caller: Address, provider: Address, amount: u64) -> u32 {
+        require_keeper(&env, &caller);
+
+        let since_key = DataKey::LiquiditySince(provider.clone());
+        let since: u64 = env
+            .storage()
+            .persistent()
+            .get(&since_key)
+            .expect("Provider has no liquidity deposited");
+
+        let current_time = env.ledger().timestamp();
+        if current_time < since + LIQUIDITY_LOCK_SECONDS {
+            panic!("Liquidity still within its lock period");
+        }
+
+        let shares = Self::get_lender_shares(env.clone(), provider.clone());
+        let position = (shares as u128) * (get_pool_value(&env) as u128) / (get_total_shares(&env) as u128);
+        if amount as u128 > position {
+            panic!("Reward amount exceeds the provider's deposit");
+        }
+
+        let held_seconds = (current_time - since) as u128;
+        let bonus = ((amount as u128) * held_seconds / LIQUIDITY_BONUS_STROOP_SECONDS)
+            .min(MAX_LIQUIDITY_BONUS as u128) as u32;
+        if bonus == 0 {
+            panic!("Liquidity too small to earn a bonus");
+        }
+
+        env.storage().persistent().set(&since_key, &current_time);
+
+        let rep_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ReputationManagerContract(()))
+            .expect("Contract not initialized");
+
+        let rep_mgr_client = ReputationManagerClient::new(&env, &rep_mgr_addr);
+        rep_mgr_client.update_score(&env.current_contract_address(), &provider, &(bonus as i32));
+        record_reputation_adjustment(&env, &provider, bonus as i32);
+
+        bonus
+    }
+
+    /// Pay out the period's interest rebates from the pool
+    /// Callable by an approved keeper at most once per rebate period. Every agent that paid
+    /// interest since the last distribution and now meets the reputation threshold gets
+    /// back its share of what it paid; the period's tallies then start over for everyone.
+    /// Returns the total rebated.
+    pub fn distribute_rebates(env: Env, caller: Address) -> u64 {
+        require_keeper(&env, &caller);
+
+        let config = Self::get_config(env.clone());
+        let current_time = env.ledger().timestamp();
+        let last: Option<u64> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::LastRebateDistribution(()));
+        if let Some(last) = last {
+            if current_time < last.saturating_add(config.rebate_policy.period_seconds) {
+                panic!("Rebates already distributed this period");
+            }
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::LastRebateDistribution(()), &current_time);
+
+        let xlm_token: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::XlmTokenContract(()))
+            .expect("Contract not initialized");
+        let xlm_client = token::Client::new(&env, &xlm_token);
+
+        let payers: Vec<Address> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::RebatePayers(()))
+            .unwrap_or(Vec::new(&env));
+        let mut available = pool_balance(&env, &xlm_client);
+        let mut total = 0u64;
+        for agent in payers.iter() {
+            let paid = Self::get_interest_paid(env.clone(), agent.clone());
+            env.storage().persistent().remove(&DataKey::InterestPaid(agent.clone()));
+
+            if Self::get_effective_reputation(env.clone(), agent.clone())
+                < config.rebate_policy.min_reputation
+            {
+                continue;
+            }
+            let rebate = ((paid as u128) * (config.rebate_policy.rebate_bps as u128)
+                / (BPS_DENOMINATOR as u128)) as u64;
+            let rebate = rebate.min(available);
+            if rebate == 0 {
+                continue;
+            }
+
+            xlm_client.transfer(&env.current_contract_address(), &agent, &(rebate as i128));
+            available -= rebate;
+            total += rebate;
+        }
+        env.storage().persistent().remove(&DataKey::RebatePayers(()));
+
+        total
+    }
+
+    /// Endorse a newcomer, lending it a small temporary reputation boost
+    /// The endorser must be well established and is penalized if the endorsee
+    /// defaults while the endorsement is active.
+    pub fn endorse(env: Env, endorser: Address, endorsee: Address) {
+        endorser.require_auth();
+
+        if endorser == endorsee {
+            panic!("Agents cannot endorse themselves");
+        }
+
+        let rep_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ReputationManagerContract(()))
+            .expect("Contract not initialized");
+
+        let rep_mgr_client = ReputationManagerClient::new(&env, &rep_mgr_addr);
+        if rep_mgr_client.get_score(&endorser) < ENDORSER_MIN_REPUTATION {
+            panic!("Endorser reputation too low");
+        }
+
+        if active_endorsement(&env, &endorsee).is_some() {
+            panic!("Agent is already endorsed");
+        }
+
+        // Only endorsements still running count against the cap
+        let endorsed_key = DataKey::EndorsedAgents(endorser.clone());
+        let endorsed: Vec<Address> = env
+            .storage()
+            .persistent()
+            .get(&endorsed_key)
+            .unwrap_or(Vec::new(&env));
+
+        let mut active = Vec::new(&env);
+        for agent in endorsed.iter() {
+            if let Some(endorsement) = active_endorsement(&env, &agent) {
+                if endorsement.endorser == endorser {
+                    active.push_back(agent);
+                }
+            }
+        }
+
+        if active.len() >= MAX_ACTIVE_ENDORSEMENTS {
+            panic!("Endorser has reached the maximum number of active endorsements");
+        }
+
+        active.push_back(endorsee.clone());
+        env.storage().persistent().set(&endorsed_key, &active);
+
+        let mut endorsers = get_endorsers(&env);
+        if !endorsers.contains(&endorser) {
+            endorsers.push_back(endorser.clone());
+            env.storage().persistent().set(&DataKey::Endorsers(()), &endorsers);
+        }
+
+        let endorsement = Endorsement {
+            endorser,
+            expires_at: env.ledger().timestamp() + ENDORSEMENT_DURATION_SECONDS,
+        };
+        env.storage()
+            .persistent()
+            .set(&DataKey::Endorsement(endorsee), &endorsement);
+    }
+
+    /// Get the endorsement currently backing an agent, if any
+    pub fn get_endorsement(env: Env, agent: Address) -> Option<Endorsement> {
+        active_endorsement(&env, &agent)
+    }
+
+    /// Get the reputation score used for lending decisions
+    /// (stored score, time-weighted when configured, plus endorsement boost)
+    pub fn get_effective_reputation(env: Env, agent: Address) -> u32 {
+        let rep_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ReputationManagerContract(()))
+            .expect("Contract not initialized");
+
+        let rep_mgr_client = ReputationManagerClient::new(&env, &rep_mgr_addr);
+        lending_score(&env, &agent, rep_mgr_client.get_score(&agent))
+    }
+
+    /// Get the full loan history for an agent (oldest first)
+    pub fn get_loan_history(env: Env, agent: Address) -> Vec<LoanRecord> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::LoanHistory(agent))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Restructure a distressed agent's active loan as an alternative to default
+    /// Extends the term to `new_duration_seconds` from now and forgives part of the
+    /// principal, reducing the pool's outstanding total. No reputation change is
+    /// applied. A loan already reported as defaulted leaves the recovery worklist and
+    /// can be reported again if it misses the new term. Only callable by admin.
+    pub fn restructure_loan(
+        env: Env,
+        admin: Address,
+        agent: Address,
+        new_duration_seconds: u64,
+        forgiven_amount: u64,
+    ) -> Loan {
+        require_admin(&env, &admin);
+
+        let loan_key = DataKey::Loan(agent.clone());
+        let mut loan: Loan = env
+            .storage()
+            .persistent()
+            .get(&loan_key)
+            .expect("No active loan found");
+
+        if loan.repaid {
+            panic!("Cannot restructure a repaid loan");
+        }
+
+        let new_due_date =
+            loan_due_date(&env, &Self::get_config(env.clone()), new_duration_seconds);
+        if new_due_date <= loan.due_date {
+            panic!("Restructuring must extend the loan term");
+        }
+
+        if forgiven_amount >= loan.amount {
+            panic!("Forgiven amount must be less than the outstanding principal");
+        }
+
+        // Interest owed follows the reduced principal and the new term
+        let previously_booked = loan.booked_interest;
+        loan.amount -= forgiven_amount;
+        loan.due_date = new_due_date;
+        loan.booked_interest = interest_at_due_date(&env, &loan);
+        set_outstanding_interest(
+            &env,
+            (get_outstanding_interest(&env) + loan.booked_interest).saturating_sub(previously_booked),
+        );
+        save_loan(&env, &loan);
+        set_total_outstanding(&env, get_total_outstanding(&env).saturating_sub(forgiven_amount));
+
+        remove_defaulted_agent(&env, &agent);
+        env.storage()
+            .persistent()
+            .remove(&DataKey::PenaltyApplied(loan.loan_id));
+
+        loan
+    }
+
+    /// Rebuild an agent's reputation from its authoritative loan history
+    /// Replays every recorded outcome, together with the credit discipline and liquidity
+    /// rewards and endorser penalties granted outside loans, from the ReputationManager's
+    /// default score with the same bounds it applies, then corrects the stored score to match.
+    /// Only callable by admin. Returns the corrected score.
+    pub fn recompute_reputation(env: Env, admin: Address, agent: Address) -> u32 {
+        require_admin(&env, &admin);
+
+        let rep_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ReputationManagerContract(()))
+            .expect("Contract not initialized");
+
+        let rep_mgr_client = ReputationManagerClient::new(&env, &rep_mgr_addr);
+
+        // Replay history step by step, in the order the changes were made, so clamping
+        // matches the live updates
+        let mut replayed = rep_mgr_client.get_default_score();
+        let adjustments = get_reputation_adjustments(&env, &agent);
+        let mut next_adjustment = 0;
+        for record in Self::get_loan_history(env.clone(), agent.clone()).iter() {
+            while let Some((made_at, delta)) = adjustments.get(next_adjustment) {
+                if made_at >= record.closed_at {
+                    break;
+                }
+                replayed = apply_bounded_delta(replayed, delta);
+                next_adjustment += 1;
+            }
+            replayed = apply_bounded_delta(replayed, record.reputation_delta);
+        }
+        while let Some((_, delta)) = adjustments.get(next_adjustment) {
+            replayed = apply_bounded_delta(replayed, delta);
+            next_adjustment += 1;
+        }
+
+        let current_score = rep_mgr_client.get_score(&agent);
+        let correction = (replayed as i32) - (current_score as i32);
+
+        if correction != 0 {
+            let contract_addr = env.current_contract_address();
+            rep_mgr_client.update_score(&contract_addr, &agent, &correction);
+        }
+
+        replayed
+    }
+
+    /// Export the lending state (config, counters, loans, per-agent records, endorsements
+    /// and lender positions)
+    /// Token balances live in the token contract and are not part of the export, nor are
+    /// the contract wiring and the admin's access lists (keepers, allowed tokens, privileged
+    /// and blocked borrowers, price oracle), which `import_state` leaves as they are.
+    /// Only callable by admin.
+    pub fn export_state(env: Env, admin: Address) -> StateBlob {
+        require_admin(&env, &admin);
+
+        let mut loans = Vec::new(&env);
+        let mut agents = Vec::new(&env);
+        for agent in get_borrowers(&env).iter() {
+            let mut penalty_applied = false;
+            if let Some(loan) = Self::get_loan(env.clone(), agent.clone()) {
+                penalty_applied = is_penalty_applied(&env, loan.loan_id);
+                loans.push_back(loan);
+            }
+            agents.push_back(AgentSnapshot {
+                agent: agent.clone(),
+                state: Self::get_agent_state(env.clone(), agent.clone()),
+                history: Self::get_loan_history(env.clone(), agent.clone()),
+                penalty_applied,
+                default_reporter: Self::get_default_reporter(env.clone(), agent.clone()),
+                recent_bonuses: env
+                    .storage()
+                    .persistent()
+                    .get(&DataKey::RecentBonuses(agent.clone()))
+                    .unwrap_or(Vec::new(&env)),
+                reminder_offset: env
+                    .storage()
+                    .persistent()
+                    .get(&DataKey::ReminderOffset(agent.clone())),
+                interest_paid: Self::get_interest_paid(env.clone(), agent),
+            });
+        }
+
+        let mut endorsements = Vec::new(&env);
+        for endorser in get_endorsers(&env).iter() {
+            let endorsed: Vec<Address> = env
+                .storage()
+                .persistent()
+                .get(&DataKey::EndorsedAgents(endorser.clone()))
+                .unwrap_or(Vec::new(&env));
+            for endorsee in endorsed.iter() {
+                let stored: Option<Endorsement> = env
+                    .storage()
+                    .persistent()
+                    .get(&DataKey::Endorsement(endorsee.clone()));
+                if let Some(endorsement) = stored {
+                    if endorsement.endorser == endorser {
+                        endorsements.push_back((endorsee, endorsement));
+                    }
+                }
+            }
+        }
+
+        let mut reputation_adjustments = Vec::new(&env);
+        for agent in adjustable_agents(&env).iter() {
+            let adjustments = get_reputation_adjustments(&env, &agent);
+            if !adjustments.is_empty() {
+                reputation_adjustments.push_back((agent, adjustments));
+            }
+        }
+
+        let mut lenders = Vec::new(&env);
+        for lender in get_lenders(&env).iter() {
+            lenders.push_back(LenderSnapshot {
+                lender: lender.clone(),
+                shares: Self::get_lender_shares(env.clone(), lender.clone()),
+                liquidity_since: env
+                    .storage()
+                    .persistent()
+                    .get(&DataKey::LiquiditySince(lender.clone())),
+                withdrawal_window: env
+                    .storage()
+                    .persistent()
+                    .get(&DataKey::WithdrawalWindow(lender))
+                    .unwrap_or_default(),
+            });
+        }
+
+        StateBlob {
+            config: Self::get_config(env.clone()),
+            total_outstanding: get_total_outstanding(&env),
+            outstanding_interest: get_outstanding_interest(&env),
+            next_loan_id: next_loan_id(&env),
+            loans,
+            agents,
+            endorsements,
+            total_shares: get_total_shares(&env),
+            lenders,
+            accrued_fees: Self::get_accrued_fees(env.clone()),
+            reserve_balance: get_reserve_balance(&env),
+            utilization_history: Self::get_utilization_history(env.clone()),
+            last_rebate_distribution: env
+                .storage()
+                .persistent()
+                .get(&DataKey::LastRebateDistribution(())),
+            reputation_adjustments,
+        }
+    }
+
+    /// Replace the lending state with a previously exported blob
+    /// Records of borrowers missing from the blob are cleared. Only callable by admin.
+    pub fn import_state(env: Env, admin: Address, blob: StateBlob) {
+        require_admin(&env, &admin);
+
+        let storage = env.storage().persistent();
+        for agent in adjustable_agents(&env).iter() {
+            storage.remove(&DataKey::ReputationAdjustments(agent));
+        }
+        for agent in get_borrowers(&env).iter() {
+            if let Some(loan) = Self::get_loan(env.clone(), agent.clone()) {
+                storage.remove(&DataKey::LoanById(loan.loan_id));
+                storage.remove(&DataKey::PenaltyApplied(loan.loan_id));
+            }
+            storage.remove(&DataKey::Loan(agent.clone()));
+            storage.remove(&DataKey::AgentState(agent.clone()));
+            storage.remove(&DataKey::LoanHistory(agent.clone()));
+            storage.remove(&DataKey::DefaultReporter(agent.clone()));
+            storage.remove(&DataKey::RecentBonuses(agent.clone()));
+            storage.remove(&DataKey::ReminderOffset(agent.clone()));
+            storage.remove(&DataKey::InterestPaid(agent));
+        }
+        storage.remove(&DataKey::RebatePayers(()));
+        storage.remove(&DataKey::ActiveLoans(()));
+        storage.remove(&DataKey::DefaultedAgents(()));
+        for endorser in get_endorsers(&env).iter() {
+            let endorsed: Vec<Address> = storage
+                .get(&DataKey::EndorsedAgents(endorser.clone()))
+                .unwrap_or(Vec::new(&env));
+            for endorsee in endorsed.iter() {
+                storage.remove(&DataKey::Endorsement(endorsee));
+            }
+            storage.remove(&DataKey::EndorsedAgents(endorser));
+        }
+        storage.remove(&DataKey::Endorsers(()));
+        for lender in get_lenders(&env).iter() {
+            storage.remove(&DataKey::LenderShares(lender.clone()));
+            storage.remove(&DataKey::LiquiditySince(lender.clone()));
+            storage.remove(&DataKey::WithdrawalWindow(lender));
+        }
+        storage.remove(&DataKey::Lenders(()));
+
+        for loan in blob.loans.iter() {
+            save_loan(&env, &loan);
+        }
+
+        let mut borrowers = Vec::new(&env);
+        let mut rebate_payers = Vec::new(&env);
+        for snapshot in blob.agents.iter() {
+            storage.set(&DataKey::AgentState(snapshot.agent.clone()), &snapshot.state);
+            storage.set(&DataKey::LoanHistory(snapshot.agent.clone()), &snapshot.history);
+            if !snapshot.recent_bonuses.is_empty() {
+                let bonuses_key = DataKey::RecentBonuses(snapshot.agent.clone());
+                storage.set(&bonuses_key, &snapshot.recent_bonuses);
+            }
+            if snapshot.interest_paid > 0 {
+                let paid_key = DataKey::InterestPaid(snapshot.agent.clone());
+                storage.set(&paid_key, &snapshot.interest_paid);
+                rebate_payers.push_back(snapshot.agent.clone());
+            }
+            if let Some(offset) = snapshot.reminder_offset {
+                storage.set(&DataKey::ReminderOffset(snapshot.agent.clone()), &offset);
+            }
+            if let Some(reporter) = snapshot.default_reporter.clone() {
+                storage.set(&DataKey::DefaultReporter(snapshot.agent.clone()), &reporter);
+            }
+            if snapshot.penalty_applied {
+                if let Some(loan) = Self::get_loan(env.clone(), snapshot.agent.clone()) {
+                    storage.set(&DataKey::PenaltyApplied(loan.loan_id), &true);
+                    if !loan.repaid {
+                        add_defaulted_agent(&env, &snapshot.agent);
+                    }
+                }
+            }
+            borrowers.push_back(snapshot.agent);
+        }
+
+        storage.set(&DataKey::Borrowers(()), &borrowers);
+        storage.set(&DataKey::RebatePayers(()), &rebate_payers);
+
+        let mut endorsers: Vec<Address> = Vec::new(&env);
+        for (endorsee, endorsement) in blob.endorsements.iter() {
+            let endorsed_key = DataKey::EndorsedAgents(endorsement.endorser.clone());
+            let mut endorsed: Vec<Address> = storage.get(&endorsed_key).unwrap_or(Vec::new(&env));
+            endorsed.push_back(endorsee.clone());
+            storage.set(&endorsed_key, &endorsed);
+            if !endorsers.contains(&endorsement.endorser) {
+                endorsers.push_back(endorsement.endorser.clone());
+            }
+            storage.set(&DataKey::Endorsement(endorsee), &endorsement);
+        }
+        storage.set(&DataKey::Endorsers(()), &endorsers);
+
+        for snapshot in blob.lenders.iter() {
+            set_lender_shares(&env, &snapshot.lender, snapshot.shares);
+            if let Some(since) = snapshot.liquidity_since {
+                storage.set(&DataKey::LiquiditySince(snapshot.lender.clone()), &since);
+            }
+            if snapshot.withdrawal_window != WithdrawalWindow::default() {
+                let window_key = DataKey::WithdrawalWindow(snapshot.lender.clone());
+                storage.set(&window_key, &snapshot.withdrawal_window);
+            }
+        }
+        storage.set(&DataKey::TotalShares(()), &blob.total_shares);
+        storage.set(&DataKey::AccruedFees(()), &blob.accrued_fees);
+        storage.set(&DataKey::ReserveBalance(()), &blob.reserve_balance);
+        storage.set(&DataKey::UtilizationHistory(()), &blob.utilization_history);
+        for (agent, adjustments) in blob.reputation_adjustments.iter() {
+            storage.set(&DataKey::ReputationAdjustments(agent), &adjustments);
+        }
+        match blob.last_rebate_distribution {
+            Some(last) => storage.set(&DataKey::LastRebateDistribution(()), &last),
+            None => storage.remove(&DataKey::LastRebateDistribution(())),
+        }
+
+        storage.set(&DataKey::Config(()), &blob.config);
+        set_total_outstanding(&env, blob.total_outstanding);
+        set_outstanding_interest(&env, blob.outstanding_interest);
+        storage.set(&DataKey::NextLoanId(()), &blob.next_loan_id);
+    }
+
+    /// Format a stroop amount as an XLM decimal string (e.g. 15_000_000 -> "1.5")
+    pub fn stroops_to_xlm_string(env: Env, amount: u64) -> String {
+        let mut buf = [0u8; MAX_XLM_STRING_LEN];
+        let mut len = write_digits(&mut buf, 0, amount / STROOPS_PER_XLM, 1);
+
+        let fraction = amount % STROOPS_PER_XLM;
+        if fraction > 0 {
+            buf[len] = b'.';
+            len = write_digits(&mut buf, len + 1, fraction, XLM_DECIMALS);
+            while buf[len - 1] == b'0' {
+                len -= 1;
+            }
+        }
+
+        String::from_bytes(&env, &buf[..len])
+    }
+
+    /// Parse an XLM decimal string into stroops
+    /// Digits past the 7th decimal place are rounded half-up; panics on malformed
+    /// input or amounts that overflow u64 stroops
+    pub fn xlm_string_to_stroops(_env: Env, amount: String) -> u64 {
+        let len = amount.len() as usize;
+        if len == 0 || len > MAX_XLM_STRING_LEN {
+            panic!("Invalid XLM amount");
+        }
+
+        let mut buf = [0u8; MAX_XLM_STRING_LEN];
+        amount.copy_into_slice(&mut buf[..len]);
+
+        let mut whole: u64 = 0;
+        let mut fraction: u64 = 0;
+        let mut fraction_digits: u32 = 0;
+        let mut round_up = false;
+        let mut seen_point = false;
+        let mut seen_digit = false;
+
+        for &byte in buf[..len].iter() {
+            match byte {
+                b'.' if !seen_point => seen_point = true,
+                b'0'..=b'9' => {
+                    let digit = (byte - b'0') as u64;
+                    seen_digit = true;
+                    if !seen_point {
+                        whole = whole
+                            .checked_mul(10)
+                            .and_then(|w| w.checked_add(digit))
+                            .expect("XLM amount overflows stroops");
+                    } else if fraction_digits < XLM_DECIMALS {
+                        fraction = fraction * 10 + digit;
+                        fraction_digits += 1;
+                    } else if fraction_digits == XLM_DECIMALS {
+                        round_up = digit >= 5;
+                        fraction_digits += 1;
+                    }
+                }
+                _ => panic!("Invalid XLM amount"),
+            }
+        }
+
+        if !seen_digit {
+            panic!("Invalid XLM amount");
+        }
+
+        // Scale a short fraction up to 7 decimals ("1.5" -> 5_000_000)
+        while fraction_digits < XLM_DECIMALS {
+            fraction *= 10;
+            fraction_digits += 1;
+        }
+
+        whole
+            .checked_mul(STROOPS_PER_XLM)
+            .and_then(|stroops| stroops.checked_add(fraction))
+            .and_then(|stroops| stroops.checked_add(round_up as u64))
+            .expect("XLM amount overflows stroops")
+    }
+}
+
+// HELPER FUNCTIONS FOR ENHANCED LENDING ALGORITHM
+
+/// Load an agent's loan, panicking unless it is still outstanding
+fn get_active_loan(env: &Env, agent: &Address) -> Loan {
+    let loan: Loan = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Loan(agent.clone()))
+        .expect("No active loan found");
+
+    if loan.repaid {
+        panic!("Loan already repaid");
+    }
+
+    loan
+}
+
+/// Record a completed loan action in the agent's ReputationManager action log
+/// Under the lenient reputation fallback a failed call is skipped rather than failing
+/// the action being logged
+fn log_action(env: &Env, agent: &Address, action: &str, amount: u64) {
+    let rep_mgr_addr: Address = env
+        .storage()
+        .persistent()
+        .get(&DataKey::ReputationManagerContract(()))
+        .expect("Contract not initialized");
+
+    let logged = ReputationManagerClient::new(env, &rep_mgr_addr).try_log_action(
+        &env.current_contract_address(),
+        agent,
+        &String::from_str(env, action),
+        &amount,
+    );
+    match logged {
+        Ok(Ok(())) => {}
+        _ if LendingDemoContract::get_config(env.clone()).lenient_reputation_fallback => {}
+        _ => panic_with_error!(env, LendingError::ReputationUnavailable),
+    }
+}
+
+/// The agent's owner as registered with the AgentManager
+fn get_agent_owner(env: &Env, agent: &Address) -> Address {
+    let agent_mgr_addr: Address = env
+        .storage()
+        .persistent()
+        .get(&DataKey::AgentManagerContract(()))
+        .expect("Contract not initialized");
+
+    AgentManagerClient::new(env, &agent_mgr_addr)
+        .get_agent_info(agent)
+        .expect("Agent not registered")
+        .owner
+}
+
+/// Collect repayment of an agent's loan from `payer` and apply the outcome to the agent
+/// The caller must already have required the payer's authorization
+/// With `from_allowance` the contract pulls the repayment from the payer's allowance
+/// to it instead of requiring the payer's authorization.
+fn settle_loan(
+    env: &Env,
+    payer: &Address,
+    agent: &Address,
+    mut loan: Loan,
+    from_allowance: bool,
+    payment_amount: Option<u64>,
+) {
+    let rep_mgr_addr: Address = env
+        .storage()
+        .persistent()
+        .get(&DataKey::ReputationManagerContract(()))
+        .expect("Contract not initialized");
+
+    let xlm_token: Address = env
+        .storage()
+        .persistent()
+        .get(&DataKey::XlmTokenContract(()))
+        .expect("Contract not initialized");
+
+    let rep_mgr_client = ReputationManagerClient::new(env, &rep_mgr_addr);
+    let xlm_client = token::Client::new(env, &xlm_token);
+
+    // INTEREST: Owed at the loan's locked-in rate; settling an interest-free loan uses
+    // up the agent's first loan, every later one pays interest
+    set_outstanding_interest(
+        env,
+        get_outstanding_interest(env).saturating_sub(loan.booked_interest),
+    );
+    let mut agent_state = LendingDemoContract::get_agent_state(env.clone(), agent.clone());
+    let interest = accrued_interest(env, &loan);
+    if loan.terms.interest_bps == 0 && !agent_state.had_first_loan {
+        agent_state.had_first_loan = true;
+        set_agent_state(env, agent, &agent_state);
+    }
+
+    // FEES: The protocol's share of the interest, part of which is waived when the
+    // repayment comes early enough to earn the early bonus
+    let config = LendingDemoContract::get_config(env.clone());
+    let fee = ((interest as u128) * (config.protocol_fee_bps as u128) / (BPS_DENOMINATOR as u128)) as u64;
+    let current_time = env.ledger().timestamp();
+    let is_early = !is_penalty_applied(env, loan.loan_id)
+        && current_time <= loan.due_date.saturating_sub(loan.terms.early_payment_threshold);
+    let waived_fee = if is_early {
+        ((fee as u128) * (config.early_fee_waiver_bps as u128) / (BPS_DENOMINATOR as u128)) as u64
+    } else {
+        0
+    };
+
+    // ACTUAL XLM TRANSFER: Receive XLM repayment (principal + interest, less any
+    // waived fee) from payer to contract
+    let repayment = loan.amount.saturating_add(interest - waived_fee);
+    let payment = payment_amount.unwrap_or(repayment);
+    if payment < repayment {
+        panic!("Payment does not cover the amount owed");
+    }
+    let contract = env.current_contract_address();
+    if from_allowance {
+        xlm_client.transfer_from(&contract, payer, &contract, &(payment as i128));
+    } else {
+        xlm_client.transfer(payer, &contract, &(payment as i128));
+    }
+
+    // CHANGE: Anything paid beyond what is owed goes straight back to the payer
+    if payment > repayment {
+        xlm_client.transfer(&contract, payer, &((payment - repayment) as i128));
+    }
+    log_action(env, agent, "repay_loan", repayment);
+
+    // REBATES: Paid interest counts towards the agent's rebate for the period
+    let interest_paid = interest - waived_fee;
+    if interest_paid > 0 {
+        let paid = LendingDemoContract::get_interest_paid(env.clone(), agent.clone());
+        if paid == 0 {
+            let mut payers: Vec<Address> = env
+                .storage()
+                .persistent()
+                .get(&DataKey::RebatePayers(()))
+                .unwrap_or(Vec::new(env));
+            payers.push_back(agent.clone());
+            env.storage().persistent().set(&DataKey::RebatePayers(()), &payers);
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::InterestPaid(agent.clone()), &paid.saturating_add(interest_paid));
+    }
+
+    // The collected fee is set aside, out of lendable liquidity
+    if fee > waived_fee {
+        let fees = LendingDemoContract::get_accrued_fees(env.clone());
+        env.storage()
+            .persistent()
+            .set(&DataKey::AccruedFees(()), &fees.saturating_add(fee - waived_fee));
+    }
+
+    // RESERVE: A share of the remaining interest tops the reserve up to its target
+    let reserve = get_reserve_balance(env);
+    let fill = ((interest as u128) * (config.reserve_fill_bps as u128) / (BPS_DENOMINATOR as u128)) as u64;
+    let fill = fill
+        .min(interest - fee)
+        .min(config.reserve_target.saturating_sub(reserve));
+    if fill > 0 {
+        env.storage()
+            .persistent()
+            .set(&DataKey::ReserveBalance(()), &(reserve + fill));
+    }
+    let recovered = repayment - (fee - waived_fee) - fill;
+
+    // Mark loan as repaid, starting the agent's borrowing cooldown
+    loan.repaid = true;
+    save_loan(env, &loan);
+    remove_defaulted_agent(env, agent);
+    agent_state.last_loan_closed_at = Some(current_time);
+    set_agent_state(env, agent, &agent_state);
+    set_total_outstanding(env, get_total_outstanding(env).saturating_sub(loan.amount));
+
+    // A default already penalized via `report_default` or `is_loan_overdue` was
+    // recorded then; settling it afterwards does not count as a second default,
+    // but earns back part of the penalty
+    if is_penalty_applied(env, loan.loan_id) {
+        split_recovery(env, &config.recovery_split, recovered);
+        recover_from_default(env, agent, &loan);
+        return;
+    }
+
+    // ENHANCED REPUTATION UPDATE: Time-based bonuses/penalties with automatic default detection
+    // under the loan's own terms (the early bonus is independent of any fee waiver)
+    let contract_addr = env.current_contract_address();
+
+    let outcome = if current_time > loan.due_date + loan.terms.default_after_seconds {
+        // AUTOMATIC DEFAULT PENALTY: Loan was overdue beyond the default threshold
+        LoanOutcome::Default
+    } else if is_early {
+        // Early payment bonus
+        LoanOutcome::Early
+    } else if current_time <= loan.due_date + loan.terms.grace_period_seconds {
+        // On-time payment (including grace period)
+        LoanOutcome::OnTime
+    } else {
+        // Late payment penalty (past grace, before default)
+        LoanOutcome::Late
+    };
+
+    let reputation_delta = if outcome == LoanOutcome::Default {
+        penalize_endorser(env, agent);
+        register_default(env, agent, &loan)
+    } else if outcome == LoanOutcome::Late && !agent_state.had_late_payment {
+        // CURE PERIOD: An agent's first late repayment ever is only a warning
+        agent_state.had_late_payment = true;
+        set_agent_state(env, agent, &agent_state);
+        LateWarning {
+            agent: agent.clone(),
+            loan_id: loan.loan_id,
+        }
+        .publish(env);
+        0
+    } else {
+        decay_repayment_bonus(env, agent, outcome_reputation_delta(outcome))
+    };
+
+    record_loan_outcome(env, agent, &loan, outcome, reputation_delta);
+    rep_mgr_client.update_score(&contract_addr, agent, &reputation_delta);
+
+    if outcome == LoanOutcome::Default {
+        split_recovery(env, &config.recovery_split, recovered);
+        recover_from_default(env, agent, &loan);
+    }
+}
+
+/// Lead time before the due date that earns a loan of a reputation tier the early bonus
+fn early_payment_threshold(config: &LendingConfig, tier: u32) -> u64 {
+    config
+        .early_payment_thresholds
+        .get(tier.saturating_sub(1))
+        .unwrap_or(EARLY_PAYMENT_THRESHOLD)
+}
+
+/// Route the reserve and treasury shares of funds recovered from a defaulted loan
+/// The pool's share needs no bookkeeping: it stays in the contract as liquidity.
+fn split_recovery(env: &Env, split: &RecoverySplit, recovered: u64) {
+    let share = |bps: u32| ((recovered as u128) * (bps as u128) / (BPS_DENOMINATOR as u128)) as u64;
+
+    let to_reserve = share(split.reserve_bps);
+    if to_reserve > 0 {
+        env.storage()
+            .persistent()
+            .set(&DataKey::ReserveBalance(()), &(get_reserve_balance(env) + to_reserve));
+    }
+
+    let to_treasury = share(split.treasury_bps);
+    if to_treasury > 0 {
+        let fees = LendingDemoContract::get_accrued_fees(env.clone());
+        env.storage()
+            .persistent()
+            .set(&DataKey::AccruedFees(()), &fees.saturating_add(to_treasury));
+    }
+}
+
+/// Restore part of a default penalty once the defaulted loan is repaid
+/// Clears the loan's penalty flag: its default is resolved rather than outstanding.
+fn recover_from_default(env: &Env, agent: &Address, loan: &Loan) {
+    let points = LendingDemoContract::get_config(env.clone()).default_recovery_points;
+    if points == 0 {
+        return;
+    }
+
+    let rep_mgr_addr: Address = env
+        .storage()
+        .persistent()
+        .get(&DataKey::ReputationManagerContract(()))
+        .expect("Contract not initialized");
+
+    let recovery = points as i32;
+    record_loan_outcome(env, agent, loan, LoanOutcome::Recovered, recovery);
+    ReputationManagerClient::new(env, &rep_mgr_addr).update_score(
+        &env.current_contract_address(),
+        agent,
+        &recovery,
+    );
+    env.storage()
+        .persistent()
+        .remove(&DataKey::PenaltyApplied(loan.loan_id));
+}
+
+/// Reject this contract or any configured protocol contract acting as an agent
+/// Such loans would move funds in a loop and corrupt the pool's accounting.
+fn reject_protocol_address(env: &Env, agent: &Address) {
+    if *agent == env.current_contract_address() {
+        panic!("Protocol contracts cannot act as agents");
+    }
+
+    let protocol_keys = [
+        DataKey::AgentManagerContract(()),
+        DataKey::ReputationManagerContract(()),
+        DataKey::XlmTokenContract(()),
+    ];
+    for key in protocol_keys.iter() {
+        let addr: Option<Address> = env.storage().persistent().get(key);
+        if addr.as_ref() == Some(agent) {
+            panic!("Protocol contracts cannot act as agents");
+        }
+    }
+}
+
+/// Get the agent manager, reputation manager and token addresses, checking they are usable
+/// Each must be set, none may be this contract or another protocol contract, and the
+/// token must answer a balance query; otherwise reverts with `LendingError::Misconfigured`.
+fn require_wiring(env: &Env) -> (Address, Address, Address) {
+    let get = |key: DataKey| -> Address {
+        match env.storage().persistent().get(&key) {
+            Some(addr) => addr,
+            None => panic_with_error!(env, LendingError::Misconfigured),
+        }
+    };
+    let agent_mgr = get(DataKey::AgentManagerContract(()));
+    let rep_mgr = get(DataKey::ReputationManagerContract(()));
+    let xlm_token = get(DataKey::XlmTokenContract(()));
+
+    let contract = env.current_contract_address();
+    if agent_mgr == contract
+        || rep_mgr == contract
+        || xlm_token == contract
+        || agent_mgr == rep_mgr
+        || agent_mgr == xlm_token
+        || rep_mgr == xlm_token
+    {
+        panic_with_error!(env, LendingError::Misconfigured);
+    }
+
+    match token::Client::new(env, &xlm_token).try_balance(&contract) {
+        Ok(Ok(_)) => (agent_mgr, rep_mgr, xlm_token),
+        _ => panic_with_error!(env, LendingError::Misconfigured),
+    }
+}
+
+/// Verify the caller is the stored admin
+fn require_admin(env: &Env, admin: &Address) {
+    admin.require_auth();
+
+    let stored_admin: Address = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Admin(()))
+        .expect("Contract not initialized");
+
+    if stored_admin != *admin {
+        panic!("Unauthorized: only admin can perform this action");
+    }
+}
+
+/// Reputation delta produced by each loan outcome (defaults escalate, see `register_default`)
+fn outcome_reputation_delta(outcome: LoanOutcome) -> i32 {
+    match outcome {
+        LoanOutcome::Early => REPUTATION_INCREASE_EARLY,
+        LoanOutcome::OnTime => REPUTATION_INCREASE_ON_TIME,
+        LoanOutcome::Late => REPUTATION_DECREASE_LATE,
+        LoanOutcome::Default => REPUTATION_DECREASE_DEFAULT,
+        LoanOutcome::Recovered => 0, // Configured, see `default_recovery_points`
+    }
+}
+
+/// Apply a delta to a score using the ReputationManager's bounds
+fn apply_bounded_delta(score: u32, delta: i32) -> u32 {
+    ((score as i32) + delta).clamp(REPUTATION_MIN as i32, REPUTATION_MAX as i32) as u32
+}
+
+/// Reputation changes this contract made to an agent outside loan outcomes, oldest first
+fn get_reputation_adjustments(env: &Env, agent: &Address) -> Vec<(u64, i32)> {
+    env.storage()
+        .persistent()
+        .get(&DataKey::ReputationAdjustments(agent.clone()))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Remember a reputation change made outside a loan outcome so it survives a recompute
+fn record_reputation_adjustment(env: &Env, agent: &Address, delta: i32) {
+    let mut adjustments = get_reputation_adjustments(env, agent);
+    adjustments.push_back((env.ledger().timestamp(), delta));
+    env.storage()
+        .persistent()
+        .set(&DataKey::ReputationAdjustments(agent.clone()), &adjustments);
+}
+
+/// Append a closed-out loan to the agent's history
+fn record_loan_outcome(
+    env: &Env,
+    agent: &Address,
+    loan: &Loan,
+    outcome: LoanOutcome,
+    reputation_delta: i32,
+) {
+    let history_key = DataKey::LoanHistory(agent.clone());
+    let mut history: Vec<LoanRecord> = env
+        .storage()
+        .persistent()
+        .get(&history_key)
+        .unwrap_or(Vec::new(env));
+
+    history.push_back(LoanRecord {
+        loan_id: loan.loan_id,
+        amount: loan.amount,
+        created_at: loan.created_at,
+        due_date: loan.due_date,
+        closed_at: env.ledger().timestamp(),
+        outcome,
+        reputation_delta,
+    });
+
+    env.storage().persistent().set(&history_key, &history);
+}
+
+/// Apply the default penalty for a loan and mark it as penalized
+/// Shared by `report_default` and the automatic check in `is_loan_overdue`
+fn apply_default_penalty(env: &Env, agent: &Address, loan: &Loan) {
+    let rep_mgr_addr: Address = env
+        .storage()
+        .persistent()
+        .get(&DataKey::ReputationManagerContract(()))
+        .expect("Contract not initialized");
+
+    let rep_mgr_client = ReputationManagerClient::new(env, &rep_mgr_addr);
+    let contract_addr = env.current_contract_address();
+
+    let penalty = register_default(env, agent, loan);
+    rep_mgr_client.update_score(&contract_addr, agent, &penalty);
+
+    record_loan_outcome(env, agent, loan, LoanOutcome::Default, penalty);
+    penalize_endorser(env, agent);
+
+    // Mark penalty as applied
+    env.storage()
+        .persistent()
+        .set(&DataKey::PenaltyApplied(loan.loan_id), &true);
+    add_defaulted_agent(env, agent);
+}
+
+/// Put an agent on the recovery worklist, dropping the oldest entry when it is full
+fn add_defaulted_agent(env: &Env, agent: &Address) {
+    let mut defaulted = LendingDemoContract::get_defaulted_agents(env.clone());
+    if defaulted.contains(agent) {
+        return;
+    }
+    if defaulted.len() >= MAX_DEFAULTED_AGENTS {
+        defaulted.pop_front();
+    }
+    defaulted.push_back(agent.clone());
+    env.storage()
+        .persistent()
+        .set(&DataKey::DefaultedAgents(()), &defaulted);
+}
+
+/// Take an agent off the recovery worklist; returns whether it was on it
+fn remove_defaulted_agent(env: &Env, agent: &Address) -> bool {
+    let mut defaulted = LendingDemoContract::get_defaulted_agents(env.clone());
+    let Some(index) = defaulted.first_index_of(agent) else {
+        return false;
+    };
+    defaulted.remove(index);
+    env.storage()
+        .persistent()
+        .set(&DataKey::DefaultedAgents(()), &defaulted);
+    true
+}
+
+/// Whether the default penalty has already been applied for a loan
+fn is_penalty_applied(env: &Env, loan_id: u64) -> bool {
+    env.storage()
+        .persistent()
+        .get(&DataKey::PenaltyApplied(loan_id))
+        .unwrap_or(false)
+}
+
+/// Scale down a repayment bonus when the agent has collected many within the decay window
+/// Penalties pass through unchanged. Records the bonus time for later repayments.
+fn decay_repayment_bonus(env: &Env, agent: &Address, delta: i32) -> i32 {
+    let config = LendingDemoContract::get_config(env.clone());
+    if delta <= 0 || config.bonus_decay_window_seconds == 0 {
+        return delta;
+    }
+
+    let now = env.ledger().timestamp();
+    let key = DataKey::RecentBonuses(agent.clone());
+    let previous: Vec<u64> = env
+        .storage()
+        .persistent()
+        .get(&key)
+        .unwrap_or(Vec::new(env));
+
+    let mut recent = Vec::new(env);
+    for awarded_at in previous.iter() {
+        if now < awarded_at + config.bonus_decay_window_seconds {
+            recent.push_back(awarded_at);
+        }
+    }
+
+    let earlier = recent.len();
+    recent.push_back(now);
+    env.storage().persistent().set(&key, &recent);
+
+    if earlier < config.bonus_decay_full_count {
+        delta
+    } else {
+        delta * config.bonus_decay_full_count as i32 / (earlier + 1) as i32
+    }
+}
+
+/// Get an agent's endorsement if it has not expired
+fn active_endorsement(env: &Env, agent: &Address) -> Option<Endorsement> {
+    let endorsement: Option<Endorsement> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Endorsement(agent.clone()));
+
+    endorsement.filter(|e| env.ledger().timestamp() < e.expires_at)
+}
+
+/// Score used for lending decisions given the agent's spot score
+fn lending_score(env: &Env, agent: &Address, spot_score: u32) -> u32 {
+    let config: LendingConfig = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Config(()))
+        .expect("Contract not initialized");
+    let window = config.reputation_twap_window_seconds;
+    let score = if window == 0 {
+        spot_score
+    } else {
+        let rep_mgr_addr: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ReputationManagerContract(()))
+            .expect("Contract not initialized");
+
+        // Without the history the spot score stands in under the lenient fallback
+        let rep_mgr_client = ReputationManagerClient::new(env, &rep_mgr_addr);
+        let history = rep_mgr_client.try_get_score_history(agent);
+        match (history, rep_mgr_client.try_get_default_score()) {
+            (Ok(Ok(history)), Ok(Ok(default_score))) => spot_score.min(time_weighted_score(
+                env,
+                &history,
+                default_score,
+                spot_score,
+                window,
+            )),
+            _ if config.lenient_reputation_fallback => spot_score,
+            _ => panic_with_error!(env, LendingError::ReputationUnavailable),
+        }
+    };
+
+    apply_endorsement_boost(env, agent, score)
+}
+
+/// Time-weighted average of an agent's score over the trailing window
+/// Time before the oldest sample counts at the default score; falls back to the
+/// spot score when there is no history
+fn time_weighted_score(
+    env: &Env,
+    history: &Vec<ScoreSample>,
+    default_score: u32,
+    spot_score: u32,
+    window: u64,
+) -> u32 {
+    let now = env.ledger().timestamp();
+    let start = now.saturating_sub(window);
+    if history.is_empty() || now == start {
+        return spot_score;
+    }
+
+    let mut current = default_score;
+    let mut cursor = start;
+    let mut weighted: u128 = 0;
+    for sample in history.iter() {
+        if sample.timestamp > start {
+            weighted += current as u128 * (sample.timestamp - cursor) as u128;
+            cursor = sample.timestamp;
+        }
+        current = sample.score;
+    }
+    weighted += current as u128 * (now - cursor) as u128;
+
+    (weighted / (now - start) as u128) as u32
+}
+
+/// Add the endorsement boost to a score while the agent's endorsement is active
+fn apply_endorsement_boost(env: &Env, agent: &Address, score: u32) -> u32 {
+    match active_endorsement(env, agent) {
+        Some(_) => (score + ENDORSEMENT_BOOST).min(REPUTATION_MAX),
+        None => score,
+    }
+}
+
+/// Propagate a default to the agent's active endorser and end the endorsement
+fn penalize_endorser(env: &Env, agent: &Address) {
+    let endorsement = match active_endorsement(env, agent) {
+        Some(endorsement) => endorsement,
+        None => return,
+    };
+
+    let rep_mgr_addr: Address = env
+        .storage()
+        .persistent()
+        .get(&DataKey::ReputationManagerContract(()))
+        .expect("Contract not initialized");
+
+    let rep_mgr_client = ReputationManagerClient::new(env, &rep_mgr_addr);
+    rep_mgr_client.update_score(
+        &env.current_contract_address(),
+        &endorsement.endorser,
+        &REPUTATION_DECREASE_ENDORSER,
+    );
+    record_reputation_adjustment(env, &endorsement.endorser, REPUTATION_DECREASE_ENDORSER);
+
+    env.storage()
+        .persistent()
+        .remove(&DataKey::Endorsement(agent.clone()));
+}
+
+fn set_agent_state(env: &Env, agent: &Address, agent_state: &AgentState) {
+    env.storage()
+        .persistent()
+        .set(&DataKey::AgentState(agent.clone()), agent_state);
+}
+
+/// Seconds the agent has gone without a default
+fn good_standing_duration(env: &Env, agent_state: &AgentState) -> u64 {
+    match agent_state.good_standing_since {
+        Some(since) => env.ledger().timestamp().saturating_sub(since),
+        None => 0,
+    }
+}
+
+/// Count a default against the agent and return its escalated reputation penalty
+/// A long enough good-standing run clears earlier defaults first; the
+/// good-standing clock then restarts from this default.
+fn register_default(env: &Env, agent: &Address, loan: &Loan) -> i32 {
+    let mut agent_state: AgentState = env
+        .storage()
+        .persistent()
+        .get(&DataKey::AgentState(agent.clone()))
+        .unwrap_or_default();
+
+    let penalty = default_penalty(env, &agent_state, loan);
+
+    if good_standing_duration(env, &agent_state) >= DEFAULT_COUNT_RESET_SECONDS {
+        agent_state.default_count = 0;
+    }
+    agent_state.default_count += 1;
+    agent_state.good_standing_since = Some(env.ledger().timestamp());
+    set_agent_state(env, agent, &agent_state);
+
+    penalty
+}
+
+/// Reputation penalty a default of `loan` would cost given the agent's prior defaults
+/// Follows the escalation schedule (after any good-standing reset) and principal scaling
+fn default_penalty(env: &Env, agent_state: &AgentState, loan: &Loan) -> i32 {
+    let prior_defaults = if good_standing_duration(env, agent_state) >= DEFAULT_COUNT_RESET_SECONDS {
+        0
+    } else {
+        agent_state.default_count
+    };
+
+    let config: LendingConfig = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Config(()))
+        .expect("Contract not initialized");
+    let schedule = config.default_penalty_schedule;
+    let step = prior_defaults.min(schedule.len() - 1);
+    let penalty = schedule.get(step).unwrap();
+
+    if config.scale_default_penalty {
+        scale_penalty_by_principal(penalty, loan.amount, loan.terms.tier_limit)
+    } else {
+        penalty
+    }
+}
+
+/// Scale a penalty by principal / tier limit, keeping at least 1 point and at most the full penalty
+fn scale_penalty_by_principal(penalty: i32, principal: u64, tier_limit: u64) -> i32 {
+    if tier_limit == 0 || penalty == 0 {
+        return penalty;
+    }
+
+    let share = principal.min(tier_limit) as i64;
+    let scaled = (penalty as i64 * share / tier_limit as i64) as i32;
+    scaled.min(-1)
+}
+
+/// Reputation tier 1-4 for lending (0 = not eligible)
+fn reputation_tier(reputation_score: u32) -> u32 {
+    match reputation_score {
+        50..=59 => 1,
+        60..=74 => 2,
+        75..=89 => 3,
+        90..=100 => 4,
+        _ => 0,
+    }
+}
+
+/// Due date of a loan issued now for `duration_seconds`, within the configured maximum term
+fn loan_due_date(env: &Env, config: &LendingConfig, duration_seconds: u64) -> u64 {
+    if duration_seconds > config.max_loan_duration_seconds {
+        panic!("Loan duration exceeds the maximum term");
+    }
+    env.ledger()
+        .timestamp()
+        .checked_add(duration_seconds)
+        .expect("Loan due date overflows")
+}
+
+/// Pool utilization a reputation tier may borrow up to (tier 0 falls back to the hard cap)
+fn tier_utilization_cap(config: &LendingConfig, tier: u32) -> u32 {
+    if tier == 0 {
+        return config.utilization_hard_cap_bps;
+    }
+    config
+        .tier_utilization_caps_bps
+        .get(tier - 1)
+        .unwrap_or(config.utilization_hard_cap_bps)
+}
+
+/// Base loan limit of a reputation tier, before any good-standing bonus
+fn tier_max_loan(env: &Env, tier: u32) -> u64 {
+    if let Some(oracle) = LendingDemoContract::get_price_oracle(env.clone()) {
+        let usd_cents = match tier {
+            1 => TIER_1_MAX_LOAN_USD_CENTS,
+            2 => TIER_2_MAX_LOAN_USD_CENTS,
+            3 => TIER_3_MAX_LOAN_USD_CENTS,
+            4 => TIER_4_MAX_LOAN_USD_CENTS,
+            _ => 0,
+        };
+        let price = PriceOracleClient::new(env, &oracle).xlm_usd_price();
+        if price == 0 {
+            panic!("Oracle returned no XLM price");
+        }
+        let stroops = (usd_cents as u128) * PRICE_UNITS_PER_USD_CENT * (STROOPS_PER_XLM as u128)
+            / (price as u128);
+        return stroops.min(u64::MAX as u128) as u64;
+    }
+
+    match tier {
+        1 => TIER_1_MAX_LOAN,     // 0.5 XLM for new/low reputation
+        2 => TIER_2_MAX_LOAN,     // 2.0 XLM for decent reputation
+        3 => TIER_3_MAX_LOAN,     // 5.0 XLM for good reputation
+        4 => TIER_4_MAX_LOAN,     // 10.0 XLM for excellent reputation
+        _ => 0,                   // No loans for very low reputation
+    }
+}
+
+/// Calculate maximum loan amount based on reputation score (tiered system)
+/// Agents eligible for a loan earn a bonus proportional to their good-standing
+/// duration, never exceeding the tier-4 limit
+fn calculate_max_loan_amount(env: &Env, reputation_score: u32, good_standing_seconds: u64) -> u64 {
+    let tier_limit = tier_max_loan(env, reputation_tier(reputation_score));
+
+    if tier_limit == 0 {
+        return 0;
+    }
+
+    let standing_bonus = (good_standing_seconds / SECONDS_PER_DAY)
+        .saturating_mul(GOOD_STANDING_BONUS_PER_DAY);
+    tier_limit
+        .saturating_add(standing_bonus)
+        .min(tier_max_loan(env, REPUTATION_TIER_COUNT))
+}
+
+/// Write `value` as decimal digits at `start`, left-padded with zeros to `min_digits`
+/// Returns the index just past the last digit written
+fn write_digits(buf: &mut [u8], start: usize, value: u64, min_digits: u32) -> usize {
+    let mut digits = [0u8; 20];
+    let mut count = 0;
+    let mut remaining = value;
+    while remaining > 0 || count < min_digits as usize {
+        digits[count] = b'0' + (remaining % 10) as u8;
+        remaining /= 10;
+        count += 1;
+    }
+
+    for i in 0..count {
+        buf[start + i] = digits[count - 1 - i];
+    }
+    start + count
+}
+
+/// Interest a loan owes if repaid at its due date, under the configured interest mode
+/// This is what the pool books as outstanding interest while the loan is lent out.
+fn interest_at_due_date(env: &Env, loan: &Loan) -> u64 {
+    if loan.terms.interest_bps == 0 {
+        return 0;
+    }
+
+    let config = LendingDemoContract::get_config(env.clone());
+    calculate_accrued_interest(
+        loan.amount,
+        loan.terms.interest_bps,
+        loan.due_date.saturating_sub(loan.created_at),
+        config.interest_period_seconds,
+        config.interest_compounding,
+    )
+    .max(loan.terms.min_interest)
+}
+
+/// Interest accrued on a loan so far at its locked-in rate, under the configured interest mode
+/// Never less than the loan's minimum interest; the agent's interest-free first loan
+/// accrues nothing
+fn accrued_interest(env: &Env, loan: &Loan) -> u64 {
+    if loan.terms.interest_bps == 0 {
+        return 0;
+    }
+
+    let config = LendingDemoContract::get_config(env.clone());
+    let elapsed = env.ledger().timestamp().saturating_sub(loan.created_at);
+    calculate_accrued_interest(
+        loan.amount,
+        loan.terms.interest_bps,
+        elapsed,
+        config.interest_period_seconds,
+        config.interest_compounding,
+    )
+    .max(loan.terms.min_interest)
+}
+
+/// Interest on `principal` after `elapsed` seconds at `rate_bps` per period
+/// Simple interest is linear in time; compounding applies per whole period (raising
+/// the growth factor to the period count by squaring) and accrues linearly within the
+/// current one. Saturates instead of overflowing.
+fn calculate_accrued_interest(
+    principal: u64,
+    rate_bps: u32,
+    elapsed: u64,
+    period: u64,
+    compounding: bool,
+) -> u64 {
+    let rate = rate_bps as u128;
+    let denominator = BPS_DENOMINATOR as u128;
+    if period == 0 {
+        return (principal as u128 * rate / denominator) as u64;
+    }
+
+    let max_interest = (u64::MAX - principal) as u128;
+    let mut balance = principal as u128;
+
+    let partial_seconds = if compounding {
+        let mut periods = (elapsed / period).min(MAX_COMPOUNDING_PERIODS);
+        let mut factor = (denominator + rate) * GROWTH_SCALE / denominator;
+        let mut growth = GROWTH_SCALE;
+        while periods > 0 {
+            if periods & 1 == 1 {
+                growth = mul_growth(growth, factor);
+            }
+            periods >>= 1;
+            if periods > 0 {
+                factor = mul_growth(factor, factor);
+            }
+        }
+
+        balance = mul_growth(growth, balance * GROWTH_SCALE) / GROWTH_SCALE;
+        if balance - principal as u128 >= max_interest {
+            return max_interest as u64;
+        }
+        elapsed % period
+    } else {
+        elapsed
+    };
+
+    let partial_interest = balance
+        .saturating_mul(rate)
+        .saturating_mul(partial_seconds as u128)
+        / (denominator * period as u128);
+    let interest = (balance - principal as u128).saturating_add(partial_interest);
+    interest.min(max_interest) as u64
+}
+
+/// Multiply two GROWTH_SCALE fixed-point values, rounding down
+/// Saturates at u64::MAX (in fixed point), beyond which no balance can grow anyway.
+fn mul_growth(a: u128, b: u128) -> u128 {
+    let cap = u64::MAX as u128 * GROWTH_SCALE;
+    let (a_whole, a_fraction) = (a / GROWTH_SCALE, a % GROWTH_SCALE);
+    let (b_whole, b_fraction) = (b / GROWTH_SCALE, b % GROWTH_SCALE);
+    a_whole
+        .saturating_mul(b_whole)
+        .saturating_mul(GROWTH_SCALE)
+        .saturating_add(a_whole * b_fraction)
+        .saturating_add(a_fraction * b_whole)
+        .saturating_add(a_fraction * b_fraction / GROWTH_SCALE)
+        .min(cap)
+}
+
+/// Calculate pool utilization in basis points to prevent over-lending
+/// `total_pool` is the XLM held by the contract plus the principal lent out
+fn calculate_pool_utilization_bps(outstanding: u64, total_pool: u64) -> u32 {
+    if total_pool == 0 {
+        return BPS_DENOMINATOR; // 100% utilization if no liquidity
+    }
+
+    let utilization = (outstanding as u128) * (BPS_DENOMINATOR as u128) / (total_pool as u128);
+    (utilization as u32).min(BPS_DENOMINATOR)
+}
+
+/// Total principal currently lent out and not yet repaid
+fn get_total_outstanding(env: &Env) -> u64 {
+    env.storage()
+        .persistent()
+        .get(&DataKey::TotalOutstanding(()))
+        .unwrap_or(0)
+}
+
+fn set_total_outstanding(env: &Env, outstanding: u64) {
+    env.storage()
+        .persistent()
+        .set(&DataKey::TotalOutstanding(()), &outstanding);
+}
+
+/// Persist a loan as its agent's latest loan and under its id
+/// Also keeps the active-loan index in step with the loan's repaid flag
+fn save_loan(env: &Env, loan: &Loan) {
+    let storage = env.storage().persistent();
+    storage.set(&DataKey::Loan(loan.agent.clone()), loan);
+    storage.set(&DataKey::LoanById(loan.loan_id), loan);
+
+    let mut active = get_active_loans(env);
+    let position = active.first_index_of(&loan.agent);
+    match (loan.repaid, position) {
+        (false, None) => active.push_back(loan.agent.clone()),
+        (true, Some(index)) => {
+            active.remove(index);
+        }
+        _ => return,
+    }
+    storage.set(&DataKey::ActiveLoans(()), &active);
+}
+
+/// Agents with an unrepaid loan, in order of issue
+fn get_active_loans(env: &Env) -> Vec<Address> {
+    env.storage()
+        .persistent()
+        .get(&DataKey::ActiveLoans(()))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Id the next issued loan will receive (ids start at 1)
+fn next_loan_id(env: &Env) -> u64 {
+    env.storage()
+        .persistent()
+        .get(&DataKey::NextLoanId(()))
+        .unwrap_or(1)
+}
+
+/// Every agent that has taken a loan, in order of first borrow
+fn get_borrowers(env: &Env) -> Vec<Address> {
+    env.storage()
+        .persistent()
+        .get(&DataKey::Borrowers(()))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Every agent that has endorsed another
+fn get_endorsers(env: &Env) -> Vec<Address> {
+    env.storage()
+        .persistent()
+        .get(&DataKey::Endorsers(()))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Pool shares issued to all lenders
+fn get_total_shares(env: &Env) -> u64 {
+    env.storage()
+        .persistent()
+        .get(&DataKey::TotalShares(()))
+        .unwrap_or(0)
+}
+
+fn set_lender_shares(env: &Env, lender: &Address, shares: u64) {
+    let mut lenders = get_lenders(env);
+    if !lenders.contains(lender) {
+        lenders.push_back(lender.clone());
+        env.storage().persistent().set(&DataKey::Lenders(()), &lenders);
+    }
+    env.storage()
+        .persistent()
+        .set(&DataKey::LenderShares(lender.clone()), &shares);
+}
+
+/// Every agent this contract may have adjusted the reputation of outside a loan outcome
+/// (borrowers, lenders and endorsers)
+fn adjustable_agents(env: &Env) -> Vec<Address> {
+    let mut agents = get_borrowers(env);
+    for agent in get_lenders(env).iter().chain(get_endorsers(env).iter()) {
+        if !agents.contains(&agent) {
+            agents.push_back(agent);
+        }
+    }
+    agents
+}
+
+/// Every lender that has held pool shares
+fn get_lenders(env: &Env) -> Vec<Address> {
+    env.storage()
+        .persistent()
+        .get(&DataKey::Lenders(()))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Value backing pool shares: idle liquidity plus principal lent out
+fn get_pool_value(env: &Env) -> u64 {
+    let xlm_token: Address = env
+        .storage()
+        .persistent()
+        .get(&DataKey::XlmTokenContract(()))
+        .expect("Contract not initialized");
+
+    let liquidity = pool_balance(env, &token::Client::new(env, &xlm_token));
+    liquidity + get_total_outstanding(env)
+}
+
+/// Require `caller` to be an approved keeper
+fn require_keeper(env: &Env, caller: &Address) {
+    caller.require_auth();
+
+    let is_keeper: bool = env
+        .storage()
+        .persistent()
+        .get(&DataKey::ApprovedKeeper(caller.clone()))
+        .unwrap_or(false);
+
+    if !is_keeper {
+        panic!("Unauthorized: caller is not an approved keeper");
+    }
+}
+
+/// XLM a lender may still withdraw this period, with its current withdrawal window
+fn withdrawal_allowance(env: &Env, provider: &Address) -> (u64, WithdrawalWindow) {
+    let shares = LendingDemoContract::get_lender_shares(env.clone(), provider.clone());
+    let position = if shares == 0 {
+        0
+    } else {
+        ((shares as u128) * (get_pool_value(env) as u128) / (get_total_shares(env) as u128)) as u64
+    };
+
+    let now = env.ledger().timestamp();
+    let stored: Option<WithdrawalWindow> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::WithdrawalWindow(provider.clone()));
+    let window = match stored {
+        Some(window) if now < window.period_start + WITHDRAWAL_PERIOD_SECONDS => window,
+        _ => WithdrawalWindow { period_start: now, withdrawn: 0 },
+    };
+
+    let rep_mgr_addr: Address = env
+        .storage()
+        .persistent()
+        .get(&DataKey::ReputationManagerContract(()))
+        .expect("Contract not initialized");
+    let score = ReputationManagerClient::new(env, &rep_mgr_addr).get_score(provider);
+
+    let limit_bps = match reputation_tier(score) {
+        0 => TIER_0_WITHDRAWAL_BPS,
+        1 => TIER_1_WITHDRAWAL_BPS,
+        2 => TIER_2_WITHDRAWAL_BPS,
+        _ => return (position, window),
+    };
+    let cap = ((position + window.withdrawn) as u128 * limit_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+    (cap.saturating_sub(window.withdrawn).min(position), window)
+}
+
+/// Store the utilization history, keeping only the newest samples the config allows
+fn save_utilization_history(env: &Env, mut history: Vec<(u64, u32)>) {
+    let size = LendingDemoContract::get_config(env.clone()).utilization_history_size;
+    while history.len() > size {
+        history.pop_front();
+    }
+    env.storage()
+        .persistent()
+        .set(&DataKey::UtilizationHistory(()), &history);
+}
+
+/// Bounty for reporting a default on a loan of `principal`
+fn default_bounty(principal: u64) -> u64 {
+    let share = principal as u128 * DEFAULT_REPORT_BOUNTY_BPS as u128 / BPS_DENOMINATOR as u128;
+    (share as u64).min(MAX_DEFAULT_REPORT_BOUNTY)
+}
+
+/// XLM held by the contract that belongs to the pool (accrued protocol fees and reserve excluded)
+fn pool_balance(env: &Env, xlm_client: &token::Client) -> u64 {
+    let balance = xlm_client.balance(&env.current_contract_address()) as u64;
+    balance
+        .saturating_sub(LendingDemoContract::get_accrued_fees(env.clone()))
+        .saturating_sub(get_reserve_balance(env))
+}
+
+/// Reserve set aside from interest income
+fn get_reserve_balance(env: &Env) -> u64 {
+    env.storage()
+        .persistent()
+        .get(&DataKey::ReserveBalance(()))
+        .unwrap_or(0)
+}
+
+/// Interest owed on loans currently lent out
+fn get_outstanding_interest(env: &Env) -> u64 {
+    env.storage()
+        .persistent()
+        .get(&DataKey::OutstandingInterest(()))
+        .unwrap_or(0)
+}
+
+fn set_outstanding_interest(env: &Env, interest: u64) {
+    env.storage()
+        .persistent()
+        .set(&DataKey::OutstandingInterest(()), &interest);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use soroban_sdk::{contract, contractimpl, token::StellarAssetClient, Env, IntoVal, Symbol};
+    use soroban_sdk::testutils::{Address as _, Events, Ledger};
+
+    /// AgentManager stand-in that authorizes every registered agent
+    #[contract]
+    pub struct MockAgentManager;
+
+    #[contractimpl]
+    impl MockAgentManager {
+        pub fn is_authorized(_env: Env, _agent: Address, _action: String, _amount: u64) -> bool {
+            true
+        }
+
+        pub fn register_agent(
+            env: Env,
+            owner: Address,
+            agent: Address,
+            _scopes: Vec<String>,
+            _max_amount: u64,
+        ) {
+            owner.require_auth();
+            env.storage().persistent().set(&agent, &owner);
+
+            let mut agents = Self::get_owner_agents(env.clone(), owner.clone());
+            agents.push_back(agent);
+            env.storage().persistent().set(&(owner, 0u32), &agents);
+        }
+
+        pub fn get_owner_agents(env: Env, owner: Address) -> Vec<Address> {
+            env.storage()
+                .persistent()
+                .get(&(owner, 0u32))
+                .unwrap_or(Vec::new(&env))
+        }
+
+        pub fn get_agent_owner(env: Env, agent: Address) -> Option<Address> {
+            env.storage().persistent().get(&agent)
+        }
+
+        pub fn set_owner_budget(env: Env, owner: Address, budget: Option<u64>) {
+            match budget {
+                Some(budget) => env.storage().persistent().set(&(owner, 1u32), &budget),
+                None => env.storage().persistent().remove(&(owner, 1u32)),
+            }
+        }
+
+        pub fn get_owner_budget(env: Env, owner: Address) -> Option<u64> {
+            env.storage().persistent().get(&(owner, 1u32))
+        }
+
+        pub fn get_agent_info(env: Env, agent: Address) -> Option<AgentInfo> {
+            let revoked = env.storage().persistent().has(&(agent.clone(), ()));
+            Self::get_agent_owner(env.clone(), agent).map(|owner| AgentInfo {
+                owner,
+                scopes: Vec::new(&env),
+                max_amount: u64::MAX,
+                revoked,
+            })
+        }
+
+        /// Mark an agent revoked without touching `is_authorized`
+        pub fn revoke_agent(env: Env, agent: Address) {
+            env.storage().persistent().set(&(agent, ()), &true);
+        }
+
+        pub fn set_reputation_manager(env: Env, rep: Address) {
+            env.storage().instance().set(&(), &rep);
+        }
+
+        pub fn get_reputation_manager(env: Env) -> Option<Address> {
+            env.storage().instance().get(&())
+        }
+
+        pub fn authorize_and_score(env: Env, agent: Address, action: String, amount: u64) -> (bool, u32) {
+            let rep: Address = env.storage().instance().get(&()).unwrap();
+            let score = MockReputationManagerClient::new(&env, &rep).get_score(&agent);
+            (Self::is_authorized(env, agent, action, amount), score)
+        }
+    }
+
+    /// ReputationManager stand-in that is unreachable
+    #[contract]
+    pub struct BrokenReputationManager;
+
+    #[contractimpl]
+    impl BrokenReputationManager {
+        pub fn get_score(_env: Env, _agent: Address) -> u32 {
+            panic!("reputation manager unavailable")
+        }
+    }
+
+    /// Price oracle stand-in returning a settable price
+    #[contract]
+    pub struct MockPriceOracle;
+
+    #[contractimpl]
+    impl MockPriceOracle {
+        pub fn set_price(env: Env, price: u64) {
+            env.storage().instance().set(&(), &price);
+        }
+
+        pub fn xlm_usd_price(env: Env) -> u64 {
+            env.storage().instance().get(&()).unwrap()
+        }
+    }
+
+    /// ReputationManager stand-in storing bounded scores per agent
+    #[contract]
+    pub struct MockReputationManager;
+
+    #[contractimpl]
+    impl MockReputationManager {
+        pub fn set_score(env: Env, agent: Address, score: u32) {
+            env.storage().persistent().set(&agent, &score);
+
+            let mut history = Self::get_score_history(env.clone(), agent.clone());
+            history.push_back(ScoreSample { timestamp: env.ledger().timestamp(), score });
+            env.storage().persistent().set(&(agent, ()), &history);
+        }
+
+        pub fn get_score(env: Env, agent: Address) -> u32 {
+            env.storage()
+                .persistent()
+                .get(&agent)
+                .unwrap_or_else(|| Self::get_default_score(env.clone()))
+        }
+
+        pub fn set_default_score(env: Env, score: u32) {
+            env.storage().persistent().set(&0u32, &score);
+        }
+
+        pub fn get_default_score(env: Env) -> u32 {
+            env.storage().persistent().get(&0u32).unwrap_or(50)
+        }
+
+        pub fn update_score(env: Env, _caller: Address, agent: Address, delta: i32) {
+            let score = Self::get_score(env.clone(), agent.clone());
+            let new_score = ((score as i32) + delta).clamp(0, 100) as u32;
+            Self::set_score(env, agent, new_score);
+        }
+
+        pub fn get_score_history(env: Env, agent: Address) -> Vec<ScoreSample> {
+            env.storage()
+                .persistent()
+                .get(&(agent, ()))
+                .unwrap_or(Vec::new(&env))
+        }
+
+        pub fn log_action(env: Env, _caller: Address, agent: Address, action: String, amount: u64) {
+            let mut log = Self::get_action_log(env.clone(), agent.clone());
+            log.push_back((action, amount));
+            env.storage().persistent().set(&(agent, 0u32), &log);
+        }
+
+        pub fn get_action_log(env: Env, agent: Address) -> Vec<(String, u64)> {
+            env.storage()
+                .persistent()
+                .get(&(agent, 0u32))
+                .unwrap_or(Vec::new(&env))
+        }
+    }
+
+    struct TestSetup<'a> {
+        env: Env,
+        admin: Address,
+        client: LendingDemoContractClient<'a>,
+        agents: MockAgentManagerClient<'a>,
+        rep: MockReputationManagerClient<'a>,
+        token: token::Client<'a>,
+        token_admin: StellarAssetClient<'a>,
+    }
+
+    /// Deploy the lending contract against mock protocol contracts and a real token
+    fn setup(pool_liquidity: i128) -> TestSetup<'static> {
+        setup_with_floor(pool_liquidity, 0)
+    }
+
+    /// Same as `setup`, with a minimum pool balance configured
+    fn setup_with_floor(pool_liquidity: i128, min_pool_balance: u64) -> TestSetup<'static> {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let contract_id = env.register(LendingDemoContract, ());
+        let client = LendingDemoContractClient::new(&env, &contract_id);
+        let agent_mgr_id = env.register(MockAgentManager, ());
+        let agents = MockAgentManagerClient::new(&env, &agent_mgr_id);
+        let rep_id = env.register(MockReputationManager, ());
+        let rep = MockReputationManagerClient::new(&env, &rep_id);
+        agents.set_reputation_manager(&rep_id);
+
+        let admin = Address::generate(&env);
+        let token_id = env.register_stellar_asset_contract_v2(admin.clone()).address();
+        let token = token::Client::new(&env, &token_id);
+        let token_admin = StellarAssetClient::new(&env, &token_id);
+
+        client.initialize(
+            &admin,
+            &agent_mgr_id,
+            &rep_id,
+            &token_id,
+            &6_000,
+            &8_000,
+            &min_pool_balance,
+            &None,
+        );
+        token_admin.mint(&contract_id, &pool_liquidity);
+
+        TestSetup { env, admin, client, agents, rep, token, token_admin }
+    }
+
+    /// Create an agent holding enough XLM to repay its loans
+    fn funded_agent(t: &TestSetup, balance: i128) -> Address {
+        let agent = Address::generate(&t.env);
+        let owner = Address::generate(&t.env);
+        t.agents.register_agent(&owner, &agent, &Vec::new(&t.env), &u64::MAX);
+        t.token_admin.mint(&agent, &balance);
+        agent
+    }
+
+    #[test]
+    fn test_contract_initialization() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let contract_id = env.register(LendingDemoContract, ());
+        let client = LendingDemoContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let agent_manager_addr = Address::generate(&env);
+        let reputation_manager_addr = Address::generate(&env);
+        let xlm_token_addr = Address::generate(&env);
+
+        // Test initialization
+        client.initialize(
+            &admin,
+            &agent_manager_addr,
+            &reputation_manager_addr,
+            &xlm_token_addr,
+            &6_000,
+            &8_000,
+            &0,
+            &None,
+        );
+
+        // Verify addresses are stored (we can't directly check storage in tests,
+        // but if initialization didn't panic, it worked)
+    }
+
+    #[test]
+    fn test_get_wiring() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let contract_id = env.register(LendingDemoContract, ());
+        let client = LendingDemoContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let agent_manager_addr = Address::generate(&env);
+        let reputation_manager_addr = Address::generate(&env);
+        let xlm_token_addr = Address::generate(&env);
+
+        // Nothing is wired up before initialization
+        let wiring = client.get_wiring();
+        assert_eq!(wiring.agent_manager, None);
+        assert_eq!(wiring.reputation_manager, None);
+        assert_eq!(wiring.xlm_token, None);
+        assert_eq!(wiring.admin, None);
+
+        client.initialize(
+            &admin,
+            &agent_manager_addr,
+            &reputation_manager_addr,
+            &xlm_token_addr,
+            &6_000,
+            &8_000,
+            &0,
+            &None,
+        );
+
+        let wiring = client.get_wiring();
+        assert_eq!(wiring.agent_manager, Some(agent_manager_addr));
+        assert_eq!(wiring.reputation_manager, Some(reputation_manager_addr));
+        assert_eq!(wiring.xlm_token, Some(xlm_token_addr));
+        assert_eq!(wiring.admin, Some(admin));
+    }
+
+    #[test]
+    fn test_initialize_only_once() {
+        let t = setup(0);
+
+        // A second initialization cannot take over the contract
+        let attacker = Address::generate(&t.env);
+        let result = t.client.try_initialize(
+            &attacker,
+            &attacker,
+            &t.rep.address,
+            &t.token.address,
+            &6_000,
+            &8_000,
+            &0,
+            &None,
+        );
+        assert!(result.is_err());
+        assert_eq!(t.client.get_wiring().admin, Some(t.admin.clone()));
+        assert_eq!(t.client.get_wiring().agent_manager, Some(t.agents.address.clone()));
+    }
+
+    #[test]
+    fn test_loan_storage() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let contract_id = env.register(LendingDemoContract, ());
+        let client = LendingDemoContractClient::new(&env, &contract_id);
+
+        let agent = Address::generate(&env);
+
+        // Initially no loan should exist
+        let loan = client.get_loan(&agent);
+        assert!(loan.is_none());
+
+        // Note: We can't test request_loan without the other contracts deployed
+        // This demonstrates the contract structure is correct
+    }
+
+    #[test]
+    fn test_recompute_reputation_from_history() {
+        let t = setup(100_000_000);
+        let agent = funded_agent(&t, 10_000_000);
+        let day = 24 * 60 * 60;
+
+        // Early repayment: +12
+        t.client.request_loan(&agent, &1_000_000, &(7 * day));
+        t.client.repay_loan(&agent, &None);
+
+        // Default reported past grace: -25
+        t.client.request_loan(&agent, &1_000_000, &day);
+        t.env.ledger().set_timestamp(3 * day);
+        t.client.report_default(&Address::generate(&t.env), &agent);
+        assert_eq!(t.token.balance(&t.client.address), 99_000_000 - 10_000); // 1% bounty
+
+        let history = t.client.get_loan_history(&agent);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0).unwrap().outcome, LoanOutcome::Early);
+        assert_eq!(history.get(1).unwrap().outcome, LoanOutcome::Default);
+        assert_eq!(t.rep.get_score(&agent), 37);
+
+        // Corrupt the stored score, then rebuild it from history
+        t.rep.set_score(&agent, &90);
+        assert_eq!(t.client.recompute_reputation(&t.admin, &agent), 37);
+        assert_eq!(t.rep.get_score(&agent), 37);
+    }
+
+    #[test]
+    fn test_recompute_reputation_from_default_score_and_adjustments() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let keeper = Address::generate(&t.env);
+        let agent = funded_agent(&t, 10_000_000);
+        t.client.approve_keeper(&t.admin, &keeper);
+        t.rep.set_default_score(&60);
+
+        // Credit discipline reward (+2), then an early repayment (+12)
+        t.client.request_loan(&agent, &(TIER_1_MAX_LOAN / 5), &(40 * day));
+        t.env.ledger().set_timestamp(31 * day);
+        t.client.reward_credit_discipline(&keeper, &agent);
+        t.client.repay_loan(&agent, &None);
+        assert_eq!(t.rep.get_score(&agent), 74);
+
+        t.rep.set_score(&agent, &10);
+        assert_eq!(t.client.recompute_reputation(&t.admin, &agent), 74);
+        assert_eq!(t.rep.get_score(&agent), 74);
+
+        // Time before the first score sample averages at the default score
+        let newcomer = Address::generate(&t.env);
+        t.client.set_reputation_twap_window(&t.admin, &(10 * day));
+        t.rep.set_score(&newcomer, &80);
+        t.env.ledger().set_timestamp(36 * day);
+        assert_eq!(t.client.get_effective_reputation(&newcomer), 70);
+    }
+
+    #[test]
+    fn test_utilization_warning_zone_and_hard_cap() {
+        let t = setup(10_000_000);
+        let day = 24 * 60 * 60;
+        let config = t.client.get_config();
+        assert_eq!(config.utilization_warning_bps, 6_000);
+        assert_eq!(config.utilization_hard_cap_bps, 8_000);
+
+        // 50% utilization: below the warning level
+        let first = funded_agent(&t, 0);
+        t.client.request_loan(&first, &5_000_000, &day);
+        assert_eq!(t.client.get_pool_utilization(), 50);
+
+        // 70% utilization: in the warning zone, still allowed but flagged
+        let second = funded_agent(&t, 0);
+        t.client.request_loan(&second, &2_000_000, &day);
+        let topics: Vec<soroban_sdk::Val> = (
+            Symbol::new(&t.env, "pool"),
+            Symbol::new(&t.env, "high_utilization"),
+        )
+            .into_val(&t.env);
+        let flagged = t
+            .env
+            .events()
+            .all()
+            .iter()
+            .any(|(contract, event_topics, _)| contract == t.client.address && event_topics == topics);
+        assert!(flagged);
+        assert_eq!(t.client.get_pool_utilization(), 70);
+
+        // 90% utilization: above the hard cap, rejected
+        let third = funded_agent(&t, 0);
+        assert!(t.client.try_request_loan(&third, &2_000_000, &day).is_err());
+        assert_eq!(t.client.get_pool_utilization(), 70);
+
+        // Just past the cap is rejected, landing exactly on it is allowed
+        assert!(t.client.try_request_loan(&third, &1_000_001, &day).is_err());
+        t.client.request_loan(&third, &1_000_000, &day);
+        assert_eq!(t.client.get_pool_utilization(), 80);
+    }
+
+    #[test]
+    fn test_first_loan_is_interest_free() {
+        let t = setup(100_000_000);
+        let agent = funded_agent(&t, 10_000_000);
+        let day = 24 * 60 * 60;
+        assert!(!t.client.get_agent_state(&agent).had_first_loan);
+
+        // First loan: only the principal comes back
+        t.client.request_loan(&agent, &2_000_000, &(7 * day));
+        t.client.repay_loan(&agent, &None);
+        assert_eq!(t.token.balance(&agent), 10_000_000);
+        assert!(t.client.get_agent_state(&agent).had_first_loan);
+
+        // Second loan: principal plus 5% interest
+        t.client.request_loan(&agent, &2_000_000, &(7 * day));
+        t.client.repay_loan(&agent, &None);
+        assert_eq!(t.token.balance(&agent), 9_900_000);
+        assert_eq!(t.token.balance(&t.client.address), 100_100_000);
+
+        // Each loan and repayment lands in the agent's action log
+        let log = t.rep.get_action_log(&agent);
+        assert_eq!(log.len(), 4);
+        assert_eq!(log.get(0).unwrap(), (String::from_str(&t.env, "borrow"), 2_000_000));
+        assert_eq!(log.get(3).unwrap(), (String::from_str(&t.env, "repay_loan"), 2_100_000));
+    }
+
+    #[test]
+    fn test_pool_status_reflects_outstanding_loan() {
+        let t = setup(10_000_000);
+        let agent = funded_agent(&t, 10_000_000);
+        let day = 24 * 60 * 60;
+
+        // Use up the interest-free first loan so the next one accrues interest
+        t.client.request_loan(&agent, &1_000_000, &(7 * day));
+        t.client.repay_loan(&agent, &None);
+
+        t.client.request_loan(&agent, &4_000_000, &(7 * day));
+        let status = t.client.get_pool_status();
+        assert_eq!(status.total_liquidity, 6_000_000);
+        assert_eq!(status.total_outstanding, 4_000_000);
+        assert_eq!(status.accrued_interest, 200_000);
+        assert_eq!(status.reserve_balance, 0);
+        assert_eq!(status.utilization_bps, 4_000);
+
+        t.client.repay_loan(&agent, &None);
+        let status = t.client.get_pool_status();
+        assert_eq!(status.total_liquidity, 10_200_000);
+        assert_eq!(status.total_outstanding, 0);
+        assert_eq!(status.accrued_interest, 0);
+        assert_eq!(status.utilization_bps, 0);
+    }
+
+    #[test]
+    fn test_grace_and_default_windows_are_distinct() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        t.client.set_default_windows(&t.admin, &day, &(3 * day));
+
+        // Each agent borrows at t=0 with a 1 day term, so it is due at `day`
+        let on_time = funded_agent(&t, 10_000_000);
+        let late = funded_agent(&t, 10_000_000);
+        let defaulted = funded_agent(&t, 10_000_000);
+        t.client.request_loan(&on_time, &1_000_000, &day);
+        t.client.request_loan(&late, &1_000_000, &day);
+        t.client.request_loan(&defaulted, &1_000_000, &day);
+
+        // Within grace: on time
+        t.env.ledger().set_timestamp(day + day / 2);
+        t.client.repay_loan(&on_time, &None);
+        assert_eq!(t.rep.get_score(&on_time), 58);
+
+        // Past grace but inside the default window: late, and not reportable as default
+        t.env.ledger().set_timestamp(3 * day);
+        assert!(t.client.try_report_default(&Address::generate(&t.env), &late).is_err());
+        assert!(!t.client.is_loan_overdue(&late));
+        t.client.repay_loan(&late, &None);
+        assert_eq!(t.client.get_loan_history(&late).get(0).unwrap().outcome, LoanOutcome::Late);
+        assert_eq!(t.rep.get_score(&late), 50); // First lateness is only a warning
+
+        // Past the default window: default
+        t.env.ledger().set_timestamp(4 * day + 1);
+        t.client.repay_loan(&defaulted, &None);
+        assert_eq!(t.rep.get_score(&defaulted), 25);
+        let history = t.client.get_loan_history(&defaulted);
+        assert_eq!(history.get(0).unwrap().outcome, LoanOutcome::Default);
+    }
+
+    #[test]
+    fn test_endorsement_boost_and_default_propagation() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let endorser = funded_agent(&t, 0);
+        let newcomer = funded_agent(&t, 0);
+        t.rep.set_score(&endorser, &80);
+        t.rep.set_score(&newcomer, &46);
+
+        // Below tier 1 the newcomer cannot borrow on its own
+        assert!(t.client.try_request_loan(&newcomer, &1_000_000, &day).is_err());
+
+        // Low-reputation agents cannot endorse
+        assert!(t.client.try_endorse(&newcomer, &endorser).is_err());
+
+        t.client.endorse(&endorser, &newcomer);
+        assert_eq!(t.client.get_effective_reputation(&newcomer), 51);
+        t.client.request_loan(&newcomer, &1_000_000, &day);
+
+        // The newcomer defaults: both agents are penalized and the endorsement ends
+        t.env.ledger().set_timestamp(3 * day);
+        t.client.report_default(&Address::generate(&t.env), &newcomer);
+        assert_eq!(t.rep.get_score(&newcomer), 21);
+        assert_eq!(t.rep.get_score(&endorser), 70);
+        assert!(t.client.get_endorsement(&newcomer).is_none());
+    }
+
+    #[test]
+    fn test_endorsements_are_capped_per_endorser() {
+        let t = setup(100_000_000);
+        let endorser = funded_agent(&t, 0);
+        t.rep.set_score(&endorser, &90);
+
+        for _ in 0..MAX_ACTIVE_ENDORSEMENTS {
+            t.client.endorse(&endorser, &Address::generate(&t.env));
+        }
+        assert!(t.client.try_endorse(&endorser, &Address::generate(&t.env)).is_err());
+
+        // Expired endorsements free up capacity
+        t.env.ledger().set_timestamp(ENDORSEMENT_DURATION_SECONDS);
+        t.client.endorse(&endorser, &Address::generate(&t.env));
+    }
+
+    #[test]
+    fn test_good_standing_raises_loan_cap() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let veteran = funded_agent(&t, 10_000_000);
+        let newcomer = funded_agent(&t, 0);
+
+        // Veteran starts its good-standing clock at t=0 and repays early (50 -> 62)
+        t.client.request_loan(&veteran, &1_000_000, &(7 * day));
+        t.client.repay_loan(&veteran, &None);
+        t.rep.set_score(&newcomer, &62);
+
+        t.env.ledger().set_timestamp(60 * day);
+        assert_eq!(t.client.get_max_loan_for_agent(&newcomer), TIER_2_MAX_LOAN);
+        assert_eq!(
+            t.client.get_max_loan_for_agent(&veteran),
+            TIER_2_MAX_LOAN + 60 * GOOD_STANDING_BONUS_PER_DAY
+        );
+
+        // The bonus never lifts the cap past tier 4
+        t.env.ledger().set_timestamp(1000 * day);
+        assert_eq!(t.client.get_max_loan_for_agent(&veteran), TIER_4_MAX_LOAN);
+
+        // A default restarts the clock
+        t.client.request_loan(&veteran, &1_000_000, &day);
+        t.env.ledger().set_timestamp(1003 * day);
+        t.client.report_default(&Address::generate(&t.env), &veteran);
+        t.rep.set_score(&veteran, &62);
+        assert_eq!(t.client.get_max_loan_for_agent(&veteran), TIER_2_MAX_LOAN);
+    }
+
+    #[test]
+    fn test_onboard_and_borrow_is_atomic() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let owner = Address::generate(&t.env);
+        let scopes = soroban_sdk::vec![&t.env, String::from_str(&t.env, "borrow")];
+
+        // Success: registered and funded in one call
+        let agent = Address::generate(&t.env);
+        t.client.onboard_and_borrow(&owner, &agent, &scopes, &10_000_000, &1_000_000, &day);
+        assert_eq!(t.agents.get_agent_owner(&agent), Some(owner.clone()));
+        assert_eq!(t.token.balance(&agent), 1_000_000);
+        assert_eq!(t.client.get_loan(&agent).unwrap().amount, 1_000_000);
+
+        // Failure: the loan exceeds the tier-1 limit, so the registration is rolled back too
+        let rejected = Address::generate(&t.env);
+        let result = t.client.try_onboard_and_borrow(
+            &owner,
+            &rejected,
+            &scopes,
+            &100_000_000,
+            &(TIER_1_MAX_LOAN + 1),
+            &day,
+        );
+        assert!(result.is_err());
+        assert!(t.agents.get_agent_owner(&rejected).is_none());
+        assert!(t.client.get_loan(&rejected).is_none());
+    }
+
+    #[test]
+    fn test_xlm_string_conversions() {
+        let env = Env::default();
+        let contract_id = env.register(LendingDemoContract, ());
+        let client = LendingDemoContractClient::new(&env, &contract_id);
+        let xlm = |s: &str| String::from_str(&env, s);
+
+        assert_eq!(client.stroops_to_xlm_string(&0), xlm("0"));
+        assert_eq!(client.stroops_to_xlm_string(&1), xlm("0.0000001"));
+        assert_eq!(client.stroops_to_xlm_string(&15_000_000), xlm("1.5"));
+        assert_eq!(client.stroops_to_xlm_string(&100_000_000), xlm("10"));
+
+        // Round-trip, including fractional XLM and the u64 maximum
+        for amount in [0, 1, 1_000_000, 15_000_000, 123_456_789, u64::MAX] {
+            let formatted = client.stroops_to_xlm_string(&amount);
+            assert_eq!(client.xlm_string_to_stroops(&formatted), amount);
+        }
+
+        assert_eq!(client.xlm_string_to_stroops(&xlm("0.1")), 1_000_000);
+        assert_eq!(client.xlm_string_to_stroops(&xlm(".5")), 5_000_000);
+        assert_eq!(client.xlm_string_to_stroops(&xlm("2.")), 20_000_000);
+
+        // Sub-stroop precision rounds half-up
+        assert_eq!(client.xlm_string_to_stroops(&xlm("1.00000004")), 10_000_000);
+        assert_eq!(client.xlm_string_to_stroops(&xlm("1.00000005")), 10_000_001);
+
+        // Malformed and overflowing inputs are rejected
+        assert!(client.try_xlm_string_to_stroops(&xlm("")).is_err());
+        assert!(client.try_xlm_string_to_stroops(&xlm(".")).is_err());
+        assert!(client.try_xlm_string_to_stroops(&xlm("1.2.3")).is_err());
+        assert!(client.try_xlm_string_to_stroops(&xlm("-1")).is_err());
+        assert!(client.try_xlm_string_to_stroops(&xlm("1844674407371")).is_err());
+    }
+
+    #[test]
+    fn test_restructure_loan_extends_and_forgives() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 10_000_000);
+
+        t.client.request_loan(&agent, &4_000_000, &day);
+        t.rep.set_score(&agent, &40);
+
+        // Past due and below threshold: restructure instead of defaulting
+        t.env.ledger().set_timestamp(day + day / 2);
+        let loan = t.client.restructure_loan(&t.admin, &agent, &(7 * day), &1_000_000);
+        assert_eq!(loan.amount, 3_000_000);
+        assert_eq!(loan.due_date, day + day / 2 + 7 * day);
+        assert_eq!(t.client.get_pool_status().total_outstanding, 3_000_000);
+        assert_eq!(t.rep.get_score(&agent), 40);
+
+        // The new term keeps the loan out of default
+        t.env.ledger().set_timestamp(5 * day);
+        assert!(t.client.try_report_default(&Address::generate(&t.env), &agent).is_err());
+        assert!(!t.client.is_loan_overdue(&agent));
+
+        // Restructuring cannot shorten the term or forgive the whole loan
+        assert!(t.client.try_restructure_loan(&t.admin, &agent, &day, &0).is_err());
+        assert!(t.client.try_restructure_loan(&t.admin, &agent, &(30 * day), &3_000_000).is_err());
+    }
+
+    #[test]
+    fn test_restructure_clears_reported_default() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 10_000_000);
+
+        t.client.request_loan(&agent, &4_000_000, &day);
+        t.env.ledger().set_timestamp(3 * day);
+        t.client.report_default(&Address::generate(&t.env), &agent);
+        assert_eq!(t.client.get_defaulted_agents().len(), 1);
+        assert_eq!(t.client.preview_default_penalty(&agent), 0);
+
+        // The restructured loan is off the worklist and no longer marked as penalized
+        t.client.restructure_loan(&t.admin, &agent, &(7 * day), &0);
+        assert_eq!(t.client.get_defaulted_agents().len(), 0);
+        assert!(t.client.preview_default_penalty(&agent) < 0);
+
+        // Missing the new term is a fresh default
+        t.env.ledger().set_timestamp(12 * day);
+        t.client.report_default(&Address::generate(&t.env), &agent);
+        assert_eq!(t.client.get_defaulted_agents(), Vec::from_array(&t.env, [agent]));
+    }
+
+    #[test]
+    fn test_default_bounty_paid_once() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 0);
+        let keeper = Address::generate(&t.env);
+
+        t.client.request_loan(&agent, &1_000_000, &day);
+        t.env.ledger().set_timestamp(3 * day);
+
+        // Neither the defaulter nor its owner can claim the bounty on its loan
+        assert!(t.client.try_report_default(&agent, &agent).is_err());
+        let owner = t.agents.get_agent_owner(&agent).unwrap();
+        assert!(t.client.try_report_default(&owner, &agent).is_err());
+
+        t.client.report_default(&keeper, &agent);
+        assert_eq!(t.token.balance(&keeper), 10_000); // 1% of the 1 XLM principal
+        assert_eq!(t.client.get_default_reporter(&agent), Some(keeper.clone()));
+
+        // A second report on the same loan is rejected and pays nothing
+        assert!(t.client.try_report_default(&keeper, &agent).is_err());
+        assert!(t
+            .client
+            .try_report_default(&Address::generate(&t.env), &agent)
+            .is_err());
+        assert_eq!(t.token.balance(&keeper), 10_000);
+    }
+
+    #[test]
+    fn test_default_bounty_stays_below_principal() {
+        let t = setup(1_000_000_000);
+        let day = 24 * 60 * 60;
+
+        // A defaulter reporting itself through a fresh address recovers only the bounty
+        for principal in [MIN_LOAN_AMOUNT, 1_000_000, TIER_1_MAX_LOAN] {
+            let agent = funded_agent(&t, 0);
+            let accomplice = Address::generate(&t.env);
+            let pool_before = t.token.balance(&t.client.address);
+
+            t.env.ledger().set_timestamp(0);
+            t.client.request_loan(&agent, &principal, &day);
+            t.env.ledger().set_timestamp(3 * day);
+            t.client.report_default(&accomplice, &agent);
+
+            let bounty = t.token.balance(&accomplice);
+            assert!(bounty > 0 && bounty <= t.client.get_default_bounty() as i128);
+            assert_eq!(pool_before - t.token.balance(&t.client.address), principal as i128 + bounty);
+
+            // Loan and bounty together exceed the principal by at most 1%
+            let defaulter_side = t.token.balance(&agent) + bounty;
+            assert!(defaulter_side <= principal as i128 + principal as i128 / 100);
+        }
+
+        // Large defaults pay at most the capped bounty
+        assert_eq!(default_bounty(u64::MAX), MAX_DEFAULT_REPORT_BOUNTY);
+    }
+
+    #[test]
+    fn test_best_effort_loan_capped_to_available_funds() {
+        let t = setup(3_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 0);
+
+        // Tier 1 allows 5M, but utilization headroom (80% of 3M) allows only 2.4M
+        assert!(t.client.try_request_loan(&agent, &TIER_1_MAX_LOAN, &day).is_err());
+        let issued = t.client.request_loan_best_effort(&agent, &TIER_1_MAX_LOAN, &day);
+        assert_eq!(issued, 2_400_000);
+        assert_eq!(t.token.balance(&agent), 2_400_000);
+        assert_eq!(t.client.get_loan(&agent).unwrap().amount, 2_400_000);
+
+        // Smaller requests are issued as-is
+        let modest = funded_agent(&t, 0);
+        t.token_admin.mint(&t.client.address, &10_000_000);
+        assert_eq!(t.client.request_loan_best_effort(&modest, &500_000, &day), 500_000);
+
+        // Nothing fundable (no tier-1 reputation) is still rejected
+        let unproven = funded_agent(&t, 0);
+        t.rep.set_score(&unproven, &40);
+        assert!(t
+            .client
+            .try_request_loan_best_effort(&unproven, &1_000_000, &day)
+            .is_err());
+    }
+
+    #[test]
+    fn test_repeat_defaults_escalate_penalty() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 10_000_000);
+        let keeper = Address::generate(&t.env);
+
+        // First default: -25
+        t.client.request_loan(&agent, &1_000_000, &day);
+        t.env.ledger().set_timestamp(3 * day);
+        assert_eq!(t.client.preview_default_penalty(&agent), REPUTATION_DECREASE_DEFAULT);
+        t.client.report_default(&keeper, &agent);
+        assert_eq!(t.client.preview_default_penalty(&agent), 0);
+        assert_eq!(t.rep.get_score(&agent), 25);
+        assert_eq!(t.client.get_agent_state(&agent).default_count, 1);
+
+        // Second default, caught by the auto-penalty path: -35
+        t.client.repay_loan(&agent, &None);
+        t.rep.set_score(&agent, &80);
+        t.client.request_loan(&agent, &1_000_000, &day);
+        t.env.ledger().set_timestamp(6 * day);
+        let preview = t.client.preview_default_penalty(&agent);
+        assert!(t.client.is_loan_overdue(&agent));
+        assert_eq!(t.rep.get_score(&agent), 45);
+        assert_eq!(t.client.get_agent_state(&agent).default_count, 2);
+
+        let history = t.client.get_loan_history(&agent);
+        let last = history.get(history.len() - 1).unwrap();
+        assert_eq!(last.outcome, LoanOutcome::Default);
+        assert_eq!(last.reputation_delta, REPUTATION_DECREASE_SECOND_DEFAULT);
+        assert_eq!(last.reputation_delta, preview);
+    }
+
+    #[test]
+    fn test_export_and_import_state_round_trip() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let first = funded_agent(&t, 10_000_000);
+        let second = funded_agent(&t, 10_000_000);
+        t.client.set_bonus_decay(&t.admin, &(30 * day), &3);
+        t.client.set_protocol_fee(&t.admin, &1_000);
+        t.client.set_reserve_policy(&t.admin, &10_000_000, &2_000);
+
+        // Baseline: one settled loan, an active loan and a reported default
+        t.client.request_loan(&first, &1_000_000, &(7 * day));
+        t.env.ledger().set_timestamp(2 * day);
+        t.client.repay_loan(&first, &None);
+        t.client.request_loan(&first, &1_000_000, &(7 * day));
+        t.client.repay_loan(&first, &None);
+        t.client.request_loan(&first, &2_000_000, &(7 * day));
+        let first_owner = t.agents.get_agent_owner(&first).unwrap();
+        t.client.set_reminder_offset(&first_owner, &first, &day);
+        t.client.request_loan(&second, &3_000_000, &day);
+        let reporter = Address::generate(&t.env);
+        t.env.ledger().set_timestamp(5 * day);
+        t.client.report_default(&reporter, &second);
+        let endorser = Address::generate(&t.env);
+        let newcomer = Address::generate(&t.env);
+        t.rep.set_score(&endorser, &80);
+        t.client.endorse(&endorser, &newcomer);
+        let lender = Address::generate(&t.env);
+        t.token_admin.mint(&lender, &20_000_000);
+        t.client.deposit(&lender, &10_000_000);
+        let keeper = Address::generate(&t.env);
+        t.client.approve_keeper(&t.admin, &keeper);
+        t.client.record_utilization_sample(&keeper);
+        let baseline = t.client.export_state(&t.admin);
+        assert_eq!(baseline.agents.len(), 2);
+        assert_eq!(baseline.endorsements.len(), 1);
+        assert_eq!(baseline.agents.get(0).unwrap().recent_bonuses.len(), 2);
+        assert!(baseline.agents.get(0).unwrap().interest_paid > 0);
+        assert_eq!(baseline.total_outstanding, 5_000_000);
+
+        // Diverge: distribute rebates, settle both loans, default again, onboard a new borrower
+        t.client.distribute_rebates(&keeper);
+        t.client.repay_loan(&first, &None);
+        t.client.repay_loan(&second, &None);
+        t.rep.set_score(&second, &50);
+        t.client.request_loan(&second, &1_000_000, &day);
+        t.client.endorse(&endorser, &second);
+        t.env.ledger().set_timestamp(8 * day);
+        t.client.report_default(&Address::generate(&t.env), &second);
+        let third = funded_agent(&t, 0);
+        t.client.request_loan(&third, &1_000_000, &day);
+        t.client.deposit(&lender, &10_000_000);
+        t.client.withdraw(&lender, &1_000);
+        assert!(t.client.get_accrued_fees() > baseline.accrued_fees);
+        assert!(t.client.get_pool_status().reserve_balance > baseline.reserve_balance);
+        t.client.record_utilization_sample(&keeper);
+        t.client.set_reminder_offset(&first_owner, &first, &(2 * day));
+        assert_eq!(t.client.export_state(&t.admin).reputation_adjustments.len(), 1);
+        assert_ne!(t.client.export_state(&t.admin), baseline);
+
+        // Restore and compare
+        t.client.import_state(&t.admin, &baseline);
+        assert_eq!(t.client.export_state(&t.admin), baseline);
+        assert!(t.client.get_loan(&third).is_none());
+        assert!(!t.client.get_loan(&first).unwrap().repaid);
+        assert_eq!(t.client.get_loan_history(&second).len(), 1);
+        assert_eq!(t.client.get_default_reporter(&second), Some(reporter));
+        assert_eq!(t.client.reminder_time(&first), Some(8 * day));
+        assert_eq!(
+            t.client.get_interest_paid(&first),
+            baseline.agents.get(0).unwrap().interest_paid
+        );
+        assert_eq!(t.client.get_defaulted_agents().len(), 1);
+        assert_eq!(t.client.get_pool_status().total_outstanding, 5_000_000);
+        assert_eq!(t.client.get_endorsement(&newcomer).unwrap().endorser, endorser);
+        assert_eq!(baseline.lenders.len(), 2);
+        assert_eq!(baseline.lenders.get(1).unwrap().liquidity_since, Some(5 * day));
+        assert_eq!(baseline.lenders.get(1).unwrap().withdrawal_window.withdrawn, 0);
+        assert_eq!(t.client.get_accrued_fees(), baseline.accrued_fees);
+        assert_eq!(t.client.get_pool_status().reserve_balance, baseline.reserve_balance);
+        assert_eq!(t.client.get_utilization_history().len(), 1);
+        assert_eq!(
+            t.client.get_lender_shares(&lender),
+            baseline.lenders.get(1).unwrap().shares
+        );
+
+        // Only the admin can export or import
+        let outsider = Address::generate(&t.env);
+        assert!(t.client.try_export_state(&outsider).is_err());
+        assert!(t.client.try_import_state(&outsider, &baseline).is_err());
+    }
+
+    #[test]
+    fn test_loan_ids_are_distinct_and_increasing() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 10_000_000);
+        let other = funded_agent(&t, 0);
+
+        let first_id = t.client.request_loan(&agent, &1_000_000, &(7 * day)).loan_id;
+        let other_id = t.client.request_loan(&other, &2_000_000, &(7 * day)).loan_id;
+        t.client.repay_loan(&agent, &None);
+        let second_id = t.client.request_loan(&agent, &3_000_000, &(7 * day)).loan_id;
+
+        assert!(first_id < other_id && other_id < second_id);
+        assert_eq!(t.client.get_loan(&agent).unwrap().loan_id, second_id);
+
+        // Earlier loans stay addressable by id, with their final state
+        let first = t.client.get_loan_by_id(&first_id).unwrap();
+        assert_eq!(first.amount, 1_000_000);
+        assert!(first.repaid);
+        assert_eq!(t.client.get_loan_by_id(&other_id).unwrap().agent, other);
+        assert_eq!(t.client.get_loan_history(&agent).get(0).unwrap().loan_id, first_id);
+        assert!(t.client.get_loan_by_id(&(second_id + 1)).is_none());
+    }
+
+    #[test]
+    fn test_min_pool_balance_floor() {
+        let t = setup_with_floor(10_000_000, 6_000_000);
+        let day = 24 * 60 * 60;
+        assert_eq!(t.client.get_config().min_pool_balance, 6_000_000);
+
+        // 5M would leave 5M in the pool, under the 6M floor
+        let greedy = funded_agent(&t, 0);
+        assert!(t.client.try_request_loan(&greedy, &5_000_000, &day).is_err());
+
+        // 4M leaves exactly the floor
+        t.client.request_loan(&greedy, &4_000_000, &day);
+        assert_eq!(t.token.balance(&t.client.address), 6_000_000);
+
+        // Best-effort sizing respects the floor too
+        let other = funded_agent(&t, 0);
+        assert!(t
+            .client
+            .try_request_loan_best_effort(&other, &1_000_000, &day)
+            .is_err());
+    }
+
+    #[test]
+    fn test_time_weighted_reputation_dampens_spikes() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let sustained = funded_agent(&t, 0);
+        let spiked = funded_agent(&t, 0);
+        let unscored = funded_agent(&t, 0);
+
+        t.env.ledger().set_timestamp(day);
+        t.rep.set_score(&sustained, &90);
+        t.env.ledger().set_timestamp(40 * day);
+        t.rep.set_score(&spiked, &90);
+
+        // Spot scores: both look excellent
+        assert_eq!(t.client.get_max_loan_for_agent(&spiked), TIER_4_MAX_LOAN);
+
+        t.client.set_reputation_twap_window(&t.admin, &(30 * day));
+        t.env.ledger().set_timestamp(40 * day + 60 * 60);
+        assert_eq!(t.client.get_effective_reputation(&sustained), 90);
+        assert_eq!(t.client.get_max_loan_for_agent(&sustained), TIER_4_MAX_LOAN);
+
+        // One hour at 90 barely moves the 30-day average off the baseline
+        assert_eq!(t.client.get_effective_reputation(&spiked), 50);
+        assert_eq!(t.client.get_max_loan_for_agent(&spiked), TIER_1_MAX_LOAN);
+        assert!(t.client.try_request_loan(&spiked, &TIER_2_MAX_LOAN, &day).is_err());
+
+        // No history: spot score
+        assert_eq!(t.client.get_effective_reputation(&unscored), 50);
+    }
+
+    #[test]
+    #[should_panic(expected = "Protocol contracts cannot act as agents")]
+    fn test_loan_to_own_contract_rejected() {
+        let t = setup(10_000_000);
+        let pool = t.client.address.clone();
+
+        t.client.request_loan(&pool, &1_000_000, &(24 * 60 * 60));
+    }
+
+    #[test]
+    fn test_required_collateral_by_reputation_tier() {
+        let t = setup(10_000_000);
+        let newcomer = funded_agent(&t, 0);
+        let veteran = funded_agent(&t, 0);
+        t.rep.set_score(&veteran, &95);
+
+        // Same loan, 50% LTV for tier 1 vs 90% LTV for tier 4
+        assert_eq!(t.client.required_collateral(&newcomer, &9_000_000), 18_000_000);
+        assert_eq!(t.client.required_collateral(&veteran, &9_000_000), 10_000_000);
+
+        let flat = soroban_sdk::vec![&t.env, 10_000, 10_000, 10_000, 10_000];
+        t.client.set_collateral_ltv_schedule(&t.admin, &flat);
+        assert_eq!(t.client.required_collateral(&newcomer, &9_000_000), 9_000_000);
+    }
+
+    #[test]
+    fn test_owner_repay_requires_true_owner() {
+        let t = setup(10_000_000);
+        let owner = Address::generate(&t.env);
+        let stranger = Address::generate(&t.env);
+        let agent = Address::generate(&t.env);
+        t.agents.register_agent(&owner, &agent, &Vec::new(&t.env), &u64::MAX);
+        t.token_admin.mint(&owner, &5_000_000);
+        t.token_admin.mint(&stranger, &5_000_000);
+
+        t.client.request_loan(&agent, &1_000_000, &(24 * 60 * 60));
+
+        assert!(t.client.try_owner_repay(&stranger, &agent).is_err());
+
+        t.client.owner_repay(&owner, &agent);
+        assert!(t.client.get_loan(&agent).unwrap().repaid);
+        assert_eq!(t.token.balance(&owner), 4_000_000);
+        assert_eq!(t.token.balance(&agent), 1_000_000);
+
+        // Early repayment credits the agent, not the owner
+        assert_eq!(t.rep.get_score(&agent), 62);
+        assert_eq!(t.rep.get_score(&owner), 50);
+    }
+
+    #[test]
+    #[should_panic(expected = "Agent has been revoked")]
+    fn test_revoked_agent_never_borrows() {
+        let t = setup(10_000_000);
+        let agent = funded_agent(&t, 0);
+
+        // The mock still reports the agent as authorized
+        t.agents.revoke_agent(&agent);
+        t.client.request_loan(&agent, &1_000_000, &(24 * 60 * 60));
+    }
+
+    #[test]
+    fn test_early_payment_window_widens_with_tier() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let hour = 60 * 60;
+        let top = funded_agent(&t, 0);
+        let newcomer = funded_agent(&t, 0);
+        t.rep.set_score(&top, &95);
+
+        let thresholds = soroban_sdk::vec![&t.env, 24 * hour, 18 * hour, 12 * hour, 6 * hour];
+        t.client.set_early_payment_thresholds(&t.admin, &thresholds);
+
+        t.client.request_loan(&top, &1_000_000, &(2 * day));
+        t.client.request_loan(&newcomer, &1_000_000, &(2 * day));
+        assert_eq!(t.client.get_loan(&top).unwrap().tier, 4);
+        assert_eq!(t.client.get_loan(&newcomer).unwrap().tier, 1);
+
+        // 20 hours before the due date: early for tier 4 (6h), not for tier 1 (24h)
+        t.env.ledger().set_timestamp(2 * day - 20 * hour);
+        t.client.repay_loan(&top, &None);
+        t.client.repay_loan(&newcomer, &None);
+
+        assert_eq!(t.client.get_loan_history(&top).get(0).unwrap().outcome, LoanOutcome::Early);
+        assert_eq!(t.client.get_loan_history(&newcomer).get(0).unwrap().outcome, LoanOutcome::OnTime);
+    }
+
+    #[test]
+    fn test_back_to_back_loans_see_reduced_headroom() {
+        let t = setup(10_000_000);
+        let first = funded_agent(&t, 0);
+        let second = funded_agent(&t, 0);
+
+        t.client.request_loan(&first, &4_000_000, &(24 * 60 * 60));
+        assert_eq!(t.client.get_pool_status().total_outstanding, 4_000_000);
+
+        // 4 + 5 of 10 would breach the 80% cap; 4 + 4 does not
+        assert!(t.client.try_request_loan(&second, &5_000_000, &(24 * 60 * 60)).is_err());
+        t.client.request_loan(&second, &4_000_000, &(24 * 60 * 60));
+        assert_eq!(t.client.get_pool_status().total_outstanding, 8_000_000);
+    }
+
+    #[test]
+    fn test_loan_in_disallowed_token_reverts() {
+        let t = setup(10_000_000);
+        let agent = funded_agent(&t, 0);
+        assert!(t.client.is_token_allowed(&t.token.address));
+
+        t.client.disallow_token(&t.admin, &t.token.address);
+        assert!(!t.client.is_token_allowed(&t.token.address));
+        assert!(t.client.try_request_loan(&agent, &1_000_000, &(24 * 60 * 60)).is_err());
+
+        t.client.allow_token(&t.admin, &t.token.address);
+        t.client.request_loan(&agent, &1_000_000, &(24 * 60 * 60));
+    }
+
+    #[test]
+    fn test_default_penalty_scales_with_principal() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let reporter = Address::generate(&t.env);
+        let large = funded_agent(&t, 0);
+        let small = funded_agent(&t, 0);
+
+        t.client.set_default_penalty_scaling(&t.admin, &true);
+
+        // Full tier-1 limit vs 20% of it
+        t.client.request_loan(&large, &TIER_1_MAX_LOAN, &day);
+        t.client.request_loan(&small, &(TIER_1_MAX_LOAN / 5), &day);
+
+        t.env.ledger().set_timestamp(3 * day);
+        t.client.report_default(&reporter, &large);
+        t.client.report_default(&reporter, &small);
+
+        assert_eq!(t.rep.get_score(&large), 25);
+        assert_eq!(t.rep.get_score(&small), 45);
+    }
+
+    #[test]
+    fn test_loans_due_within_window() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let soon = funded_agent(&t, 1_000_000);
+        let later = funded_agent(&t, 0);
+        let repaid = funded_agent(&t, 1_000_000);
+
+        t.client.request_loan(&soon, &1_000_000, &day);
+        t.client.request_loan(&later, &1_000_000, &(10 * day));
+        t.client.request_loan(&repaid, &1_000_000, &day);
+        t.client.repay_loan(&repaid, &None);
+
+        let due = t.client.loans_due_within(&(2 * day));
+        assert_eq!(due, soroban_sdk::vec![&t.env, soon.clone()]);
+
+        // Closing the loan prunes it from the worklist
+        t.client.repay_loan(&soon, &None);
+        assert!(t.client.loans_due_within(&(2 * day)).is_empty());
+        assert_eq!(t.client.loans_due_within(&(10 * day)).len(), 1);
+    }
+
+    #[test]
+    fn test_credit_discipline_reward() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let keeper = Address::generate(&t.env);
+        let disciplined = funded_agent(&t, 0);
+        let maxed = funded_agent(&t, 0);
+        t.client.approve_keeper(&t.admin, &keeper);
+
+        // 20% vs 100% of the tier-1 limit
+        t.client.request_loan(&disciplined, &(TIER_1_MAX_LOAN / 5), &(40 * day));
+        t.client.request_loan(&maxed, &TIER_1_MAX_LOAN, &(40 * day));
+
+        // Too early in the period
+        assert!(t.client.try_reward_credit_discipline(&keeper, &disciplined).is_err());
+
+        t.env.ledger().set_timestamp(31 * day);
+        t.client.reward_credit_discipline(&keeper, &disciplined);
+        assert_eq!(t.rep.get_score(&disciplined), 52);
+        assert!(t.client.try_reward_credit_discipline(&keeper, &maxed).is_err());
+        assert_eq!(t.rep.get_score(&maxed), 50);
+
+        // No double claim within the same period
+        assert!(t.client.try_reward_credit_discipline(&keeper, &disciplined).is_err());
+    }
+
+    #[test]
+    fn test_reputation_unavailable_strict_and_lenient() {
+        let t = setup(10_000_000);
+        let agent = funded_agent(&t, 0);
+        let broken = t.env.register(BrokenReputationManager, ());
+
+        // The outage hits both the AgentManager's scoring and the lending contract's own
+        // calls (action log, score history)
+        t.agents.set_reputation_manager(&broken);
+        t.env.as_contract(&t.client.address, || {
+            t.env
+                .storage()
+                .persistent()
+                .set(&DataKey::ReputationManagerContract(()), &broken);
+        });
+        t.client.set_reputation_twap_window(&t.admin, &(7 * 24 * 60 * 60));
+
+        // Strict (default): a typed error instead of an opaque failure
+        let result = t.client.try_request_loan(&agent, &1_000_000, &(24 * 60 * 60));
+        assert_eq!(
+            result.err().unwrap().unwrap(),
+            soroban_sdk::Error::from_contract_error(LendingError::ReputationUnavailable as u32)
+        );
+
+        // Lenient: lend as if the agent had the baseline score (the manager's default is
+        // unreachable too)
+        t.client.set_reputation_fallback(&t.admin, &true);
+        assert!(t.client.get_config().lenient_reputation_fallback);
+        assert!(t.client.try_request_loan(&agent, &TIER_2_MAX_LOAN, &(24 * 60 * 60)).is_err());
+        t.client.request_loan(&agent, &1_000_000, &(24 * 60 * 60));
+        assert_eq!(t.client.get_loan(&agent).unwrap().amount, 1_000_000);
+    }
+
+    #[test]
+    fn test_compounding_interest_exceeds_simple() {
+        let day = 24 * 60 * 60;
+        let amount_due_after = |compounding: bool, days: u64| {
+            let t = setup(100_000_000);
+            t.client.set_interest_mode(&t.admin, &compounding, &day);
+            let agent = funded_agent(&t, 10_000_000);
+
+            // Use up the interest-free first loan
+            t.client.request_loan(&agent, &1_000_000, &(30 * day));
+            t.client.repay_loan(&agent, &None);
+
+            let start = t.env.ledger().timestamp();
+            t.client.request_loan(&agent, &1_000_000, &(30 * day));
+            t.env.ledger().set_timestamp(start + days * day);
+            t.client.get_amount_due(&agent)
+        };
+
+        // 5% per day: simple 1_000_000 * (1 + 0.05 * 10), compounding 1_000_000 * 1.05^10
+        assert_eq!(amount_due_after(false, 10), 1_500_000);
+        assert_eq!(amount_due_after(true, 10), 1_628_894); // Rounded down
+        assert_eq!(amount_due_after(true, 1), amount_due_after(false, 1));
+
+        // A long-dormant loan saturates instead of overflowing
+        assert_eq!(amount_due_after(true, 3_650), u64::MAX);
+    }
+
+    #[test]
+    fn test_outstanding_interest_follows_interest_mode() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        t.client.set_interest_mode(&t.admin, &false, &day);
+        let agent = funded_agent(&t, 10_000_000);
+        t.client.request_loan(&agent, &1_000_000, &day);
+        t.client.repay_loan(&agent, &None);
+
+        // 5% per day over a 10-day term
+        t.client.request_loan(&agent, &1_000_000, &(10 * day));
+        assert_eq!(t.client.get_pool_status().accrued_interest, 500_000);
+
+        // Half the principal over a 20-day term
+        t.client.restructure_loan(&t.admin, &agent, &(20 * day), &500_000);
+        assert_eq!(t.client.get_pool_status().accrued_interest, 500_000);
+
+        t.client.repay_loan(&agent, &None);
+        assert_eq!(t.client.get_pool_status().accrued_interest, 0);
+    }
+
+    #[test]
+    fn test_deposit_earns_repaid_interest() {
+        let t = setup(10_000_000);
+        let lender = Address::generate(&t.env);
+        let agent = funded_agent(&t, 1_000_000);
+        t.token_admin.mint(&lender, &10_000_000);
+        t.rep.set_score(&lender, &80); // Withdraws without a per-period limit
+
+        // Pre-existing liquidity becomes the admin's stake; the lender buys in at par
+        assert_eq!(t.client.deposit(&lender, &10_000_000), 10_000_000);
+        assert_eq!(t.client.get_lender_shares(&t.admin), 10_000_000);
+        assert_eq!(t.client.get_liquidity(), 20_000_000);
+
+        // Interest-free first loan, then a loan paying 5% interest
+        t.client.request_loan(&agent, &2_000_000, &(24 * 60 * 60));
+        t.client.repay_loan(&agent, &None);
+        t.client.request_loan(&agent, &2_000_000, &(24 * 60 * 60));
+        t.client.repay_loan(&agent, &None);
+
+        // Half the shares earn half the 100_000 interest
+        assert_eq!(t.client.withdraw(&lender, &10_000_000), 10_050_000);
+        assert_eq!(t.token.balance(&lender), 10_050_000);
+        assert_eq!(t.client.get_lender_shares(&lender), 0);
+    }
+
+    #[test]
+    fn test_new_loan_starts_with_clean_penalty_state() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let reporter = Address::generate(&t.env);
+        let agent = funded_agent(&t, 1_000_000);
+
+        t.client.request_loan(&agent, &1_000_000, &day);
+        t.env.ledger().set_timestamp(3 * day);
+        t.client.report_default(&reporter, &agent);
+        t.client.repay_loan(&agent, &None);
+        assert_eq!(t.rep.get_score(&agent), 25);
+
+        // The next loan's default is penalized on its own, exactly once
+        t.rep.set_score(&agent, &50);
+        t.client.request_loan(&agent, &1_000_000, &day);
+        assert!(!t.client.is_loan_overdue(&agent));
+        t.env.ledger().set_timestamp(6 * day);
+        t.client.report_default(&reporter, &agent);
+        assert!(t.client.try_report_default(&reporter, &agent).is_err());
+        assert_eq!(t.rep.get_score(&agent), 15);
+    }
+
+    #[test]
+    fn test_rapid_repayment_bonuses_diminish() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 10_000_000);
+        t.client.set_bonus_decay(&t.admin, &day, &3);
+
+        for _ in 0..5 {
+            t.client.request_loan(&agent, &100_000, &(2 * day));
+            t.client.repay_loan(&agent, &None);
+        }
+
+        // Once the window has passed, bonuses are full again
+        t.env.ledger().set_timestamp(2 * day);
+        t.client.request_loan(&agent, &100_000, &(4 * day));
+        t.client.repay_loan(&agent, &None);
+
+        // Three full early bonuses, then 12 * 3/4 and 12 * 3/5
+        let mut deltas = Vec::new(&t.env);
+        for record in t.client.get_loan_history(&agent).iter() {
+            deltas.push_back(record.reputation_delta);
         }
+        assert_eq!(deltas, soroban_sdk::vec![&t.env, 12, 12, 12, 9, 7, 12]);
+    }
 
-        // ✅ NEW: Check if penalty already applied to prevent duplicate penalties
-        let penalty_key = DataKey::PenaltyApplied(agent.clone());
-        let penalty_already_applied: bool = env
-            .storage()
-            .persistent()
-            .get(&penalty_key)
-            .unwrap_or(false);
+    #[test]
+    fn test_first_late_repayment_is_penalty_free() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 10_000_000);
+        t.client.set_default_windows(&t.admin, &day, &(3 * day));
 
-        if penalty_already_applied {
-            panic!("Penalty already applied for this loan");
+        // First late repayment: a warning, no penalty
+        t.client.request_loan(&agent, &1_000_000, &day);
+        t.env.ledger().set_timestamp(3 * day);
+        t.client.repay_loan(&agent, &None);
+        let topics: Vec<soroban_sdk::Val> =
+            (Symbol::new(&t.env, "loan"), Symbol::new(&t.env, "late_warning")).into_val(&t.env);
+        let warned = t
+            .env
+            .events()
+            .all()
+            .iter()
+            .any(|(contract, event_topics, _)| contract == t.client.address && event_topics == topics);
+        assert!(warned);
+        assert_eq!(t.rep.get_score(&agent), 50);
+        assert!(t.client.get_agent_state(&agent).had_late_payment);
+
+        // Second late repayment: penalized normally
+        t.client.request_loan(&agent, &1_000_000, &day);
+        t.env.ledger().set_timestamp(6 * day);
+        t.client.repay_loan(&agent, &None);
+        assert_eq!(t.rep.get_score(&agent), 45);
+    }
+
+    #[test]
+    fn test_protocol_fees_are_kept_out_of_liquidity() {
+        let t = setup(10_000_000);
+        t.client.set_protocol_fee(&t.admin, &2_000);
+        let agent = funded_agent(&t, 1_000_000);
+        let treasury = Address::generate(&t.env);
+
+        // Interest-free first loan, then a loan paying 100_000 interest (20% kept as fees)
+        t.client.request_loan(&agent, &2_000_000, &(24 * 60 * 60));
+        t.client.repay_loan(&agent, &None);
+        t.client.request_loan(&agent, &2_000_000, &(24 * 60 * 60));
+        t.client.repay_loan(&agent, &None);
+
+        assert_eq!(t.client.get_accrued_fees(), 20_000);
+        assert_eq!(t.client.get_liquidity(), 10_100_000);
+        assert_eq!(t.client.get_lendable_liquidity(), 10_080_000);
+        assert_eq!(t.client.get_pool_status().total_liquidity, 10_080_000);
+
+        // Withdrawing fees leaves principal and the lenders' interest untouched
+        assert_eq!(t.client.withdraw_fees(&t.admin, &treasury), 20_000);
+        assert_eq!(t.token.balance(&treasury), 20_000);
+        assert_eq!(t.client.get_accrued_fees(), 0);
+        assert_eq!(t.client.get_lendable_liquidity(), 10_080_000);
+        assert_eq!(t.client.withdraw_fees(&t.admin, &treasury), 0);
+    }
+
+    #[test]
+    fn test_utilization_history_is_bounded() {
+        let t = setup(100_000_000);
+        let keeper = Address::generate(&t.env);
+        let agent = funded_agent(&t, 0);
+        t.client.approve_keeper(&t.admin, &keeper);
+        t.client.set_utilization_history_size(&t.admin, &3);
+
+        // Only approved keepers record samples
+        assert!(t.client.try_record_utilization_sample(&agent).is_err());
+
+        t.client.record_utilization_sample(&keeper);
+        t.client.request_loan(&agent, &5_000_000, &(24 * 60 * 60));
+        for hour in 1..4u64 {
+            t.env.ledger().set_timestamp(hour * 60 * 60);
+            t.client.record_utilization_sample(&keeper);
         }
 
-        // DACTP REPUTATION UPDATE: Heavy penalty for default
-        let contract_addr = env.current_contract_address();
-        rep_mgr_client.update_score(
-            &contract_addr,
-            &agent,
-            &REPUTATION_DECREASE_DEFAULT
+        // Four samples recorded, the idle-pool one dropped
+        let history = t.client.get_utilization_history();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.get(0).unwrap(), (60 * 60, 500));
+        assert_eq!(history.get(2).unwrap(), (3 * 60 * 60, 500));
+
+        // Shrinking the buffer trims the oldest samples
+        t.client.set_utilization_history_size(&t.admin, &1);
+        assert_eq!(t.client.get_utilization_history().get(0).unwrap(), (3 * 60 * 60, 500));
+    }
+
+    #[test]
+    fn test_liquidity_bonus_waits_for_lock_period() {
+        let t = setup(0);
+        let day = 24 * 60 * 60;
+        let keeper = Address::generate(&t.env);
+        let provider = funded_agent(&t, 200_000_000);
+        t.client.approve_keeper(&t.admin, &keeper);
+
+        t.client.deposit(&provider, &200_000_000);
+
+        // Still locked after three days
+        t.env.ledger().set_timestamp(3 * day);
+        assert!(t.client.try_reward_liquidity_provision(&keeper, &provider, &200_000_000).is_err());
+        assert_eq!(t.rep.get_score(&provider), 50);
+
+        // 20 XLM held for a week
+        t.env.ledger().set_timestamp(7 * day);
+        assert!(t.client.try_reward_liquidity_provision(&keeper, &provider, &300_000_000).is_err());
+        assert_eq!(t.client.reward_liquidity_provision(&keeper, &provider, &200_000_000), 2);
+        assert_eq!(t.rep.get_score(&provider), 52);
+
+        // The reward restarts the lock
+        assert!(t.client.try_reward_liquidity_provision(&keeper, &provider, &200_000_000).is_err());
+    }
+
+    #[test]
+    fn test_liquidity_top_up_restarts_lock() {
+        let t = setup(0);
+        let day = 24 * 60 * 60;
+        let keeper = Address::generate(&t.env);
+        let provider = funded_agent(&t, 200_001_000);
+        t.client.approve_keeper(&t.admin, &keeper);
+
+        // Dust held through the lock period, then a large top-up
+        t.client.deposit(&provider, &1_000);
+        t.env.ledger().set_timestamp(7 * day);
+        t.client.deposit(&provider, &200_000_000);
+        assert!(t.client.try_reward_liquidity_provision(&keeper, &provider, &200_000_000).is_err());
+        assert_eq!(t.rep.get_score(&provider), 50);
+
+        // The top-up earns its bonus only once it has been held for a full lock period
+        t.env.ledger().set_timestamp(14 * day);
+        assert_eq!(t.client.reward_liquidity_provision(&keeper, &provider, &200_000_000), 2);
+    }
+
+    #[test]
+    fn test_privileged_borrower_skips_utilization_cap() {
+        let t = setup(10_000_000);
+        let day = 24 * 60 * 60;
+        let normal = funded_agent(&t, 0);
+        let system = funded_agent(&t, 0);
+        t.client.add_privileged_borrower(&t.admin, &system);
+        assert!(t.client.is_privileged_borrower(&system));
+        assert!(!t.client.is_privileged_borrower(&normal));
+
+        // 70% utilization
+        t.client.request_loan(&funded_agent(&t, 0), &5_000_000, &day);
+        t.client.request_loan(&funded_agent(&t, 0), &2_000_000, &day);
+
+        // Taking the pool to 90%: past the 80% hard cap
+        assert!(t.client.try_request_loan(&normal, &2_000_000, &day).is_err());
+        t.client.request_loan(&system, &2_000_000, &day);
+        assert_eq!(t.client.get_pool_utilization(), 90);
+
+        t.client.remove_privileged_borrower(&t.admin, &system);
+        assert!(!t.client.is_privileged_borrower(&system));
+    }
+
+    #[test]
+    fn test_reminder_time_follows_due_date() {
+        let t = setup(10_000_000);
+        let day = 24 * 60 * 60;
+        let owner = Address::generate(&t.env);
+        let agent = Address::generate(&t.env);
+        t.agents.register_agent(&owner, &agent, &Vec::new(&t.env), &u64::MAX);
+        t.token_admin.mint(&agent, &1_000_000);
+
+        // Only the owner sets the preference
+        assert!(t.client.try_set_reminder_offset(&agent, &agent, &day).is_err());
+        t.client.set_reminder_offset(&owner, &agent, &day);
+        assert_eq!(t.client.reminder_time(&agent), None); // No active loan yet
+
+        t.env.ledger().set_timestamp(1_000);
+        t.client.request_loan(&agent, &1_000_000, &(7 * day));
+        let due_date = t.client.get_loan(&agent).unwrap().due_date;
+        assert_eq!(t.client.reminder_time(&agent), Some(due_date - day));
+
+        t.client.repay_loan(&agent, &None);
+        assert_eq!(t.client.reminder_time(&agent), None);
+    }
+
+    #[test]
+    fn test_single_loan_capped_to_share_of_liquidity() {
+        let t = setup(10_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 0);
+        assert_eq!(t.client.max_single_loan(), 10_000_000);
+
+        // No loan may take more than 25% of free liquidity, even at low utilization
+        t.client.set_max_single_loan_bps(&t.admin, &2_500);
+        assert_eq!(t.client.max_single_loan(), 2_500_000);
+        assert!(t.client.try_request_loan(&agent, &3_000_000, &day).is_err());
+
+        t.client.request_loan(&agent, &2_500_000, &day);
+        assert_eq!(t.client.max_single_loan(), 1_875_000);
+    }
+
+    #[test]
+    fn test_oracle_keeps_loan_limits_stable_in_usd() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 0);
+        let oracle_id = t.env.register(MockPriceOracle, ());
+        let oracle = MockPriceOracleClient::new(&t.env, &oracle_id);
+
+        // No oracle: fixed stroop limits
+        assert_eq!(t.client.get_max_loan_for_agent(&agent), TIER_1_MAX_LOAN);
+
+        // 0.10 USD per XLM: the USD limit matches the fixed one
+        oracle.set_price(&1_000_000);
+        t.client.set_price_oracle(&t.admin, &Some(oracle_id.clone()));
+        assert_eq!(t.client.get_max_loan_for_agent(&agent), TIER_1_MAX_LOAN);
+
+        // XLM doubles: the same 5 cents buys half the XLM
+        oracle.set_price(&2_000_000);
+        assert_eq!(t.client.get_max_loan_for_agent(&agent), TIER_1_MAX_LOAN / 2);
+        assert!(t.client.try_request_loan(&agent, &TIER_1_MAX_LOAN, &day).is_err());
+        t.client.request_loan(&agent, &(TIER_1_MAX_LOAN / 2), &day);
+
+        t.client.set_price_oracle(&t.admin, &None);
+        assert_eq!(t.client.get_max_loan_for_reputation(&50), TIER_1_MAX_LOAN);
+
+        // The oracle can also be given at initialization
+        let priced = LendingDemoContractClient::new(&t.env, &t.env.register(LendingDemoContract, ()));
+        priced.initialize(
+            &t.admin,
+            &t.agents.address,
+            &t.rep.address,
+            &t.token.address,
+            &6_000,
+            &8_000,
+            &0,
+            &Some(oracle_id.clone()),
         );
+        assert_eq!(priced.get_price_oracle(), Some(oracle_id));
+        assert_eq!(priced.get_max_loan_for_reputation(&50), TIER_1_MAX_LOAN / 2);
+    }
+
+    #[test]
+    fn test_transfer_loan_moves_it_to_the_new_agent() {
+        let t = setup(10_000_000);
+        let day = 24 * 60 * 60;
+        let from_agent = funded_agent(&t, 0);
+        let to_agent = funded_agent(&t, 1_000_000);
+        let unregistered = Address::generate(&t.env);
+
+        let loan_id = t.client.request_loan(&from_agent, &1_000_000, &day).loan_id;
+        assert!(t.client.try_transfer_loan(&t.admin, &from_agent, &unregistered).is_err());
 
-        // ✅ NEW: Mark penalty as applied
-        env.storage().persistent().set(&penalty_key, &true);
+        t.client.transfer_loan(&t.admin, &from_agent, &to_agent);
+        assert_eq!(t.client.get_loan(&from_agent), None);
+        let loan = t.client.get_loan(&to_agent).unwrap();
+        assert_eq!(loan.loan_id, loan_id);
+        assert_eq!(loan.agent, to_agent);
+        assert_eq!(t.client.get_loan_by_id(&loan_id).unwrap().agent, to_agent);
+        assert_eq!(t.client.loans_due_within(&day), Vec::from_array(&t.env, [to_agent.clone()]));
+        assert_eq!(t.client.get_pool_status().total_outstanding, 1_000_000);
+
+        // The interest-free first loan stays free under the new agent, and the original
+        // agent has used up its free loan
+        assert_eq!(loan.terms.interest_bps, 0);
+        assert_eq!(loan.booked_interest, 0);
+        assert!(t.client.get_agent_state(&from_agent).had_first_loan);
+
+        // The new agent repays it, interest-free
+        t.client.repay_loan(&to_agent, &None);
+        assert_eq!(t.client.get_pool_status().total_outstanding, 0);
+        assert_eq!(t.token.balance(&to_agent), 0);
+
+        // The original agent's next loan pays interest
+        t.env.ledger().set_timestamp(2 * day);
+        let receipt = t.client.request_loan(&from_agent, &1_000_000, &day);
+        assert_eq!(receipt.interest_bps, LOAN_INTEREST_BPS as u32);
     }
 
-    /// Get loan information
-    pub fn get_loan(env: Env, agent: Address) -> Option<Loan> {
-        let key = DataKey::Loan(agent);
-        env.storage().persistent().get(&key)
+    #[test]
+    fn test_reserve_fills_from_interest_until_target() {
+        let t = setup(10_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 1_000_000);
+        t.client.set_reserve_policy(&t.admin, &60_000, &5_000);
+        assert_eq!(t.client.reserve_fill_progress(), 0);
+
+        // Interest-free first loan leaves the reserve empty
+        t.client.request_loan(&agent, &2_000_000, &day);
+        t.client.repay_loan(&agent, &None);
+        assert_eq!(t.client.get_pool_status().reserve_balance, 0);
+
+        // Half of the 100_000 interest goes to the reserve
+        t.client.request_loan(&agent, &2_000_000, &day);
+        t.client.repay_loan(&agent, &None);
+        assert_eq!(t.client.get_pool_status().reserve_balance, 50_000);
+        assert_eq!(t.client.reserve_fill_progress(), 8_333);
+
+        // Only the 10_000 still missing, then nothing
+        for _ in 0..2 {
+            t.client.request_loan(&agent, &2_000_000, &day);
+            t.client.repay_loan(&agent, &None);
+        }
+        let status = t.client.get_pool_status();
+        assert_eq!(status.reserve_balance, 60_000);
+        assert_eq!(t.client.reserve_fill_progress(), 10_000);
+
+        // The reserve is held back from lending
+        assert_eq!(t.client.get_liquidity(), 10_300_000);
+        assert_eq!(status.total_liquidity, 10_240_000);
+        assert_eq!(t.client.get_lendable_liquidity(), 10_240_000);
     }
 
-    /// Get the maximum loan amount for a given reputation score
-    /// This implements the tiered lending system
-    pub fn get_max_loan_for_reputation(env: Env, reputation_score: u32) -> u64 {
-        calculate_max_loan_amount(reputation_score)
+    #[test]
+    fn test_total_outstanding_cap() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        t.client.set_max_total_outstanding(&t.admin, &8_000_000);
+        assert_eq!(t.client.get_config().max_total_outstanding, 8_000_000);
+
+        t.client.request_loan(&funded_agent(&t, 0), &5_000_000, &day);
+        t.client.request_loan(&funded_agent(&t, 0), &3_000_000, &day);
+
+        // Ample liquidity and low utilization, but the aggregate cap is hit
+        let blocked = funded_agent(&t, 0);
+        assert!(t.client.try_request_loan(&blocked, &MIN_LOAN_AMOUNT, &day).is_err());
+
+        t.client.set_max_total_outstanding(&t.admin, &0);
+        t.client.request_loan(&blocked, &MIN_LOAN_AMOUNT, &day);
     }
 
-    /// Get current pool utilization percentage
-    pub fn get_pool_utilization(env: Env) -> u32 {
-        let xlm_token: Address = env
-            .storage()
-            .persistent()
-            .get(&DataKey::XlmTokenContract(()))
-            .expect("Contract not initialized");
+    #[test]
+    fn test_low_reputation_withdrawals_are_capped() {
+        let t = setup(0);
+        let low = Address::generate(&t.env);
+        let high = Address::generate(&t.env);
+        t.token_admin.mint(&low, &10_000_000);
+        t.token_admin.mint(&high, &10_000_000);
+        t.rep.set_score(&high, &80);
 
-        let xlm_client = token::Client::new(&env, &xlm_token);
-        let total_liquidity = xlm_client.balance(&env.current_contract_address()) as u64;
-        
-        calculate_pool_utilization(&env, total_liquidity)
+        t.client.deposit(&low, &10_000_000);
+        t.client.deposit(&high, &10_000_000);
+        assert_eq!(t.client.max_withdrawal(&low), 2_500_000);
+        assert_eq!(t.client.max_withdrawal(&high), 10_000_000);
+
+        // Score 50 redeems at most 25% of its position per day
+        assert_eq!(t.client.withdraw(&low, &10_000_000), 2_500_000);
+        assert_eq!(t.client.get_lender_shares(&low), 7_500_000);
+        assert_eq!(t.client.max_withdrawal(&low), 0);
+        assert!(t.client.try_withdraw(&low, &1_000).is_err());
+
+        // A new period resets the allowance
+        t.env.ledger().set_timestamp(24 * 60 * 60);
+        assert_eq!(t.client.max_withdrawal(&low), 1_875_000);
+
+        // High reputation withdraws freely
+        assert_eq!(t.client.withdraw(&high, &10_000_000), 10_000_000);
     }
 
-    /// Check if a loan is currently overdue (past grace period)
-    /// ✅ NEW: Automatically applies penalty if overdue and not yet penalized
-    pub fn is_loan_overdue(env: Env, agent: Address) -> bool {
-        let loan_key = DataKey::Loan(agent.clone());
-        if let Some(loan) = env.storage().persistent().get::<DataKey, Loan>(&loan_key) {
-            if !loan.repaid {
-                let current_time = env.ledger().timestamp();
-                let grace_deadline = loan.due_date + GRACE_PERIOD_SECONDS;
-                let is_overdue = current_time > grace_deadline;
-                
-                // ✅ NEW: Auto-apply penalty if overdue and not yet applied
-                if is_overdue {
-                    let penalty_key = DataKey::PenaltyApplied(agent.clone());
-                    let penalty_already_applied: bool = env
-                        .storage()
-                        .persistent()
-                        .get(&penalty_key)
-                        .unwrap_or(false);
-
-                    if !penalty_already_applied {
-                        // Apply penalty automatically
-                        let rep_mgr_addr: Address = env
-                            .storage()
-                            .persistent()
-                            .get(&DataKey::ReputationManagerContract(()))
-                            .expect("Contract not initialized");
-
-                        let rep_mgr_client = ReputationManagerClient::new(&env, &rep_mgr_addr);
-                        let contract_addr = env.current_contract_address();
-                        
-                        rep_mgr_client.update_score(
-                            &contract_addr,
-                            &agent,
-                            &REPUTATION_DECREASE_DEFAULT
-                        );
-
-                        // Mark penalty as applied
-                        env.storage().persistent().set(&penalty_key, &true);
-                    }
-                }
-                
-                return is_overdue;
-            }
+    #[test]
+    fn test_owner_reputation_aggregates_agent_scores() {
+        let t = setup(0);
+        let owner = Address::generate(&t.env);
+        for score in [40u32, 70, 91] {
+            let agent = Address::generate(&t.env);
+            t.agents.register_agent(&owner, &agent, &Vec::new(&t.env), &u64::MAX);
+            t.rep.set_score(&agent, &score);
         }
-        false
+
+        assert_eq!(t.client.owner_reputation(&owner, &AggregateMode::Average), 67);
+        assert_eq!(t.client.owner_reputation(&owner, &AggregateMode::Min), 40);
+        assert_eq!(t.client.owner_reputation(&owner, &AggregateMode::Max), 91);
+
+        // An owner without agents has the configured default score
+        let newcomer = Address::generate(&t.env);
+        assert_eq!(t.client.owner_reputation(&newcomer, &AggregateMode::Min), REPUTATION_BASELINE);
+        t.rep.set_default_score(&30);
+        assert_eq!(t.client.owner_reputation(&newcomer, &AggregateMode::Min), 30);
     }
-}
 
-/// HELPER FUNCTIONS FOR ENHANCED LENDING ALGORITHM
+    #[test]
+    fn test_repaying_a_defaulted_loan_recovers_reputation() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let keeper = Address::generate(&t.env);
+        let repaid = funded_agent(&t, 1_000_000);
+        let reported_then_repaid = funded_agent(&t, 1_000_000);
+        let abandoned = funded_agent(&t, 0);
+        t.client.set_default_recovery(&t.admin, &10);
+        assert!(t.client.try_set_default_recovery(&t.admin, &25).is_err());
+
+        for agent in [&repaid, &reported_then_repaid, &abandoned] {
+            t.client.request_loan(agent, &1_000_000, &day);
+        }
+        t.env.ledger().set_timestamp(3 * day);
+        t.client.report_default(&keeper, &reported_then_repaid);
+        t.client.report_default(&keeper, &abandoned);
 
-/// Calculate maximum loan amount based on reputation score (tiered system)
-fn calculate_max_loan_amount(reputation_score: u32) -> u64 {
-    match reputation_score {
-        0..=49 => 0,                    // No loans for very low reputation
-        50..=59 => TIER_1_MAX_LOAN,     // 0.5 XLM for new/low reputation
-        60..=74 => TIER_2_MAX_LOAN,     // 2.0 XLM for decent reputation  
-        75..=89 => TIER_3_MAX_LOAN,     // 5.0 XLM for good reputation
-        90..=100 => TIER_4_MAX_LOAN,    // 10.0 XLM for excellent reputation
-        _ => 0,                         // Safety fallback
+        // -25 for the default, +10 back for repaying it
+        t.client.repay_loan(&repaid, &None);
+        t.client.repay_loan(&reported_then_repaid, &None);
+        assert_eq!(t.rep.get_score(&repaid), 35);
+        assert_eq!(t.rep.get_score(&reported_then_repaid), 35);
+        assert_eq!(t.rep.get_score(&abandoned), 25);
+
+        let history = t.client.get_loan_history(&reported_then_repaid);
+        assert_eq!(history.get(0).unwrap().outcome, LoanOutcome::Default);
+        assert_eq!(history.get(1).unwrap().outcome, LoanOutcome::Recovered);
+        assert_eq!(history.get(1).unwrap().reputation_delta, 10);
+        assert!(!t.client.export_state(&t.admin).agents.get(1).unwrap().penalty_applied);
     }
-}
 
-/// Calculate current pool utilization to prevent over-lending
-fn calculate_pool_utilization(env: &Env, total_liquidity: u64) -> u32 {
-    if total_liquidity == 0 {
-        return 100; // 100% utilization if no liquidity
+    #[test]
+    fn test_request_loan_returns_receipt() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 1_000_000);
+        t.env.ledger().set_timestamp(1_000);
+
+        let receipt = t.client.request_loan(&agent, &2_000_000, &(7 * day));
+        let loan = t.client.get_loan(&agent).unwrap();
+        assert_eq!(receipt.loan_id, loan.loan_id);
+        assert_eq!(receipt.amount, loan.amount);
+        assert_eq!(receipt.due_date, loan.due_date);
+        assert_eq!(receipt.due_date, 1_000 + 7 * day);
+        assert_eq!(receipt.interest_bps, 0); // Interest-free first loan
+
+        t.client.repay_loan(&agent, &None);
+        let receipt = t.client.request_loan(&agent, &2_000_000, &(7 * day));
+        assert_eq!(receipt.loan_id, t.client.get_loan(&agent).unwrap().loan_id);
+        assert_eq!(receipt.interest_bps, LOAN_INTEREST_BPS as u32);
     }
 
-    // Count all active loans to calculate utilization
-    // In a real implementation, you'd track this more efficiently
-    // For now, we'll use a simplified approach
-    
-    // This is a simplified calculation - in production you'd maintain
-    // a separate counter for total outstanding loans
-    // For demo purposes, we'll assume 50% utilization as baseline
-    let estimated_utilization = 50; // Placeholder - would be calculated from actual loan data
-    
-    estimated_utilization.min(100)
-}
+    #[test]
+    fn test_utilization_cap_depends_on_tier() {
+        let t = setup(20_000_000);
+        let day = 24 * 60 * 60;
+        t.client.set_tier_utilization_caps(
+            &t.admin,
+            &Vec::from_array(&t.env, [6_000, 7_000, 8_000, 9_000]),
+        );
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use soroban_sdk::Env;
-    use soroban_sdk::testutils::Address as _;
+        let whale = funded_agent(&t, 0);
+        let tier_1 = funded_agent(&t, 0);
+        let tier_4 = funded_agent(&t, 0);
+        t.rep.set_score(&whale, &95);
+        t.rep.set_score(&tier_4, &90);
+
+        // 72.5% utilization
+        t.client.request_loan(&whale, &14_500_000, &day);
+
+        // Taking the pool to 75%: past tier 1's 60% cap, within tier 4's 90%
+        assert!(t.client.try_request_loan(&tier_1, &500_000, &day).is_err());
+        t.client.request_loan(&tier_4, &500_000, &day);
+        assert_eq!(t.client.get_pool_utilization(), 75);
+    }
 
     #[test]
-    fn test_contract_initialization() {
-        let env = Env::default();
-        env.mock_all_auths();
+    fn test_repay_via_allowance_needs_allowance() {
+        let t = setup(10_000_000);
+        let day = 24 * 60 * 60;
+        let keeper = Address::generate(&t.env);
+        t.client.approve_keeper(&t.admin, &keeper);
+        let agent = funded_agent(&t, 1_000_000);
+        let expiration = t.env.ledger().sequence() + 1_000;
 
-        let contract_id = env.register(LendingDemoContract, ());
-        let client = LendingDemoContractClient::new(&env, &contract_id);
+        // No allowance: the keeper can't collect
+        t.client.request_loan(&agent, &1_000_000, &day);
+        assert!(t.client.try_repay_via_allowance(&keeper, &agent).is_err());
 
-        let admin = Address::generate(&env);
-        let agent_manager_addr = Address::generate(&env);
-        let reputation_manager_addr = Address::generate(&env);
-        let xlm_token_addr = Address::generate(&env);
+        // Only keepers collect
+        t.token.approve(&agent, &t.client.address, &1_000_000, &expiration);
+        assert!(t.client.try_repay_via_allowance(&agent, &agent).is_err());
 
-        // Test initialization
-        client.initialize(&admin, &agent_manager_addr, &reputation_manager_addr, &xlm_token_addr);
+        t.client.repay_via_allowance(&keeper, &agent);
+        assert!(t.client.get_loan(&agent).unwrap().repaid);
+        assert_eq!(t.token.balance(&agent), 1_000_000);
+        assert_eq!(t.token.allowance(&agent, &t.client.address), 0);
+        assert_eq!(t.rep.get_score(&agent), 62); // Early repayment bonus
+    }
 
-        // Verify addresses are stored (we can't directly check storage in tests,
-        // but if initialization didn't panic, it worked)
+    #[test]
+    fn test_loan_duration_is_bounded() {
+        let t = setup(10_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 0);
+        t.env.ledger().set_timestamp(1_000);
+
+        // Would wrap past u64::MAX without the checks
+        assert!(t.client.try_request_loan(&agent, &1_000_000, &(u64::MAX - 10)).is_err());
+        assert!(t.client.try_request_loan(&agent, &1_000_000, &(366 * day)).is_err());
+        assert!(t.client.get_loan(&agent).is_none());
+
+        // Even with the limit lifted, overflow is rejected rather than wrapping
+        t.client.set_max_loan_duration(&t.admin, &u64::MAX);
+        assert!(t.client.try_request_loan(&agent, &1_000_000, &(u64::MAX - 10)).is_err());
+
+        t.client.set_max_loan_duration(&t.admin, &(30 * day));
+        assert!(t.client.try_request_loan(&agent, &1_000_000, &(31 * day)).is_err());
+        t.client.request_loan(&agent, &1_000_000, &(30 * day));
+        assert_eq!(t.client.get_loan(&agent).unwrap().due_date, 1_000 + 30 * day);
     }
 
     #[test]
-    fn test_loan_storage() {
+    fn test_owner_budget_caps_agents_together() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let owner = Address::generate(&t.env);
+        let first = Address::generate(&t.env);
+        let second = Address::generate(&t.env);
+        t.agents.register_agent(&owner, &first, &Vec::new(&t.env), &u64::MAX);
+        t.agents.register_agent(&owner, &second, &Vec::new(&t.env), &u64::MAX);
+
+        assert_eq!(t.client.owner_remaining_budget(&owner), u64::MAX);
+        t.agents.set_owner_budget(&owner, &Some(1_500_000));
+
+        t.client.request_loan(&first, &1_000_000, &day);
+        assert_eq!(t.client.owner_remaining_budget(&owner), 500_000);
+
+        // Within each agent's own limit, but not the shared budget
+        assert!(t.client.try_request_loan(&second, &1_000_000, &day).is_err());
+        t.client.request_loan(&second, &500_000, &day);
+        assert_eq!(t.client.owner_remaining_budget(&owner), 0);
+
+        // Repaying frees budget up again
+        t.client.repay_loan(&first, &None);
+        assert_eq!(t.client.owner_remaining_budget(&owner), 1_000_000);
+
+        // A transferred loan must fit the receiving owner's budget, unless it already counts there
+        let third = Address::generate(&t.env);
+        t.agents.register_agent(&owner, &third, &Vec::new(&t.env), &u64::MAX);
+        t.client.request_loan(&first, &1_000_000, &day);
+        t.client.transfer_loan(&t.admin, &second, &third);
+        assert_eq!(t.client.owner_remaining_budget(&owner), 0);
+
+        let other_owner = Address::generate(&t.env);
+        let outsider = Address::generate(&t.env);
+        t.agents.register_agent(&other_owner, &outsider, &Vec::new(&t.env), &u64::MAX);
+        t.agents.set_owner_budget(&other_owner, &Some(100_000));
+        assert!(t.client.try_transfer_loan(&t.admin, &third, &outsider).is_err());
+    }
+
+    #[test]
+    fn test_defaulted_agents_worklist() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let keeper = Address::generate(&t.env);
+        let defaulter = funded_agent(&t, 1_000_000);
+        let punctual = funded_agent(&t, 1_000_000);
+        t.client.request_loan(&defaulter, &1_000_000, &day);
+        t.client.request_loan(&punctual, &1_000_000, &(7 * day));
+        assert!(t.client.get_defaulted_agents().is_empty());
+
+        t.env.ledger().set_timestamp(3 * day);
+        t.client.report_default(&keeper, &defaulter);
+        assert_eq!(t.client.get_defaulted_agents(), Vec::from_array(&t.env, [defaulter.clone()]));
+
+        t.client.repay_loan(&punctual, &None);
+        assert_eq!(t.client.get_defaulted_agents(), Vec::from_array(&t.env, [defaulter.clone()]));
+
+        // Repaying takes the agent off the worklist
+        t.client.repay_loan(&defaulter, &None);
+        assert!(t.client.get_defaulted_agents().is_empty());
+    }
+
+    #[test]
+    fn test_early_repayment_waives_part_of_the_fee() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        t.client.set_protocol_fee(&t.admin, &2_000);
+        t.client.set_early_fee_waiver(&t.admin, &5_000);
+        let early = funded_agent(&t, 10_000_000);
+        let on_time = funded_agent(&t, 10_000_000);
+
+        // First loans are interest-free; the second ones pay 5% interest
+        for agent in [&early, &on_time] {
+            t.client.request_loan(agent, &1_000_000, &(7 * day));
+            t.client.repay_loan(agent, &None);
+            t.client.request_loan(agent, &2_000_000, &(7 * day));
+        }
+
+        // 100_000 interest: 20_000 fee, half of it waived for the early repayment
+        t.client.repay_loan(&early, &None);
+        assert_eq!(t.client.get_accrued_fees(), 10_000);
+        assert_eq!(t.token.balance(&early), 10_000_000 - 90_000);
+
+        t.env.ledger().set_timestamp(7 * day);
+        t.client.repay_loan(&on_time, &None);
+        assert_eq!(t.client.get_accrued_fees(), 30_000);
+        assert_eq!(t.token.balance(&on_time), 10_000_000 - 100_000);
+
+        // The early bonus is still earned in full alongside the waiver
+        assert_eq!(t.rep.get_score(&early), 50 + 12 + 12);
+    }
+
+    #[test]
+    fn test_loan_terms_are_locked_at_issuance() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let hour = 60 * 60;
+        let agent = funded_agent(&t, 10_000_000);
+        t.rep.set_score(&agent, &90);
+
+        // Second loan, so it carries interest
+        t.client.request_loan(&agent, &1_000_000, &day);
+        t.client.repay_loan(&agent, &None);
+        t.client.request_loan(&agent, &5_000_000, &(2 * day));
+        let terms = t.client.get_loan(&agent).unwrap().terms;
+        assert_eq!(terms.max_loan, 100_000_000);
+        assert_eq!(terms.interest_bps, 500);
+        assert_eq!(terms.grace_period_seconds, day);
+
+        // Reputation collapses and the windows tighten after issuance
+        t.rep.set_score(&agent, &10);
+        t.client.set_default_windows(&t.admin, &0, &hour);
+        let due = t.client.get_amount_due(&agent);
+        assert_eq!(due, 5_250_000);
+
+        // 12 hours late: still on time under the loan's own 1-day grace, same amount owed
+        t.env.ledger().set_timestamp(2 * day + 12 * hour);
+        assert!(!t.client.is_loan_overdue(&agent));
+        let before = t.token.balance(&agent);
+        t.client.repay_loan(&agent, &None);
+        assert_eq!(before - t.token.balance(&agent), due as i128);
+        assert_eq!(t.client.get_loan_history(&agent).get(1).unwrap().outcome, LoanOutcome::OnTime);
+    }
+
+    #[test]
+    fn test_loan_cooldown_blocks_immediate_reborrow() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let hour = 60 * 60;
+        let agent = funded_agent(&t, 1_000_000);
+        t.client.set_loan_cooldown(&t.admin, &hour);
+        t.env.ledger().set_timestamp(1_000);
+        assert_eq!(t.client.next_borrow_time(&agent), 0);
+
+        t.client.request_loan(&agent, &1_000_000, &day);
+        t.client.repay_loan(&agent, &None);
+        assert_eq!(t.client.next_borrow_time(&agent), 1_000 + hour);
+
+        assert!(t.client.try_request_loan(&agent, &1_000_000, &day).is_err());
+        t.env.ledger().set_timestamp(1_000 + hour - 1);
+        assert!(t.client.try_request_loan(&agent, &1_000_000, &day).is_err());
+
+        t.env.ledger().set_timestamp(1_000 + hour);
+        t.client.request_loan(&agent, &1_000_000, &day);
+    }
+
+    #[test]
+    fn test_recovered_default_is_split() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let keeper = Address::generate(&t.env);
+        let agent = funded_agent(&t, 0);
+        assert!(t.client.try_set_recovery_split(&t.admin, &5_000, &3_000, &1_000).is_err());
+        t.client.set_recovery_split(&t.admin, &5_000, &3_000, &2_000);
+        assert_eq!(t.client.get_config().recovery_split.treasury_bps, 2_000);
+
+        // An interest-free first loan, defaulted then repaid in full
+        t.client.request_loan(&agent, &1_000_000, &day);
+        t.env.ledger().set_timestamp(3 * day);
+        t.client.report_default(&keeper, &agent);
+        let liquidity_before = t.client.get_lendable_liquidity();
+        t.client.repay_loan(&agent, &None);
+
+        assert_eq!(t.client.get_pool_status().reserve_balance, 300_000);
+        assert_eq!(t.client.get_accrued_fees(), 200_000);
+        assert_eq!(t.client.get_lendable_liquidity() - liquidity_before, 500_000);
+    }
+
+    #[test]
+    fn test_close_loan_frees_only_repaid_loans() {
+        let t = setup(100_000_000);
+        let day = 24 * 60 * 60;
+        let agent = funded_agent(&t, 1_000_000);
+
+        let loan_id = t.client.request_loan(&agent, &1_000_000, &day).loan_id;
+        assert!(t.client.try_close_loan(&agent).is_err());
+        assert!(t.client.get_loan(&agent).is_some());
+
+        t.client.repay_loan(&agent, &None);
+        t.client.close_loan(&agent);
+        assert!(t.client.get_loan(&agent).is_none());
+        assert!(t.client.get_loan_by_id(&loan_id).is_none());
+        assert_eq!(t.client.get_loan_history(&agent).len(), 1);
+
+        // Borrowing again does not list the agent twice
+        t.client.request_loan(&agent, &1_000_000, &day);
+        assert_eq!(t.client.export_state(&t.admin).agents.len(), 1);
+    }
+
+    #[test]
+    fn test_interest_rebates_for_well_reputed_borrowers() {
+        let t = setup(100_000_000);
+        let keeper = Address::generate(&t.env);
+        t.client.approve_keeper(&t.admin, &keeper);
+        t.client.set_rebate_policy(&t.admin, &1_000, &75, &(30 * 24 * 60 * 60));
+
+        let large = funded_agent(&t, 1_000_000);
+        let small = funded_agent(&t, 1_000_000);
+        let low = funded_agent(&t, 1_000_000);
+        for agent in [&large, &small, &low] {
+            // First loans are interest-free
+            t.client.request_loan(agent, &1_000_000, &(7 * 24 * 60 * 60));
+            t.client.repay_loan(agent, &None);
+        }
+        t.client.request_loan(&large, &2_000_000, &(7 * 24 * 60 * 60));
+        t.client.repay_loan(&large, &None);
+        t.client.request_loan(&small, &1_000_000, &(7 * 24 * 60 * 60));
+        t.client.repay_loan(&small, &None);
+        t.client.request_loan(&low, &1_000_000, &(7 * 24 * 60 * 60));
+        t.client.repay_loan(&low, &None);
+        assert_eq!(t.client.get_interest_paid(&large), 100_000);
+        assert_eq!(t.client.get_interest_paid(&small), 50_000);
+
+        t.rep.set_score(&large, &90);
+        t.rep.set_score(&small, &80);
+        t.rep.set_score(&low, &60);
+        let before = [&large, &small, &low].map(|a| t.token.balance(a));
+
+        // 10% of paid interest, only for agents at 75 or above
+        assert_eq!(t.client.distribute_rebates(&keeper), 15_000);
+        assert_eq!(t.token.balance(&large) - before[0], 10_000);
+        assert_eq!(t.token.balance(&small) - before[1], 5_000);
+        assert_eq!(t.token.balance(&low), before[2]);
+        assert_eq!(t.client.get_interest_paid(&large), 0);
+        assert_eq!(t.client.get_interest_paid(&low), 0);
+
+        // Only one distribution per period
+        assert!(t.client.try_distribute_rebates(&keeper).is_err());
+        t.env.ledger().set_timestamp(31 * 24 * 60 * 60);
+        assert_eq!(t.client.distribute_rebates(&keeper), 0);
+    }
+
+    #[test]
+    fn test_request_loan_rejects_misconfigured_token() {
         let env = Env::default();
         env.mock_all_auths();
 
         let contract_id = env.register(LendingDemoContract, ());
         let client = LendingDemoContractClient::new(&env, &contract_id);
+        let agent_mgr_id = env.register(MockAgentManager, ());
+        let agents = MockAgentManagerClient::new(&env, &agent_mgr_id);
+        let rep_id = env.register(MockReputationManager, ());
+        agents.set_reputation_manager(&rep_id);
+
+        // Wired to an address with no token contract behind it
+        let admin = Address::generate(&env);
+        let missing_token = Address::generate(&env);
+        client.initialize(&admin, &agent_mgr_id, &rep_id, &missing_token, &6_000, &8_000, &0, &None);
 
         let agent = Address::generate(&env);
+        agents.register_agent(&Address::generate(&env), &agent, &Vec::new(&env), &u64::MAX);
+        let result = client.try_request_loan(&agent, &1_000_000, &(24 * 60 * 60));
+        assert_eq!(
+            result.err().unwrap().unwrap(),
+            soroban_sdk::Error::from_contract_error(LendingError::Misconfigured as u32)
+        );
+    }
 
-        // Initially no loan should exist
-        let loan = client.get_loan(&agent);
-        assert!(loan.is_none());
+    #[test]
+    fn test_request_loan_rejects_uninitialized_agent_manager() {
+        let t = setup(10_000_000);
 
-        // Note: We can't test request_loan without the other contracts deployed
-        // This demonstrates the contract structure is correct
+        // An AgentManager that was deployed but never given its ReputationManager
+        let unwired_id = t.env.register(MockAgentManager, ());
+        let unwired = MockAgentManagerClient::new(&t.env, &unwired_id);
+        let agent = Address::generate(&t.env);
+        unwired.register_agent(&Address::generate(&t.env), &agent, &Vec::new(&t.env), &u64::MAX);
+        t.env.as_contract(&t.client.address, || {
+            t.env
+                .storage()
+                .persistent()
+                .set(&DataKey::AgentManagerContract(()), &unwired_id);
+        });
+
+        // Reported as a wiring problem, even under the lenient reputation fallback
+        t.client.set_reputation_fallback(&t.admin, &true);
+        let result = t.client.try_request_loan(&agent, &1_000_000, &(24 * 60 * 60));
+        assert_eq!(
+            result.err().unwrap().unwrap(),
+            soroban_sdk::Error::from_contract_error(LendingError::Misconfigured as u32)
+        );
+    }
+
+    #[test]
+    fn test_short_small_loan_owes_minimum_interest() {
+        let t = setup(100_000_000);
+        let agent = funded_agent(&t, 1_000_000);
+        let year = 365 * 24 * 60 * 60;
+        t.client.set_interest_mode(&t.admin, &false, &year);
+        t.client.set_min_interest(&t.admin, &1_000);
+        assert_eq!(t.client.min_interest(), 1_000);
+
+        // The interest-free first loan stays free
+        t.client.request_loan(&agent, &1_000_000, &(24 * 60 * 60));
+        assert_eq!(t.client.get_amount_due(&agent), 1_000_000);
+        t.client.repay_loan(&agent, &None);
+
+        // A minute at 5% a year rounds down to nothing, so the floor applies
+        t.client.request_loan(&agent, &1_000_000, &(24 * 60 * 60));
+        t.env.ledger().set_timestamp(t.env.ledger().timestamp() + 60);
+        assert_eq!(t.client.get_amount_due(&agent), 1_001_000);
+        let before = t.token.balance(&agent);
+        t.client.repay_loan(&agent, &None);
+        assert_eq!(before - t.token.balance(&agent), 1_001_000);
+    }
+
+    #[test]
+    fn test_borrow_blocked_agent_can_still_repay() {
+        let t = setup(100_000_000);
+        let agent = funded_agent(&t, 0);
+        let day = 24 * 60 * 60;
+        t.client.request_loan(&agent, &1_000_000, &day);
+
+        assert!(t.client.try_set_borrow_block(&agent, &agent, &true).is_err());
+        t.client.set_borrow_block(&t.admin, &agent, &true);
+        assert!(t.client.is_borrow_blocked(&agent));
+
+        // The open loan can be repaid, but no new one taken
+        t.client.repay_loan(&agent, &None);
+        assert!(t.client.try_request_loan(&agent, &1_000_000, &day).is_err());
+
+        t.client.set_borrow_block(&t.admin, &agent, &false);
+        assert!(!t.client.is_borrow_blocked(&agent));
+        t.client.request_loan(&agent, &1_000_000, &day);
+    }
+
+    #[test]
+    fn test_overpayment_is_refunded() {
+        let t = setup(100_000_000);
+        let agent = funded_agent(&t, 1_000_000);
+        let day = 24 * 60 * 60;
+
+        t.client.request_loan(&agent, &1_000_000, &day);
+        t.client.repay_loan(&agent, &None);
+        t.client.request_loan(&agent, &1_000_000, &day);
+        let owed = t.client.get_amount_due(&agent);
+        assert_eq!(owed, 1_050_000);
+
+        // Underpaying is refused outright
+        assert!(t.client.try_repay_loan(&agent, &Some(owed - 1)).is_err());
+
+        // Overpaying settles the loan and hands back the difference
+        let before = t.token.balance(&agent);
+        t.client.repay_loan(&agent, &Some(owed + 200_000));
+        assert_eq!(before - t.token.balance(&agent), owed as i128);
+        assert!(t.client.get_loan(&agent).unwrap().repaid);
+    }
+
+    #[test]
+    fn test_amortization_schedule_sums_to_total_owed() {
+        let t = setup(0);
+        let day = 24 * 60 * 60;
+        t.env.ledger().set_timestamp(1_000);
+
+        // 5% flat interest on an amount that doesn't split evenly
+        let schedule = t.client.amortization_schedule(&1_000_001, &500, &(30 * day), &3);
+        assert_eq!(schedule.len(), 3);
+        let total: u64 = schedule.iter().map(|installment| installment.amount).sum();
+        let principal: u64 = schedule.iter().map(|installment| installment.principal).sum();
+        assert_eq!(total, 1_050_001);
+        assert_eq!(principal, 1_000_001);
+
+        // Evenly spaced, the last one due at the end of the term and taking the remainder
+        assert_eq!(schedule.get(0).unwrap().due_date, 1_000 + 10 * day);
+        assert_eq!(schedule.get(2).unwrap().due_date, 1_000 + 30 * day);
+        assert_eq!(schedule.get(0).unwrap().amount, 349_999);
+        assert_eq!(schedule.get(2).unwrap().amount, 350_003);
+
+        assert!(t.client.try_amortization_schedule(&1_000_000, &500, &(30 * day), &0).is_err());
+
+        // 200% flat interest on half of u64::MAX owes more than a u64 can hold
+        let result = t.client.try_amortization_schedule(&(u64::MAX / 2), &20_000, &(30 * day), &1);
+        assert_eq!(
+            result.err().unwrap().unwrap(),
+            soroban_sdk::Error::from_contract_error(LendingError::AmountOverflow as u32)
+        );
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,127 @@
 #![no_std]
 
-use soroban_sdk::{contract, contractimpl, contracttype, Address, Env};
+use soroban_sdk::{contract, contractclient, contractimpl, contracttype, Address, BytesN, Env, Executable, String, Vec};
 
 /// Reputation score bounds
 const MIN_SCORE: u32 = 0;
 const MAX_SCORE: u32 = 100;
-const DEFAULT_SCORE: u32 = 50; // Neutral, unproven user
+const DEFAULT_SCORE: u32 = 50; // Neutral midpoint; the usual starting score for unproven users
+
+/// Lower bound of each reputation tier (tiers 0-4, as used by consumer contracts)
+const TIER_1_MIN_SCORE: u32 = 50;
+const TIER_2_MIN_SCORE: u32 = 60;
+const TIER_3_MIN_SCORE: u32 = 75;
+const TIER_4_MIN_SCORE: u32 = 90;
+const TIER_COUNT: usize = 5;
+
+/// Most recent score changes kept per agent (oldest are dropped first)
+const MAX_SCORE_HISTORY: u32 = 32;
+
+/// Most recent actions kept in each agent's action log (oldest are dropped first)
+const MAX_ACTION_LOG: u32 = 50;
+
+/// Most recent idempotency keys remembered per caller (oldest are forgotten first)
+const MAX_IDEMPOTENCY_KEYS: u32 = 64;
+
+/// Threshold-crossing subscriptions notified on score changes (oldest first)
+const MAX_SUBSCRIPTIONS: u32 = 8;
+
+/// A score value and when it took effect
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScoreSample {
+    pub timestamp: u64,
+    pub score: u32,
+}
+
+/// Bookkeeping about an agent's score changes
+#[contracttype]
+#[derive(Clone, Default)]
+pub struct ScoreMeta {
+    pub last_updated: u64,  // Unix timestamp of the latest change (0 if never changed)
+    pub change_count: u32,  // Number of times the score has been written
+}
+
+/// An authorized action an agent took, as reported by an approved consumer contract
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActionEntry {
+    pub action: String,   // Action name (e.g., "borrow", "repay_loan")
+    pub amount: u64,      // Amount the action moved
+    pub timestamp: u64,   // Unix timestamp when the action was logged
+    pub caller: Address,  // Consumer contract that reported the action
+}
+
+/// An idempotent score update as remembered under its key, so a resubmission can be checked
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdempotentUpdate {
+    pub agent: Address, // Agent the update applied to
+    pub delta: i32,     // Change that was requested
+    pub score: u32,     // Score the update produced
+}
+
+/// An agent's open challenge to a penalty it believes was unjust
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dispute {
+    pub loan_reference: u64, // The consumer contract's id for the disputed loan
+    pub opened_at: u64,      // Unix timestamp when the dispute was opened
+}
+
+/// An agent's score together with its metadata, for single-read UIs
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScoreDetail {
+    pub score: u32,
+    pub frozen: bool,        // Whether the agent has been frozen for a severe violation
+    pub last_updated: u64,   // Unix timestamp of the latest change (0 if never changed)
+    pub change_count: u32,   // Number of historical score changes
+    pub tier: u32,           // Reputation tier 0-4
+    pub disputed: bool,      // Whether the agent has an unresolved dispute open
+}
+
+/// Network-wide summary of stored scores, kept up to date on every score write
+/// Only agents that have been scored at least once are counted.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScoreDistribution {
+    pub tier_counts: Vec<u32>, // Number of agents in each reputation tier 0-4
+    pub agent_count: u32,      // Number of scored agents
+    pub total_score: u64,      // Sum of all scored agents' scores
+    pub average_score: u32,    // Mean score of scored agents (0 if there are none)
+}
+
+/// A contract to notify whenever an agent's score crosses a threshold
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subscription {
+    pub reactor: Address,  // Contract implementing `ReputationReactor`
+    pub threshold: u32,    // Crossed when a score moves between at-or-below and above it
+}
+
+/// Callback interface for contracts reacting to threshold crossings
+#[contractclient(name = "ReputationReactorClient")]
+pub trait ReputationReactor {
+    /// `rising` is true when the score moved above `threshold`, false when it fell to or below it
+    fn on_reputation_crossed(env: Env, agent: Address, threshold: u32, score: u32, rising: bool);
+}
+
+/// Time-based thaw for frozen agents: after the quarantine they report a low floor instead of 0
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FreezeRecovery {
+    pub quarantine_seconds: u64, // How long after a freeze the agent keeps reporting 0
+    pub recovery_floor: u32,     // Score reported once the quarantine is over
+}
+
+/// How many distinct approved callers must vote, within how long, before an agent is frozen
+#[contracttype]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FreezeQuorum {
+    pub threshold: u32,       // Distinct approvers needed (1 = any approved caller freezes alone)
+    pub window_seconds: u64,  // Votes older than this no longer count
+}
 
 /// Storage keys for reputation data
 #[contracttype]
@@ -13,140 +129,1807 @@ pub enum DataKey {
     Score(Address),            // Maps agent address -> reputation score
     ApprovedCallers(Address),  // Maps contract address -> bool (authorized to update scores)
     Admin(()),                 // The admin who can approve callers
+    AllowedWasmHashes(()),     // Audited WASM hashes approved callers must run (empty = any)
+    ScoreMeta(Address),        // Maps agent address -> ScoreMeta
+    Frozen(Address),           // Maps agent address -> bool (frozen for a severe violation)
+    ScoreHistory(Address),     // Maps agent address -> Vec<ScoreSample> (oldest first)
+    ApprovalExpiry(Address),   // Maps contract address -> u64 expiry timestamp (absent = never)
+    Dispute(Address),          // Maps agent address -> open Dispute
+    Initialized(()),           // Set once `initialize` has run
+    DefaultScore(()),          // Score reported for agents with no stored score
+    ProcessedUpdate(Address, BytesN<32>), // Maps (caller, idempotency key) -> IdempotentUpdate
+    ActionLog(Address),        // Maps agent address -> Vec<ActionEntry> (oldest first)
+    ProcessedKeys(Address),    // Maps caller address -> Vec<BytesN<32>> of remembered keys (oldest first)
+    Distribution(()),          // Running ScoreDistribution across all scored agents
+    AchievementMaxBonus(u32),  // Maps achievement id -> largest bonus it may grant
+    AchievementRedeemed(Address, u32), // Maps (agent, achievement id) -> bool (bonus granted)
+    NamespacedScore(String, Address),  // Maps (namespace, agent) -> score in a consumer's own namespace
+    CallerNamespaces(Address), // Maps caller address -> Vec<String> of namespaces it may update (absent = any)
+    Subscriptions(()),         // Vec<Subscription> notified of threshold crossings
+    FrozenAt(Address),         // Maps agent address -> Unix timestamp of its latest freeze
+    FreezeRecovery(()),        // FreezeRecovery policy (absent = frozen agents report 0 for good)
+    FreezeQuorum(()),          // FreezeQuorum policy (absent = a single approved caller freezes)
+    FreezeVotes(Address),      // Maps agent address -> Vec<(Address, u64)> of (voter, vote time) for a freeze
+    PenaltyOnly(Address),      // Maps caller address -> bool (may lower scores but never raise them)
 }
 
-#[contract]
-pub struct ReputationManagerContract;
+#[contract]
+pub struct ReputationManagerContract;
+
+#[contractimpl]
+impl ReputationManagerContract {
+    /// Initialize the contract with an admin address
+    /// Admin can approve which contracts can update reputation scores.
+    /// `default_score` is what agents without a score start at (`None` for the neutral
+    /// DEFAULT_SCORE, lower for deployments that want newcomers to prove themselves).
+    pub fn initialize(env: Env, admin: Address, default_score: Option<u32>) {
+        admin.require_auth();
+
+        if env.storage().persistent().has(&DataKey::Initialized(())) {
+            panic!("Contract already initialized");
+        }
+        env.storage().persistent().set(&DataKey::Initialized(()), &true);
+
+        let default_score = default_score.unwrap_or(DEFAULT_SCORE);
+        if default_score > MAX_SCORE {
+            panic!("Default score cannot exceed the maximum score");
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::DefaultScore(()), &default_score);
+
+        let key = DataKey::Admin(());
+        env.storage().persistent().set(&key, &admin);
+    }
+
+    /// Approve a contract to update reputation scores
+    /// Only admin can call this
+    pub fn approve_caller(env: Env, admin: Address, caller: Address) {
+        admin.require_auth();
+        
+        // Verify the caller is the admin
+        let stored_admin: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Admin(()))
+            .expect("Contract not initialized");
+        
+        if stored_admin != admin {
+            panic!("Unauthorized: only admin can approve callers");
+        }
+
+        // When allowlisting is enabled, the caller must be a contract running audited code
+        let allowed_hashes = Self::get_allowed_wasm_hashes(env.clone());
+        if !allowed_hashes.is_empty() {
+            let is_allowed = match caller.executable() {
+                Some(Executable::Wasm(hash)) => allowed_hashes.contains(&hash),
+                _ => false,
+            };
+
+            if !is_allowed {
+                panic!("Unauthorized: caller code is not on the audited WASM allowlist");
+            }
+        }
+
+        let key = DataKey::ApprovedCallers(caller.clone());
+        env.storage().persistent().set(&key, &true);
+        env.storage().persistent().remove(&DataKey::ApprovalExpiry(caller.clone()));
+        env.storage().persistent().remove(&DataKey::PenaltyOnly(caller));
+    }
+
+    /// Approve a contract to lower reputation scores but never raise them
+    /// Suited to external risk oracles and fraud monitors; positive deltas from it are
+    /// rejected. Re-approving it with `approve_caller` lifts the restriction.
+    /// Only admin can call this.
+    pub fn approve_penalty_caller(env: Env, admin: Address, caller: Address) {
+        Self::approve_caller(env.clone(), admin, caller.clone());
+        env.storage()
+            .persistent()
+            .set(&DataKey::PenaltyOnly(caller), &true);
+    }
+
+    /// Check whether a caller may only lower scores
+    pub fn is_penalty_only_caller(env: Env, caller: Address) -> bool {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PenaltyOnly(caller))
+            .unwrap_or(false)
+    }
+
+    /// Approve a contract to update reputation scores until `expires_at`
+    /// From that timestamp on the caller is treated as unapproved. A penalty-only caller
+    /// stays penalty-only. Only admin can call this.
+    pub fn approve_caller_until(env: Env, admin: Address, caller: Address, expires_at: u64) {
+        if expires_at <= env.ledger().timestamp() {
+            panic!("Approval expiry must be in the future");
+        }
+
+        let penalty_only = Self::is_penalty_only_caller(env.clone(), caller.clone());
+        Self::approve_caller(env.clone(), admin, caller.clone());
+        if penalty_only {
+            env.storage()
+                .persistent()
+                .set(&DataKey::PenaltyOnly(caller.clone()), &true);
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::ApprovalExpiry(caller), &expires_at);
+    }
+
+    /// Set the audited WASM hashes a contract must run to be approved as a caller
+    /// An empty list disables allowlisting. Only admin can call this.
+    pub fn set_allowed_wasm_hashes(env: Env, admin: Address, hashes: Vec<BytesN<32>>) {
+        admin.require_auth();
+
+        let stored_admin: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Admin(()))
+            .expect("Contract not initialized");
+
+        if stored_admin != admin {
+            panic!("Unauthorized: only admin can set allowed WASM hashes");
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::AllowedWasmHashes(()), &hashes);
+    }
+
+    /// Get the audited WASM hashes callers must run (empty when allowlisting is disabled)
+    pub fn get_allowed_wasm_hashes(env: Env) -> Vec<BytesN<32>> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::AllowedWasmHashes(()))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Set the largest reputation bonus an off-chain achievement may grant (0 disables it)
+    /// Only admin can call this.
+    pub fn set_achievement_max_bonus(env: Env, admin: Address, achievement_id: u32, max_bonus: u32) {
+        admin.require_auth();
+
+        let stored_admin: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Admin(()))
+            .expect("Contract not initialized");
+
+        if stored_admin != admin {
+            panic!("Unauthorized: only admin can configure achievements");
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::AchievementMaxBonus(achievement_id), &max_bonus);
+    }
+
+    /// Get the largest bonus an achievement may grant (0 if it isn't configured)
+    pub fn get_achievement_max_bonus(env: Env, achievement_id: u32) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::AchievementMaxBonus(achievement_id))
+            .unwrap_or(0)
+    }
+
+    /// Notify `reactor` each time an agent's score crosses `threshold` in either direction
+    /// The reactor's `on_reputation_crossed` runs on every score write that crosses it;
+    /// a failing reactor does not block the write. At most MAX_SUBSCRIPTIONS are kept.
+    /// Only admin can call this.
+    pub fn subscribe(env: Env, admin: Address, reactor: Address, threshold: u32) {
+        admin.require_auth();
+
+        let stored_admin: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Admin(()))
+            .expect("Contract not initialized");
+
+        if stored_admin != admin {
+            panic!("Unauthorized: only admin can manage subscriptions");
+        }
+
+        let subscription = Subscription { reactor, threshold };
+        let mut subscriptions = Self::get_subscriptions(env.clone());
+        if subscriptions.contains(&subscription) {
+            panic!("Already subscribed");
+        }
+        if subscriptions.len() >= MAX_SUBSCRIPTIONS {
+            panic!("Too many subscriptions");
+        }
+        subscriptions.push_back(subscription);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Subscriptions(()), &subscriptions);
+    }
+
+    /// Stop notifying `reactor` of crossings of `threshold`
+    /// Only admin can call this.
+    pub fn unsubscribe(env: Env, admin: Address, reactor: Address, threshold: u32) {
+        admin.require_auth();
+
+        let stored_admin: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Admin(()))
+            .expect("Contract not initialized");
+
+        if stored_admin != admin {
+            panic!("Unauthorized: only admin can manage subscriptions");
+        }
+
+        let mut subscriptions = Self::get_subscriptions(env.clone());
+        let index = subscriptions
+            .first_index_of(Subscription { reactor, threshold })
+            .expect("Not subscribed");
+        subscriptions.remove(index);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Subscriptions(()), &subscriptions);
+    }
+
+    /// Get the registered threshold-crossing subscriptions
+    pub fn get_subscriptions(env: Env) -> Vec<Subscription> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Subscriptions(()))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Let frozen agents signal they may rebuild once `quarantine_seconds` have passed
+    /// since their freeze, by reporting `recovery_floor` instead of 0. `None` turns the
+    /// thaw off. The stored score is untouched either way. Only admin can call this.
+    pub fn set_freeze_recovery(
+        env: Env,
+        admin: Address,
+        quarantine_seconds: Option<u64>,
+        recovery_floor: u32,
+    ) {
+        admin.require_auth();
+
+        let stored_admin: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Admin(()))
+            .expect("Contract not initialized");
+
+        if stored_admin != admin {
+            panic!("Unauthorized: only admin can configure freeze recovery");
+        }
+
+        match quarantine_seconds {
+            Some(quarantine_seconds) => {
+                if recovery_floor > MAX_SCORE {
+                    panic!("Recovery floor cannot exceed the maximum score");
+                }
+                env.storage().persistent().set(
+                    &DataKey::FreezeRecovery(()),
+                    &FreezeRecovery { quarantine_seconds, recovery_floor },
+                );
+            }
+            None => env.storage().persistent().remove(&DataKey::FreezeRecovery(())),
+        }
+    }
+
+    /// Get the thaw policy for frozen agents, if any
+    pub fn get_freeze_recovery(env: Env) -> Option<FreezeRecovery> {
+        env.storage().persistent().get(&DataKey::FreezeRecovery(()))
+    }
+
+    /// Get the reputation score for an agent
+    /// Returns the configured default score (50 unless set otherwise) if no score exists
+    /// yet, and 0 while the agent is frozen (the recovery floor once a configured
+    /// quarantine is over)
+    pub fn get_score(env: Env, agent: Address) -> u32 {
+        if is_frozen(&env, &agent) {
+            return frozen_score(&env, &agent);
+        }
+        Self::get_raw_score(env, agent)
+    }
+
+    /// Get the score agents without a stored score start at
+    pub fn get_default_score(env: Env) -> u32 {
+        default_score(&env)
+    }
+
+    /// Get the stored score for an agent, ignoring any freeze (diagnostic read for audits)
+    /// A frozen agent keeps its stored score; `get_score` reports 0 in its place, or the
+    /// recovery floor once a configured quarantine is over.
+    pub fn get_raw_score(env: Env, agent: Address) -> u32 {
+        let key = DataKey::Score(agent);
+        env.storage()
+            .persistent()
+            .get(&key)
+            .unwrap_or_else(|| default_score(&env))
+    }
+
+    /// Preview the score an agent would have after a reputation change of `delta`
+    /// Applies the same bounds as `update_score` without storing anything
+    pub fn project_reputation(env: Env, agent: Address, delta: i32) -> u32 {
+        if is_frozen(&env, &agent) {
+            return frozen_score(&env, &agent);
+        }
+        apply_delta(Self::get_raw_score(env, agent), delta)
+    }
+
+    /// Get an agent's score with its freeze state, change metadata and tier
+    pub fn get_score_detail(env: Env, agent: Address) -> ScoreDetail {
+        let score = Self::get_score(env.clone(), agent.clone());
+        let meta: ScoreMeta = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ScoreMeta(agent.clone()))
+            .unwrap_or_default();
+
+        ScoreDetail {
+            score,
+            frozen: is_frozen(&env, &agent),
+            last_updated: meta.last_updated,
+            change_count: meta.change_count,
+            tier: calculate_tier(score),
+            disputed: env.storage().persistent().has(&DataKey::Dispute(agent)),
+        }
+    }
+
+    /// Get the number of agents per tier and the average score across the network
+    /// Aggregates are maintained as scores change, so no scan is needed. They count
+    /// stored scores, so a frozen agent stays in the tier of the score it is holding.
+    pub fn get_distribution(env: Env) -> ScoreDistribution {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Distribution(()))
+            .unwrap_or(ScoreDistribution {
+                tier_counts: Vec::from_array(&env, [0; TIER_COUNT]),
+                agent_count: 0,
+                total_score: 0,
+                average_score: 0,
+            })
+    }
+
+    /// Check each agent's stored records for consistency, e.g. after an upgrade
+    /// Reports per agent whether its score and history samples are within bounds, a frozen
+    /// agent has a freeze time and last recorded 0 (or the recovery floor after quarantine),
+    /// and its history and action log are within their size limits.
+    pub fn verify_invariants(env: Env, agents: Vec<Address>) -> Vec<(Address, bool)> {
+        let mut report = Vec::new(&env);
+        for agent in agents.iter() {
+            let holds = agent_invariants_hold(&env, &agent);
+            report.push_back((agent, holds));
+        }
+        report
+    }
+
+    /// Open a dispute against a penalty the agent believes was unjust
+    /// Only the agent can dispute its own record, one dispute at a time
+    pub fn open_dispute(env: Env, agent: Address, loan_reference: u64) {
+        agent.require_auth();
+
+        let key = DataKey::Dispute(agent);
+        if env.storage().persistent().has(&key) {
+            panic!("Dispute already open");
+        }
+
+        let dispute = Dispute {
+            loan_reference,
+            opened_at: env.ledger().timestamp(),
+        };
+        env.storage().persistent().set(&key, &dispute);
+    }
+
+    /// Resolve an agent's open dispute, restoring `restore_delta` points (0 to reject it)
+    /// The restored score is capped at MAX_SCORE. Only admin can call this
+    pub fn resolve_dispute(env: Env, admin: Address, agent: Address, restore_delta: u32) {
+        admin.require_auth();
+
+        let stored_admin: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Admin(()))
+            .expect("Contract not initialized");
+
+        if stored_admin != admin {
+            panic!("Unauthorized: only admin can resolve disputes");
+        }
+
+        let key = DataKey::Dispute(agent.clone());
+        if !env.storage().persistent().has(&key) {
+            panic!("No open dispute");
+        }
+        env.storage().persistent().remove(&key);
+
+        if restore_delta > 0 {
+            // Restoring more than the whole score range is the same as restoring all of it
+            let delta = restore_delta.min(MAX_SCORE) as i32;
+            let restored = apply_delta(Self::get_raw_score(env.clone(), agent.clone()), delta);
+            set_score(&env, &agent, restored);
+        }
+    }
+
+    /// Get an agent's open dispute, if any
+    pub fn get_dispute(env: Env, agent: Address) -> Option<Dispute> {
+        env.storage().persistent().get(&DataKey::Dispute(agent))
+    }
+
+    /// Get an agent's recent score changes, oldest first (at most MAX_SCORE_HISTORY)
+    /// Before the first sample the agent held the default score
+    pub fn get_score_history(env: Env, agent: Address) -> Vec<ScoreSample> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::ScoreHistory(agent))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Update an agent's reputation score by a delta (positive or negative)
+    /// Can only be called by approved consumer contracts (e.g., lending demo)
+    /// This is triggered by real financial outcomes, not simulations
+    /// 
+    /// ✅ FIXED: Proper handling of negative deltas and bounds checking
+    pub fn update_score(env: Env, caller: Address, agent: Address, delta: i32) {
+        // ✅ FIXED: Require authentication from caller
+        caller.require_auth();
+        
+        // Verify caller is approved
+        if !is_caller_approved(&env, &caller) {
+            panic!("Unauthorized: caller not approved to update scores");
+        }
+        require_delta_allowed(&env, &caller, delta);
+
+        // Get the stored score (defaults to 50 for new agents) and apply the delta within bounds;
+        // a frozen agent's stored score still moves but it keeps reporting 0
+        let new_score = apply_delta(Self::get_raw_score(env.clone(), agent.clone()), delta);
+
+        // Store the new score
+        set_score(&env, &agent, new_score);
+    }
+
+    /// Get an agent's score in a consumer-specific namespace (e.g. "lending", "marketplace")
+    /// Namespaces are independent of each other and of the default score read by
+    /// `get_score`. Unscored agents start at the default score; frozen agents report what
+    /// `get_score` does.
+    pub fn get_score_in(env: Env, namespace: String, agent: Address) -> u32 {
+        if is_frozen(&env, &agent) {
+            return frozen_score(&env, &agent);
+        }
+        env.storage()
+            .persistent()
+            .get(&DataKey::NamespacedScore(namespace, agent))
+            .unwrap_or_else(|| default_score(&env))
+    }
+
+    /// Update an agent's score in a namespace by a delta, leaving other namespaces untouched
+    /// Can only be called by approved callers, within their namespaces if restricted.
+    /// Score history, metadata and the distribution track the default namespace only.
+    pub fn update_score_in(env: Env, caller: Address, namespace: String, agent: Address, delta: i32) {
+        caller.require_auth();
+
+        if !is_caller_approved(&env, &caller) {
+            panic!("Unauthorized: caller not approved to update scores");
+        }
+
+        let allowed: Option<Vec<String>> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::CallerNamespaces(caller.clone()));
+        if allowed.is_some_and(|namespaces| !namespaces.contains(&namespace)) {
+            panic!("Unauthorized: caller not approved for this namespace");
+        }
+        require_delta_allowed(&env, &caller, delta);
+
+        let key = DataKey::NamespacedScore(namespace, agent);
+        let score: u32 = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .unwrap_or_else(|| default_score(&env));
+        env.storage().persistent().set(&key, &apply_delta(score, delta));
+    }
+
+    /// Restrict an approved caller to updating scores in the given namespaces only
+    /// None lifts the restriction. Only admin can call this.
+    pub fn set_caller_namespaces(
+        env: Env,
+        admin: Address,
+        caller: Address,
+        namespaces: Option<Vec<String>>,
+    ) {
+        admin.require_auth();
+
+        let stored_admin: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Admin(()))
+            .expect("Contract not initialized");
+
+        if stored_admin != admin {
+            panic!("Unauthorized: only admin can restrict caller namespaces");
+        }
+
+        let key = DataKey::CallerNamespaces(caller);
+        match namespaces {
+            Some(namespaces) => env.storage().persistent().set(&key, &namespaces),
+            None => env.storage().persistent().remove(&key),
+        }
+    }
+
+    /// Update an agent's score like `update_score`, at most once per idempotency key
+    /// Resubmitting a key the caller already used is a no-op that returns the score the
+    /// original update produced, so retries are safe; reusing it for a different agent
+    /// or delta panics instead. Each caller's last
+    /// MAX_IDEMPOTENCY_KEYS keys are remembered. Returns the resulting score.
+    pub fn update_score_idempotent(
+        env: Env,
+        caller: Address,
+        agent: Address,
+        delta: i32,
+        key: BytesN<32>,
+    ) -> u32 {
+        let processed_key = DataKey::ProcessedUpdate(caller.clone(), key.clone());
+        let processed: Option<IdempotentUpdate> = env.storage().persistent().get(&processed_key);
+        if let Some(processed) = processed {
+            if processed.agent != agent || processed.delta != delta {
+                panic!("Idempotency key already used for a different update");
+            }
+            return processed.score;
+        }
+
+        Self::update_score(env.clone(), caller.clone(), agent.clone(), delta);
+        let score = Self::get_score(env.clone(), agent.clone());
+        env.storage()
+            .persistent()
+            .set(&processed_key, &IdempotentUpdate { agent, delta, score });
+
+        let keys_key = DataKey::ProcessedKeys(caller.clone());
+        let mut keys: Vec<BytesN<32>> = env
+            .storage()
+            .persistent()
+            .get(&keys_key)
+            .unwrap_or(Vec::new(&env));
+        if keys.len() >= MAX_IDEMPOTENCY_KEYS {
+            if let Some(oldest) = keys.pop_front() {
+                env.storage()
+                    .persistent()
+                    .remove(&DataKey::ProcessedUpdate(caller, oldest));
+            }
+        }
+        keys.push_back(key);
+        env.storage().persistent().set(&keys_key, &keys);
+
+        score
+    }
+
+    /// Grant a one-time reputation bonus for a verified off-chain achievement
+    /// (education, KYC tiers, ...). Each achievement is redeemed at most once per agent,
+    /// for at most its configured bonus. Only approved callers can redeem.
+    pub fn redeem_achievement(
+        env: Env,
+        caller: Address,
+        agent: Address,
+        achievement_id: u32,
+        bonus: i32,
+    ) {
+        caller.require_auth();
+
+        if !is_caller_approved(&env, &caller) {
+            panic!("Unauthorized: caller not approved");
+        }
+
+        if bonus <= 0 {
+            panic!("Achievement bonus must be positive");
+        }
+        require_delta_allowed(&env, &caller, bonus);
+        if bonus as u32 > Self::get_achievement_max_bonus(env.clone(), achievement_id) {
+            panic!("Bonus exceeds the achievement's maximum");
+        }
+
+        let redeemed_key = DataKey::AchievementRedeemed(agent.clone(), achievement_id);
+        if env.storage().persistent().has(&redeemed_key) {
+            panic!("Achievement already redeemed");
+        }
+        env.storage().persistent().set(&redeemed_key, &true);
+
+        let new_score = apply_delta(Self::get_raw_score(env.clone(), agent.clone()), bonus);
+        set_score(&env, &agent, new_score);
+    }
+
+    /// Check whether an agent has redeemed an achievement
+    pub fn is_achievement_redeemed(env: Env, agent: Address, achievement_id: u32) -> bool {
+        env.storage()
+            .persistent()
+            .has(&DataKey::AchievementRedeemed(agent, achievement_id))
+    }
+
+    /// Append an action to an agent's log for forensic review
+    /// Only approved callers can log; the log keeps the last MAX_ACTION_LOG entries
+    pub fn log_action(env: Env, caller: Address, agent: Address, action: String, amount: u64) {
+        caller.require_auth();
+
+        if !is_caller_approved(&env, &caller) {
+            panic!("Unauthorized: caller not approved");
+        }
+
+        let mut log = Self::get_action_log(env.clone(), agent.clone());
+        if log.len() >= MAX_ACTION_LOG {
+            log.pop_front();
+        }
+        log.push_back(ActionEntry {
+            action,
+            amount,
+            timestamp: env.ledger().timestamp(),
+            caller,
+        });
+        env.storage().persistent().set(&DataKey::ActionLog(agent), &log);
+    }
+
+    /// Get an agent's logged actions, oldest first (at most MAX_ACTION_LOG)
+    pub fn get_action_log(env: Env, agent: Address) -> Vec<ActionEntry> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::ActionLog(agent))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Require several approved callers to agree before an agent is frozen
+    /// `freeze_reputation` then only records a vote until `threshold` distinct approvers
+    /// have voted within `window_seconds`, which must be positive for a threshold above 1.
+    /// A threshold of 1 lets a single approved caller freeze. Only admin can call this.
+    pub fn set_freeze_quorum(env: Env, admin: Address, threshold: u32, window_seconds: u64) {
+        admin.require_auth();
+
+        let stored_admin: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Admin(()))
+            .expect("Contract not initialized");
+
+        if stored_admin != admin {
+            panic!("Unauthorized: only admin can configure the freeze quorum");
+        }
+
+        if threshold == 0 {
+            panic!("Freeze quorum must be at least 1");
+        }
+        // Without a window every earlier vote expires at once, so a quorum could never form
+        if threshold > 1 && window_seconds == 0 {
+            panic!("Freeze quorum window must be positive when more than one vote is required");
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::FreezeQuorum(()), &FreezeQuorum { threshold, window_seconds });
+    }
+
+    /// Get the freeze quorum (a threshold of 1 when none is configured)
+    pub fn get_freeze_quorum(env: Env) -> FreezeQuorum {
+        env.storage()
+            .persistent()
+            .get(&DataKey::FreezeQuorum(()))
+            .unwrap_or(FreezeQuorum { threshold: 1, window_seconds: 0 })
+    }
+
+    /// Get the approvers whose votes to freeze an agent still count
+    pub fn get_freeze_votes(env: Env, agent: Address) -> Vec<Address> {
+        let mut voters = Vec::new(&env);
+        for (voter, _) in live_freeze_votes(&env, &agent).iter() {
+            voters.push_back(voter);
+        }
+        voters
+    }
+
+    /// Freeze an agent's reputation (sets to 0, representing severe violation)
+    /// Only approved callers can freeze. Under a freeze quorum each call is one vote and
+    /// the agent is frozen by the vote that reaches the threshold.
+    pub fn freeze_reputation(env: Env, caller: Address, agent: Address) {
+        // ✅ FIXED: Require authentication from caller
+        caller.require_auth();
+        
+        // Verify caller is approved
+        if !is_caller_approved(&env, &caller) {
+            panic!("Unauthorized: caller not approved");
+        }
+
+        let quorum = Self::get_freeze_quorum(env.clone());
+        if quorum.threshold > 1 {
+            let mut votes = live_freeze_votes(&env, &agent);
+            if votes.iter().any(|(voter, _)| voter == caller) {
+                panic!("Caller already voted to freeze this agent");
+            }
+            votes.push_back((caller, env.ledger().timestamp()));
+
+            let key = DataKey::FreezeVotes(agent.clone());
+            if votes.len() < quorum.threshold {
+                env.storage().persistent().set(&key, &votes);
+                return;
+            }
+            env.storage().persistent().remove(&key);
+        }
+
+        // Report 0 from now on (frozen), keeping the stored score for audits
+        freeze(&env, &agent);
+    }
+
+    /// Carry an agent's reputation over to the new address it was rotated to
+    /// Copies the stored score, its history and metadata, and any freeze from `old_agent`
+    /// to `new_agent`, which must not have been scored yet. With `freeze_old`, the old
+    /// address is frozen so the same reputation cannot be used twice.
+    /// Only admin can call this.
+    pub fn link_identity(
+        env: Env,
+        admin: Address,
+        old_agent: Address,
+        new_agent: Address,
+        freeze_old: bool,
+    ) {
+        admin.require_auth();
+
+        let stored_admin: Address = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Admin(()))
+            .expect("Contract not initialized");
+
+        if stored_admin != admin {
+            panic!("Unauthorized: only admin can link identities");
+        }
+
+        if old_agent == new_agent {
+            panic!("Cannot link an agent to itself");
+        }
+        if env.storage().persistent().has(&DataKey::Score(new_agent.clone()))
+            || env.storage().persistent().has(&DataKey::ScoreMeta(new_agent.clone()))
+        {
+            panic!("New agent already has reputation");
+        }
+
+        let score: Option<u32> = env.storage().persistent().get(&DataKey::Score(old_agent.clone()));
+        if let Some(score) = score {
+            update_distribution(&env, None, score);
+            env.storage()
+                .persistent()
+                .set(&DataKey::Score(new_agent.clone()), &score);
+        }
+
+        let meta: Option<ScoreMeta> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::ScoreMeta(old_agent.clone()));
+        if let Some(meta) = meta {
+            env.storage()
+                .persistent()
+                .set(&DataKey::ScoreMeta(new_agent.clone()), &meta);
+        }
+
+        let history = Self::get_score_history(env.clone(), old_agent.clone());
+        if !history.is_empty() {
+            env.storage()
+                .persistent()
+                .set(&DataKey::ScoreHistory(new_agent.clone()), &history);
+        }
+
+        if is_frozen(&env, &old_agent) {
+            let frozen_at: Option<u64> = env
+                .storage()
+                .persistent()
+                .get(&DataKey::FrozenAt(old_agent.clone()));
+            env.storage().persistent().set(&DataKey::Frozen(new_agent.clone()), &true);
+            if let Some(frozen_at) = frozen_at {
+                env.storage()
+                    .persistent()
+                    .set(&DataKey::FrozenAt(new_agent), &frozen_at);
+            }
+        } else if freeze_old {
+            freeze(&env, &old_agent);
+        }
+    }
+
+    /// Cap an agent's reputation at a ceiling for less-severe violations
+    /// Sets the score to min(current, ceiling); a graduated step short of a full freeze
+    /// Only approved callers can penalize
+    pub fn penalize_to_ceiling(env: Env, caller: Address, agent: Address, ceiling: u32) {
+        caller.require_auth();
+
+        // Verify caller is approved
+        if !is_caller_approved(&env, &caller) {
+            panic!("Unauthorized: caller not approved");
+        }
+
+        let current_score = Self::get_score(env.clone(), agent.clone());
+        if current_score > ceiling {
+            set_score(&env, &agent, ceiling);
+        }
+    }
+}
+
+/// Whether a caller holds an approval that has not lapsed
+fn is_caller_approved(env: &Env, caller: &Address) -> bool {
+    let is_approved: bool = env
+        .storage()
+        .persistent()
+        .get(&DataKey::ApprovedCallers(caller.clone()))
+        .unwrap_or(false);
+
+    let expiry: Option<u64> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::ApprovalExpiry(caller.clone()));
+    let lapsed = expiry.is_some_and(|expires_at| env.ledger().timestamp() >= expires_at);
+
+    is_approved && !lapsed
+}
+
+/// Reject a score increase from a penalty-only caller
+fn require_delta_allowed(env: &Env, caller: &Address, delta: i32) {
+    if delta > 0 && ReputationManagerContract::is_penalty_only_caller(env.clone(), caller.clone()) {
+        panic!("Unauthorized: penalty-only caller cannot raise scores");
+    }
+}
+
+/// Whether an agent's stored reputation records are internally consistent
+fn agent_invariants_hold(env: &Env, agent: &Address) -> bool {
+    let stored: Option<u32> = env.storage().persistent().get(&DataKey::Score(agent.clone()));
+    if stored.is_some_and(|score| score > MAX_SCORE) {
+        return false;
+    }
+
+    let history = ReputationManagerContract::get_score_history(env.clone(), agent.clone());
+    if history.len() > MAX_SCORE_HISTORY || history.iter().any(|sample| sample.score > MAX_SCORE) {
+        return false;
+    }
+
+    // A freeze is timestamped and recorded, and every later change records the frozen score
+    if is_frozen(env, agent) {
+        let now = env.ledger().timestamp();
+        let frozen_at: Option<u64> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::FrozenAt(agent.clone()));
+        let Some(frozen_at) = frozen_at.filter(|frozen_at| *frozen_at <= now) else {
+            return false;
+        };
+        let Some(last) = history.last().filter(|last| last.timestamp >= frozen_at) else {
+            return false;
+        };
+        let recovery = ReputationManagerContract::get_freeze_recovery(env.clone());
+        let expected = match recovery {
+            Some(recovery)
+                if last.timestamp >= frozen_at.saturating_add(recovery.quarantine_seconds) =>
+            {
+                recovery.recovery_floor
+            }
+            _ => MIN_SCORE,
+        };
+        if last.score != expected {
+            return false;
+        }
+    }
+
+    let meta: ScoreMeta = env
+        .storage()
+        .persistent()
+        .get(&DataKey::ScoreMeta(agent.clone()))
+        .unwrap_or_default();
+    if history.len() > meta.change_count {
+        return false;
+    }
+
+    ReputationManagerContract::get_action_log(env.clone(), agent.clone()).len() <= MAX_ACTION_LOG
+}
+
+/// Score of an agent that has never been scored
+fn default_score(env: &Env) -> u32 {
+    env.storage()
+        .persistent()
+        .get(&DataKey::DefaultScore(()))
+        .unwrap_or(DEFAULT_SCORE)
+}
+
+/// Whether an agent has been frozen for a severe violation
+fn is_frozen(env: &Env, agent: &Address) -> bool {
+    env.storage()
+        .persistent()
+        .get(&DataKey::Frozen(agent.clone()))
+        .unwrap_or(false)
+}
+
+/// Votes to freeze an agent cast within the quorum window by callers that are still
+/// approved, oldest first
+fn live_freeze_votes(env: &Env, agent: &Address) -> Vec<(Address, u64)> {
+    let window = ReputationManagerContract::get_freeze_quorum(env.clone()).window_seconds;
+    let votes: Vec<(Address, u64)> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::FreezeVotes(agent.clone()))
+        .unwrap_or(Vec::new(env));
+
+    let now = env.ledger().timestamp();
+    let mut live = Vec::new(env);
+    for (voter, voted_at) in votes.iter() {
+        if now < voted_at.saturating_add(window) && is_caller_approved(env, &voter) {
+            live.push_back((voter, voted_at));
+        }
+    }
+    live
+}
+
+/// Freeze an agent as of now, restarting any quarantine, and record and announce the
+/// drop of its reported score
+fn freeze(env: &Env, agent: &Address) {
+    let previous = ReputationManagerContract::get_score(env.clone(), agent.clone());
+    env.storage().persistent().set(&DataKey::Frozen(agent.clone()), &true);
+    env.storage()
+        .persistent()
+        .set(&DataKey::FrozenAt(agent.clone()), &env.ledger().timestamp());
+
+    record_score_change(env, agent, MIN_SCORE);
+    notify_crossings(env, agent, previous, MIN_SCORE);
+}
+
+/// Score a frozen agent reports: 0, or the recovery floor once its quarantine is over
+fn frozen_score(env: &Env, agent: &Address) -> u32 {
+    let recovery = ReputationManagerContract::get_freeze_recovery(env.clone());
+    let frozen_at: Option<u64> = env.storage().persistent().get(&DataKey::FrozenAt(agent.clone()));
+    match (recovery, frozen_at) {
+        (Some(recovery), Some(frozen_at))
+            if env.ledger().timestamp() >= frozen_at.saturating_add(recovery.quarantine_seconds) =>
+        {
+            recovery.recovery_floor
+        }
+        _ => MIN_SCORE,
+    }
+}
+
+/// Apply a reputation change to a score within the score bounds
+fn apply_delta(score: u32, delta: i32) -> u32 {
+    // ✅ FIXED: Proper calculation with i32 arithmetic then conversion
+    let new_score_i32 = (score as i32).saturating_add(delta);
+
+    // Apply bounds checking
+    if new_score_i32 > (MAX_SCORE as i32) {
+        MAX_SCORE
+    } else if new_score_i32 < (MIN_SCORE as i32) {
+        MIN_SCORE
+    } else {
+        new_score_i32 as u32
+    }
+}
+
+/// Store an agent's score, record the change in its metadata and notify subscribers
+fn set_score(env: &Env, agent: &Address, score: u32) {
+    let previous_reported = ReputationManagerContract::get_score(env.clone(), agent.clone());
+    let previous: Option<u32> = env.storage().persistent().get(&DataKey::Score(agent.clone()));
+    update_distribution(env, previous, score);
+
+    env.storage()
+        .persistent()
+        .set(&DataKey::Score(agent.clone()), &score);
+
+    let reported = if is_frozen(env, agent) { frozen_score(env, agent) } else { score };
+    record_score_change(env, agent, reported);
+    notify_crossings(env, agent, previous_reported, reported);
+}
+
+/// Call every subscriber whose threshold lies between an agent's old and new reported score
+fn notify_crossings(env: &Env, agent: &Address, previous: u32, score: u32) {
+    if previous == score {
+        return;
+    }
+
+    let rising = score > previous;
+    for subscription in ReputationManagerContract::get_subscriptions(env.clone()).iter() {
+        let crossed = if rising {
+            previous <= subscription.threshold && score > subscription.threshold
+        } else {
+            score <= subscription.threshold && previous > subscription.threshold
+        };
+        if crossed {
+            let _ = ReputationReactorClient::new(env, &subscription.reactor).try_on_reputation_crossed(
+                agent,
+                &subscription.threshold,
+                &score,
+                &rising,
+            );
+        }
+    }
+}
+
+/// Move an agent's stored score from `previous` (None if never scored) to `score`
+/// in the running distribution
+fn update_distribution(env: &Env, previous: Option<u32>, score: u32) {
+    let mut distribution = ReputationManagerContract::get_distribution(env.clone());
+
+    match previous {
+        Some(previous) => {
+            let tier = calculate_tier(previous);
+            let count = distribution.tier_counts.get(tier).unwrap_or(0);
+            distribution.tier_counts.set(tier, count.saturating_sub(1));
+            distribution.total_score -= previous as u64;
+        }
+        None => distribution.agent_count += 1,
+    }
+
+    let tier = calculate_tier(score);
+    let count = distribution.tier_counts.get(tier).unwrap_or(0);
+    distribution.tier_counts.set(tier, count + 1);
+    distribution.total_score += score as u64;
+    distribution.average_score = (distribution.total_score / distribution.agent_count as u64) as u32;
+
+    env.storage()
+        .persistent()
+        .set(&DataKey::Distribution(()), &distribution);
+}
+
+/// Record a change of an agent's reported score in its metadata and history
+fn record_score_change(env: &Env, agent: &Address, score: u32) {
+    let meta_key = DataKey::ScoreMeta(agent.clone());
+    let mut meta: ScoreMeta = env.storage().persistent().get(&meta_key).unwrap_or_default();
+    meta.last_updated = env.ledger().timestamp();
+    meta.change_count += 1;
+    env.storage().persistent().set(&meta_key, &meta);
+
+    let history_key = DataKey::ScoreHistory(agent.clone());
+    let mut history: Vec<ScoreSample> = env
+        .storage()
+        .persistent()
+        .get(&history_key)
+        .unwrap_or(Vec::new(env));
+    if history.len() >= MAX_SCORE_HISTORY {
+        history.pop_front();
+    }
+    history.push_back(ScoreSample {
+        timestamp: meta.last_updated,
+        score,
+    });
+    env.storage().persistent().set(&history_key, &history);
+}
+
+/// Reputation tier (0-4) for a score
+fn calculate_tier(score: u32) -> u32 {
+    match score {
+        s if s >= TIER_4_MIN_SCORE => 4,
+        s if s >= TIER_3_MIN_SCORE => 3,
+        s if s >= TIER_2_MIN_SCORE => 2,
+        s if s >= TIER_1_MIN_SCORE => 1,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use soroban_sdk::{contract, contractimpl, vec, Env};
+    use soroban_sdk::testutils::{Address as _, Ledger};
+
+    /// Reactor recording every crossing it is notified of
+    #[contract]
+    pub struct MockReactor;
+
+    #[contractimpl]
+    impl MockReactor {
+        pub fn on_reputation_crossed(env: Env, _agent: Address, _threshold: u32, score: u32, rising: bool) {
+            let mut calls: Vec<(u32, bool)> = env.storage().instance().get(&0u32).unwrap_or(Vec::new(&env));
+            calls.push_back((score, rising));
+            env.storage().instance().set(&0u32, &calls);
+        }
+
+        pub fn calls(env: Env) -> Vec<(u32, bool)> {
+            env.storage().instance().get(&0u32).unwrap_or(Vec::new(&env))
+        }
+    }
+
+    #[test]
+    fn test_default_score() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let agent = Address::generate(&env);
+
+        // New agent should have default score of 50
+        assert_eq!(client.get_score(&agent), 50);
+    }
+
+    #[test]
+    fn test_update_score() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        // Initialize and approve caller
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+
+        // Initial score should be 50
+        assert_eq!(client.get_score(&agent), 50);
+
+        // Update score by +5 (simulating loan repayment)
+        client.update_score(&caller, &agent, &5);
+        assert_eq!(client.get_score(&agent), 55);
+
+        // Update score by -15 (simulating loan default)
+        client.update_score(&caller, &agent, &-15);
+        assert_eq!(client.get_score(&agent), 40);
+    }
+
+    #[test]
+    fn test_initialize_only_once() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let attacker = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+
+        // The admin (and the default score) cannot be replaced by re-initializing
+        assert!(client.try_initialize(&attacker, &Some(0)).is_err());
+        assert!(client.try_approve_caller(&attacker, &attacker).is_err());
+        assert_eq!(client.get_default_score(), 50);
+    }
+
+    #[test]
+    fn test_score_bounds() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+
+        // Try to go above MAX_SCORE (100)
+        client.update_score(&caller, &agent, &100);
+        assert_eq!(client.get_score(&agent), 100); // Should cap at 100
+
+        // Try to go below MIN_SCORE (0)
+        client.update_score(&caller, &agent, &-200);
+        assert_eq!(client.get_score(&agent), 0); // Should floor at 0
+    }
+
+    #[test]
+    fn test_negative_delta_handling() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+
+        // Start at default 50
+        assert_eq!(client.get_score(&agent), 50);
+
+        // Apply -25 penalty (like loan default)
+        client.update_score(&caller, &agent, &-25);
+        assert_eq!(client.get_score(&agent), 25);
+
+        // Apply another -25 penalty
+        client.update_score(&caller, &agent, &-25);
+        assert_eq!(client.get_score(&agent), 0); // Should floor at 0
+
+        // Try to go negative
+        client.update_score(&caller, &agent, &-10);
+        assert_eq!(client.get_score(&agent), 0); // Should stay at 0
+    }
+
+    #[test]
+    fn test_freeze_reputation() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+
+        // Set a good score
+        client.update_score(&caller, &agent, &30);
+        assert_eq!(client.get_score(&agent), 80);
+
+        // Freeze the agent (fraud detected)
+        client.freeze_reputation(&caller, &agent);
+        assert_eq!(client.get_score(&agent), 0);
+    }
+
+    #[test]
+    fn test_raw_score_survives_freeze() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+        client.update_score(&caller, &agent, &30);
+        client.freeze_reputation(&caller, &agent);
+
+        // The stored score is preserved for audits
+        assert_eq!(client.get_score(&agent), 0);
+        assert_eq!(client.get_raw_score(&agent), 80);
+    }
+
+    #[test]
+    fn test_penalize_to_ceiling() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let high_agent = Address::generate(&env);
+        let low_agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+
+        client.update_score(&caller, &high_agent, &30);
+        client.update_score(&caller, &low_agent, &-30);
+
+        // Agent above the ceiling is capped
+        client.penalize_to_ceiling(&caller, &high_agent, &30);
+        assert_eq!(client.get_score(&high_agent), 30);
+
+        // Agent already below the ceiling is unchanged
+        client.penalize_to_ceiling(&caller, &low_agent, &30);
+        assert_eq!(client.get_score(&low_agent), 20);
+
+        // Unapproved callers are rejected
+        let stranger = Address::generate(&env);
+        assert!(client.try_penalize_to_ceiling(&stranger, &high_agent, &10).is_err());
+    }
+
+    #[test]
+    fn test_wasm_hash_allowlist() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+        let admin = Address::generate(&env);
+
+        env.mock_all_auths();
+        client.initialize(&admin, &None);
+
+        // Contracts registered in tests all run the same (native) code hash
+        let audited = env.register(ReputationManagerContract, ());
+        let audited_hash = match audited.executable() {
+            Some(Executable::Wasm(hash)) => hash,
+            _ => panic!("expected a wasm contract"),
+        };
+        let other_hash = BytesN::from_array(&env, &[1u8; 32]);
+
+        // Contract running a disallowed hash is rejected
+        client.set_allowed_wasm_hashes(&admin, &vec![&env, other_hash.clone()]);
+        assert!(client.try_approve_caller(&admin, &audited).is_err());
+
+        // Contract running an allowed hash is approved
+        client.set_allowed_wasm_hashes(&admin, &vec![&env, other_hash, audited_hash]);
+        client.approve_caller(&admin, &audited);
+        client.update_score(&audited, &Address::generate(&env), &5);
+
+        // Non-contract callers never match an allowlisted hash
+        assert!(client.try_approve_caller(&admin, &Address::generate(&env)).is_err());
+
+        // Clearing the list disables allowlisting
+        client.set_allowed_wasm_hashes(&admin, &Vec::new(&env));
+        client.approve_caller(&admin, &Address::generate(&env));
+    }
+
+    #[test]
+    fn test_score_detail() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+
+        // Untouched agent: default score, no history
+        let detail = client.get_score_detail(&agent);
+        assert_eq!(detail.score, 50);
+        assert_eq!(detail.tier, 1);
+        assert_eq!(detail.change_count, 0);
+        assert!(!detail.frozen);
+
+        env.ledger().set_timestamp(1_000);
+        client.update_score(&caller, &agent, &30);
+        let detail = client.get_score_detail(&agent);
+        assert_eq!(detail.score, 80);
+        assert_eq!(detail.tier, 3);
+        assert_eq!(detail.last_updated, 1_000);
+        assert_eq!(detail.change_count, 1);
+        assert!(!detail.frozen);
+
+        env.ledger().set_timestamp(2_000);
+        client.freeze_reputation(&caller, &agent);
+        let detail = client.get_score_detail(&agent);
+        assert_eq!(detail.score, 0);
+        assert_eq!(detail.tier, 0);
+        assert_eq!(detail.last_updated, 2_000);
+        assert_eq!(detail.change_count, 2);
+        assert!(detail.frozen);
+    }
+
+    #[test]
+    fn test_score_history() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+        assert_eq!(client.get_score_history(&agent).len(), 0);
+
+        env.ledger().set_timestamp(100);
+        client.update_score(&caller, &agent, &10);
+        env.ledger().set_timestamp(200);
+        client.update_score(&caller, &agent, &-20);
+
+        let history = client.get_score_history(&agent);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0).unwrap(), ScoreSample { timestamp: 100, score: 60 });
+        assert_eq!(history.get(1).unwrap(), ScoreSample { timestamp: 200, score: 40 });
+
+        // Only the most recent changes are kept
+        for _ in 0..MAX_SCORE_HISTORY {
+            client.update_score(&caller, &agent, &1);
+        }
+        let history = client.get_score_history(&agent);
+        assert_eq!(history.len(), MAX_SCORE_HISTORY);
+        assert_eq!(history.get(0).unwrap().score, 41);
+    }
+
+    #[test]
+    fn test_approval_expiry() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+        env.ledger().set_timestamp(1_000);
+
+        client.initialize(&admin, &None);
+        client.approve_caller_until(&admin, &caller, &2_000);
+
+        client.update_score(&caller, &agent, &10);
+        assert_eq!(client.get_score(&agent), 60);
+
+        // The approval has lapsed: treated as unapproved
+        env.ledger().set_timestamp(2_000);
+        assert!(client.try_update_score(&caller, &agent, &10).is_err());
+        assert!(client.try_freeze_reputation(&caller, &agent).is_err());
+        assert_eq!(client.get_score(&agent), 60);
+
+        // Re-approving without an expiry never lapses
+        client.approve_caller(&admin, &caller);
+        env.ledger().set_timestamp(1_000_000);
+        client.update_score(&caller, &agent, &10);
+        assert_eq!(client.get_score(&agent), 70);
+    }
+
+    #[test]
+    fn test_project_reputation() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let agent = Address::generate(&env);
+
+        assert_eq!(client.project_reputation(&agent, &8), 58);
+        assert_eq!(client.project_reputation(&agent, &-25), 25);
+
+        // Projections clamp at the ceiling and floor
+        assert_eq!(client.project_reputation(&agent, &60), 100);
+        assert_eq!(client.project_reputation(&agent, &-60), 0);
+        assert_eq!(client.project_reputation(&agent, &i32::MIN), 0);
+
+        // Nothing is persisted
+        assert_eq!(client.get_score(&agent), 50);
+    }
+
+    #[test]
+    fn test_dispute_workflow() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+        client.update_score(&caller, &agent, &-25);
+
+        env.ledger().set_timestamp(1_000);
+        client.open_dispute(&agent, &7);
+        assert!(client.get_score_detail(&agent).disputed);
+        assert_eq!(client.get_dispute(&agent), Some(Dispute { loan_reference: 7, opened_at: 1_000 }));
+        assert!(client.try_open_dispute(&agent, &7).is_err());
+
+        client.resolve_dispute(&admin, &agent, &25);
+        let detail = client.get_score_detail(&agent);
+        assert!(!detail.disputed);
+        assert_eq!(detail.score, 50);
+        assert!(client.try_resolve_dispute(&admin, &agent, &0).is_err());
+
+        // An oversized restore saturates at the maximum instead of wrapping negative
+        client.open_dispute(&agent, &8);
+        client.resolve_dispute(&admin, &agent, &u32::MAX);
+        assert_eq!(client.get_score(&agent), MAX_SCORE);
+    }
+
+    #[test]
+    fn test_idempotent_update_applies_once() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
 
-#[contractimpl]
-impl ReputationManagerContract {
-    /// Initialize the contract with an admin address
-    /// Admin can approve which contracts can update reputation scores
-    pub fn initialize(env: Env, admin: Address) {
-        admin.require_auth();
-        
-        let key = DataKey::Admin(());
-        env.storage().persistent().set(&key, &admin);
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+
+        let key = BytesN::from_array(&env, &[1; 32]);
+        assert_eq!(client.update_score_idempotent(&caller, &agent, &5, &key), 55);
+
+        // A retry with the same key is a no-op returning the original result
+        assert_eq!(client.update_score_idempotent(&caller, &agent, &5, &key), 55);
+        assert_eq!(client.get_score(&agent), 55);
+
+        // Reusing the key for another agent or delta is rejected, not silently skipped
+        let stranger = Address::generate(&env);
+        assert!(client.try_update_score_idempotent(&caller, &stranger, &5, &key).is_err());
+        assert!(client.try_update_score_idempotent(&caller, &agent, &-5, &key).is_err());
+        assert_eq!(client.get_score(&stranger), 50);
+        assert_eq!(client.get_score(&agent), 55);
+
+        // A new key applies normally
+        let other = BytesN::from_array(&env, &[2; 32]);
+        assert_eq!(client.update_score_idempotent(&caller, &agent, &5, &other), 60);
+
+        // Keys beyond the remembered window are forgotten, oldest first
+        for i in 0..MAX_IDEMPOTENCY_KEYS {
+            let mut bytes = [0; 32];
+            bytes[0] = 3;
+            bytes[1..5].copy_from_slice(&i.to_be_bytes());
+            client.update_score_idempotent(&caller, &agent, &0, &BytesN::from_array(&env, &bytes));
+        }
+        assert_eq!(client.update_score_idempotent(&caller, &agent, &5, &key), 65);
     }
 
-    /// Approve a contract to update reputation scores
-    /// Only admin can call this
-    pub fn approve_caller(env: Env, admin: Address, caller: Address) {
-        admin.require_auth();
-        
-        // Verify the caller is the admin
-        let stored_admin: Address = env
-            .storage()
-            .persistent()
-            .get(&DataKey::Admin(()))
-            .expect("Contract not initialized");
-        
-        if stored_admin != admin {
-            panic!("Unauthorized: only admin can approve callers");
+    #[test]
+    fn test_action_log() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let stranger = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+
+        let borrow = String::from_str(&env, "borrow");
+        let repay = String::from_str(&env, "repay_loan");
+        assert!(client.try_log_action(&stranger, &agent, &borrow, &100).is_err());
+
+        env.ledger().set_timestamp(1_000);
+        client.log_action(&caller, &agent, &borrow, &100);
+        env.ledger().set_timestamp(2_000);
+        client.log_action(&caller, &agent, &repay, &105);
+
+        let log = client.get_action_log(&agent);
+        assert_eq!(log.len(), 2);
+        assert_eq!(
+            log.get(0).unwrap(),
+            ActionEntry { action: borrow.clone(), amount: 100, timestamp: 1_000, caller: caller.clone() }
+        );
+        assert_eq!(log.get(1).unwrap().action, repay);
+
+        // Capped, dropping the oldest entries
+        for amount in 0..MAX_ACTION_LOG as u64 {
+            client.log_action(&caller, &agent, &borrow, &amount);
         }
+        let log = client.get_action_log(&agent);
+        assert_eq!(log.len(), MAX_ACTION_LOG);
+        assert_eq!(log.get(0).unwrap().amount, 0);
+    }
 
-        let key = DataKey::ApprovedCallers(caller);
-        env.storage().persistent().set(&key, &true);
+    #[test]
+    fn test_distribution_tracks_tier_crossings() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent_a = Address::generate(&env);
+        let agent_b = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+
+        // Nobody scored yet
+        let empty = client.get_distribution();
+        assert_eq!(empty.agent_count, 0);
+        assert_eq!(empty.tier_counts, vec![&env, 0, 0, 0, 0, 0]);
+
+        // 50 -> 55 stays in tier 1
+        client.update_score(&caller, &agent_a, &5);
+        assert_eq!(client.get_distribution().tier_counts, vec![&env, 0, 1, 0, 0, 0]);
+
+        // 55 -> 65 crosses into tier 2
+        client.update_score(&caller, &agent_a, &10);
+        assert_eq!(client.get_distribution().tier_counts, vec![&env, 0, 0, 1, 0, 0]);
+
+        // A second agent drops to 30, tier 0
+        client.update_score(&caller, &agent_b, &-20);
+        let distribution = client.get_distribution();
+        assert_eq!(distribution.tier_counts, vec![&env, 1, 0, 1, 0, 0]);
+        assert_eq!(distribution.agent_count, 2);
+        assert_eq!(distribution.average_score, 47); // (65 + 30) / 2
+
+        // Back up to 95: tier 0 straight to tier 4
+        client.update_score(&caller, &agent_b, &65);
+        let distribution = client.get_distribution();
+        assert_eq!(distribution.tier_counts, vec![&env, 0, 0, 1, 0, 1]);
+        assert_eq!(distribution.average_score, 80);
     }
 
-    /// Get the reputation score for an agent
-    /// Returns DEFAULT_SCORE (50) if no score exists yet
-    pub fn get_score(env: Env, agent: Address) -> u32 {
-        let key = DataKey::Score(agent);
-        env.storage()
-            .persistent()
-            .get(&key)
-            .unwrap_or(DEFAULT_SCORE)
+    #[test]
+    fn test_achievement_redeems_once_per_agent() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent = Address::generate(&env);
+        let other = Address::generate(&env);
+        let kyc = 7u32;
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+
+        // Unconfigured achievements grant nothing
+        assert!(client.try_redeem_achievement(&caller, &agent, &kyc, &5).is_err());
+
+        client.set_achievement_max_bonus(&admin, &kyc, &5);
+        assert!(client.try_redeem_achievement(&caller, &agent, &kyc, &6).is_err());
+
+        client.redeem_achievement(&caller, &agent, &kyc, &5);
+        assert_eq!(client.get_score(&agent), 55);
+        assert!(client.is_achievement_redeemed(&agent, &kyc));
+
+        // No second redemption for the same agent
+        assert!(client.try_redeem_achievement(&caller, &agent, &kyc, &5).is_err());
+        assert_eq!(client.get_score(&agent), 55);
+
+        // Other agents can still redeem it
+        client.redeem_achievement(&caller, &other, &kyc, &3);
+        assert_eq!(client.get_score(&other), 53);
     }
 
-    /// Update an agent's reputation score by a delta (positive or negative)
-    /// Can only be called by approved consumer contracts (e.g., lending demo)
-    /// This is triggered by real financial outcomes, not simulations
-    /// 
-    /// ✅ FIXED: Proper handling of negative deltas and bounds checking
-    pub fn update_score(env: Env, caller: Address, agent: Address, delta: i32) {
-        // ✅ FIXED: Require authentication from caller
-        caller.require_auth();
-        
-        // Verify caller is approved
-        let approved_key = DataKey::ApprovedCallers(caller.clone());
-        let is_approved: bool = env
-            .storage()
-            .persistent()
-            .get(&approved_key)
-            .unwrap_or(false);
+    #[test]
+    fn test_configured_default_score() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
 
-        if !is_approved {
-            panic!("Unauthorized: caller not approved to update scores");
-        }
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let agent = Address::generate(&env);
 
-        // Get current score (defaults to 50 for new agents)
-        let current_score = Self::get_score(env.clone(), agent.clone());
+        env.mock_all_auths();
 
-        // ✅ FIXED: Proper calculation with i32 arithmetic then conversion
-        let new_score_i32 = (current_score as i32) + delta;
-        
-        // Apply bounds checking
-        let new_score = if new_score_i32 > (MAX_SCORE as i32) {
-            MAX_SCORE
-        } else if new_score_i32 < (MIN_SCORE as i32) {
-            MIN_SCORE
-        } else {
-            new_score_i32 as u32
-        };
+        client.initialize(&admin, &Some(30));
+        client.approve_caller(&admin, &caller);
 
-        // Store the new score
-        let score_key = DataKey::Score(agent);
-        env.storage().persistent().set(&score_key, &new_score);
+        // Unknown agents start at the configured default
+        assert_eq!(client.get_score(&agent), 30);
+        assert_eq!(client.get_default_score(), 30);
+
+        client.update_score(&caller, &agent, &5);
+        assert_eq!(client.get_score(&agent), 35);
+
+        // Omitting the default keeps the neutral midpoint
+        let unconfigured_id = env.register(ReputationManagerContract, ());
+        let unconfigured = ReputationManagerContractClient::new(&env, &unconfigured_id);
+        unconfigured.initialize(&admin, &None);
+        assert_eq!(unconfigured.get_default_score(), DEFAULT_SCORE);
+        assert_eq!(unconfigured.get_score(&agent), 50);
     }
 
-    /// Freeze an agent's reputation (sets to 0, representing severe violation)
-    /// Only approved callers can freeze
-    pub fn freeze_reputation(env: Env, caller: Address, agent: Address) {
-        // ✅ FIXED: Require authentication from caller
-        caller.require_auth();
-        
-        // Verify caller is approved
-        let approved_key = DataKey::ApprovedCallers(caller);
-        let is_approved: bool = env
-            .storage()
-            .persistent()
-            .get(&approved_key)
-            .unwrap_or(false);
+    #[test]
+    fn test_namespaces_are_independent() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
 
-        if !is_approved {
-            panic!("Unauthorized: caller not approved");
+        let admin = Address::generate(&env);
+        let lender = Address::generate(&env);
+        let market = Address::generate(&env);
+        let agent = Address::generate(&env);
+        let lending = String::from_str(&env, "lending");
+        let marketplace = String::from_str(&env, "marketplace");
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &lender);
+        client.approve_caller(&admin, &market);
+        client.set_caller_namespaces(&admin, &lender, &Some(vec![&env, lending.clone()]));
+
+        client.update_score_in(&lender, &lending, &agent, &20);
+        client.update_score_in(&market, &marketplace, &agent, &-10);
+
+        assert_eq!(client.get_score_in(&lending, &agent), 70);
+        assert_eq!(client.get_score_in(&marketplace, &agent), 40);
+        assert_eq!(client.get_score(&agent), 50); // Default namespace untouched
+
+        // A restricted caller stays in its own namespace
+        assert!(client.try_update_score_in(&lender, &marketplace, &agent, &10).is_err());
+        assert_eq!(client.get_score_in(&marketplace, &agent), 40);
+    }
+
+    #[test]
+    fn test_verify_invariants_on_well_formed_records() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let scored = Address::generate(&env);
+        let frozen = Address::generate(&env);
+        let unknown = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+
+        for _ in 0..40 {
+            client.update_score(&caller, &scored, &7);
         }
+        client.log_action(&caller, &scored, &String::from_str(&env, "borrow"), &1_000);
+        client.update_score(&caller, &frozen, &20);
+        client.freeze_reputation(&caller, &frozen);
 
-        // Set score to 0 (frozen)
-        let score_key = DataKey::Score(agent);
-        env.storage().persistent().set(&score_key, &MIN_SCORE);
+        let agents = vec![&env, scored.clone(), frozen.clone(), unknown.clone()];
+        let report = client.verify_invariants(&agents);
+        assert_eq!(report, vec![&env, (scored, true), (frozen, true), (unknown, true)]);
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use soroban_sdk::Env;
-    use soroban_sdk::testutils::Address as _;
+    #[test]
+    fn test_verify_invariants_flags_corrupted_freeze() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let untimed = Address::generate(&env);
+        let unrecorded = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+        client.freeze_reputation(&caller, &untimed);
+        client.freeze_reputation(&caller, &unrecorded);
+
+        // A freeze without its timestamp, and one whose last recorded score isn't the frozen one
+        env.as_contract(&contract_id, || {
+            env.storage().persistent().remove(&DataKey::FrozenAt(untimed.clone()));
+            let sample = ScoreSample { timestamp: 0, score: 30 };
+            env.storage()
+                .persistent()
+                .set(&DataKey::ScoreHistory(unrecorded.clone()), &vec![&env, sample]);
+        });
+
+        let report = client.verify_invariants(&vec![&env, untimed.clone(), unrecorded.clone()]);
+        assert_eq!(report, vec![&env, (untimed, false), (unrecorded, false)]);
+    }
 
     #[test]
-    fn test_default_score() {
+    fn test_link_identity_carries_reputation_over() {
         let env = Env::default();
         let contract_id = env.register(ReputationManagerContract, ());
         let client = ReputationManagerContractClient::new(&env, &contract_id);
 
-        let agent = Address::generate(&env);
+        let admin = Address::generate(&env);
+        let caller = Address::generate(&env);
+        let old_agent = Address::generate(&env);
+        let new_agent = Address::generate(&env);
 
-        // New agent should have default score of 50
-        assert_eq!(client.get_score(&agent), 50);
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &caller);
+        client.update_score(&caller, &old_agent, &20);
+        client.update_score(&caller, &old_agent, &10);
+
+        assert!(client.try_link_identity(&caller, &old_agent, &new_agent, &true).is_err());
+        client.link_identity(&admin, &old_agent, &new_agent, &true);
+
+        assert_eq!(client.get_score(&new_agent), 80);
+        assert_eq!(client.get_score_history(&new_agent).len(), 2);
+        assert_eq!(client.get_score_detail(&new_agent).change_count, 2);
+        assert_eq!(client.get_score(&old_agent), 0);
+        assert!(client.get_score_detail(&old_agent).frozen);
+        assert_eq!(client.get_distribution().agent_count, 2);
+
+        // The new address cannot be overwritten by another link
+        let other = Address::generate(&env);
+        client.update_score(&caller, &other, &5);
+        assert!(client.try_link_identity(&admin, &other, &new_agent, &false).is_err());
     }
 
     #[test]
-    fn test_update_score() {
+    fn test_subscribers_notified_once_per_crossing() {
         let env = Env::default();
         let contract_id = env.register(ReputationManagerContract, ());
         let client = ReputationManagerContractClient::new(&env, &contract_id);
+        let reactor_id = env.register(MockReactor, ());
+        let reactor = MockReactorClient::new(&env, &reactor_id);
 
         let admin = Address::generate(&env);
         let caller = Address::generate(&env);
@@ -154,48 +1937,62 @@ mod test {
 
         env.mock_all_auths();
 
-        // Initialize and approve caller
-        client.initialize(&admin);
+        client.initialize(&admin, &None);
         client.approve_caller(&admin, &caller);
+        assert!(client.try_subscribe(&caller, &reactor_id, &40).is_err());
+        client.subscribe(&admin, &reactor_id, &40);
 
-        // Initial score should be 50
-        assert_eq!(client.get_score(&agent), 50);
+        client.update_score(&caller, &agent, &-5);  // 45: still above
+        client.update_score(&caller, &agent, &-10); // 35: falls through 40
+        client.update_score(&caller, &agent, &-5);  // 30: still below
+        client.update_score(&caller, &agent, &20);  // 50: rises through 40
+        client.update_score(&caller, &agent, &10);  // 60: still above
+        assert_eq!(reactor.calls(), vec![&env, (35, false), (50, true)]);
 
-        // Update score by +5 (simulating loan repayment)
-        client.update_score(&caller, &agent, &5);
-        assert_eq!(client.get_score(&agent), 55);
+        // Subscriptions are bounded
+        for threshold in 1..MAX_SUBSCRIPTIONS {
+            client.subscribe(&admin, &reactor_id, &threshold);
+        }
+        assert!(client.try_subscribe(&admin, &reactor_id, &99).is_err());
 
-        // Update score by -15 (simulating loan default)
-        client.update_score(&caller, &agent, &-15);
-        assert_eq!(client.get_score(&agent), 40);
+        client.unsubscribe(&admin, &reactor_id, &40);
+        assert_eq!(client.get_subscriptions().len(), MAX_SUBSCRIPTIONS - 1);
     }
 
     #[test]
-    fn test_score_bounds() {
+    fn test_freeze_notifies_subscribers() {
         let env = Env::default();
         let contract_id = env.register(ReputationManagerContract, ());
         let client = ReputationManagerContractClient::new(&env, &contract_id);
+        let reactor_id = env.register(MockReactor, ());
+        let reactor = MockReactorClient::new(&env, &reactor_id);
 
         let admin = Address::generate(&env);
         let caller = Address::generate(&env);
         let agent = Address::generate(&env);
+        let rotated = Address::generate(&env);
+        let successor = Address::generate(&env);
 
         env.mock_all_auths();
 
-        client.initialize(&admin);
+        client.initialize(&admin, &None);
         client.approve_caller(&admin, &caller);
+        client.subscribe(&admin, &reactor_id, &0);
 
-        // Try to go above MAX_SCORE (100)
-        client.update_score(&caller, &agent, &100);
-        assert_eq!(client.get_score(&agent), 100); // Should cap at 100
+        // Dropping to 0 through a freeze is a crossing like any other
+        client.freeze_reputation(&caller, &agent);
+        assert_eq!(reactor.calls(), vec![&env, (0, false)]);
 
-        // Try to go below MIN_SCORE (0)
-        client.update_score(&caller, &agent, &-200);
-        assert_eq!(client.get_score(&agent), 0); // Should floor at 0
+        // Refreezing a frozen agent doesn't cross again
+        client.freeze_reputation(&caller, &agent);
+        assert_eq!(reactor.calls().len(), 1);
+
+        client.link_identity(&admin, &rotated, &successor, &true);
+        assert_eq!(reactor.calls(), vec![&env, (0, false), (0, false)]);
     }
 
     #[test]
-    fn test_negative_delta_handling() {
+    fn test_frozen_agent_thaws_to_recovery_floor() {
         let env = Env::default();
         let contract_id = env.register(ReputationManagerContract, ());
         let client = ReputationManagerContractClient::new(&env, &contract_id);
@@ -203,49 +2000,108 @@ mod test {
         let admin = Address::generate(&env);
         let caller = Address::generate(&env);
         let agent = Address::generate(&env);
+        let day = 24 * 60 * 60;
 
         env.mock_all_auths();
 
-        client.initialize(&admin);
+        client.initialize(&admin, &None);
         client.approve_caller(&admin, &caller);
+        client.set_freeze_recovery(&admin, &Some(30 * day), &10);
 
-        // Start at default 50
-        assert_eq!(client.get_score(&agent), 50);
+        env.ledger().set_timestamp(1_000);
+        client.freeze_reputation(&caller, &agent);
 
-        // Apply -25 penalty (like loan default)
-        client.update_score(&caller, &agent, &-25);
-        assert_eq!(client.get_score(&agent), 25);
+        // Quarantined: still 0
+        env.ledger().set_timestamp(1_000 + 30 * day - 1);
+        assert_eq!(client.get_score(&agent), 0);
 
-        // Apply another -25 penalty
-        client.update_score(&caller, &agent, &-25);
-        assert_eq!(client.get_score(&agent), 0); // Should floor at 0
+        // Afterwards the recovery floor, while the agent stays frozen
+        env.ledger().set_timestamp(1_000 + 30 * day);
+        assert_eq!(client.get_score(&agent), 10);
+        assert!(client.get_score_detail(&agent).frozen);
+        assert_eq!(client.get_raw_score(&agent), 50);
 
-        // Try to go negative
-        client.update_score(&caller, &agent, &-10);
-        assert_eq!(client.get_score(&agent), 0); // Should stay at 0
+        // Without a recovery policy a freeze lasts for good
+        client.set_freeze_recovery(&admin, &None, &0);
+        assert_eq!(client.get_score(&agent), 0);
     }
 
     #[test]
-    fn test_freeze_reputation() {
+    fn test_freeze_requires_quorum_of_distinct_approvers() {
         let env = Env::default();
         let contract_id = env.register(ReputationManagerContract, ());
         let client = ReputationManagerContractClient::new(&env, &contract_id);
 
         let admin = Address::generate(&env);
-        let caller = Address::generate(&env);
+        let first = Address::generate(&env);
+        let second = Address::generate(&env);
         let agent = Address::generate(&env);
+        let day = 24 * 60 * 60;
 
         env.mock_all_auths();
 
-        client.initialize(&admin);
-        client.approve_caller(&admin, &caller);
+        client.initialize(&admin, &None);
+        client.approve_caller(&admin, &first);
+        client.approve_caller(&admin, &second);
+        assert!(client.try_set_freeze_quorum(&admin, &2, &0).is_err());
+        client.set_freeze_quorum(&admin, &2, &day);
 
-        // Set a good score
-        client.update_score(&caller, &agent, &30);
-        assert_eq!(client.get_score(&agent), 80);
+        // One vote is not enough, and the same approver cannot vote twice
+        client.freeze_reputation(&first, &agent);
+        assert_eq!(client.get_score(&agent), 50);
+        assert!(client.try_freeze_reputation(&first, &agent).is_err());
 
-        // Freeze the agent (fraud detected)
-        client.freeze_reputation(&caller, &agent);
+        // A vote that has expired no longer counts
+        env.ledger().set_timestamp(day);
+        client.freeze_reputation(&second, &agent);
+        assert_eq!(client.get_score(&agent), 50);
+        assert_eq!(client.get_freeze_votes(&agent), vec![&env, second.clone()]);
+
+        client.freeze_reputation(&first, &agent);
         assert_eq!(client.get_score(&agent), 0);
+        assert!(client.get_freeze_votes(&agent).is_empty());
+
+        // A vote stops counting once its caller's approval lapses
+        let temporary = Address::generate(&env);
+        let other = Address::generate(&env);
+        client.approve_caller_until(&admin, &temporary, &(day + 100));
+        client.freeze_reputation(&temporary, &other);
+        env.ledger().set_timestamp(day + 200);
+        assert!(client.get_freeze_votes(&other).is_empty());
+        client.freeze_reputation(&first, &other);
+        assert_eq!(client.get_score(&other), 50);
+    }
+
+    #[test]
+    fn test_penalty_only_caller_cannot_raise_scores() {
+        let env = Env::default();
+        let contract_id = env.register(ReputationManagerContract, ());
+        let client = ReputationManagerContractClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let oracle = Address::generate(&env);
+        let agent = Address::generate(&env);
+
+        env.mock_all_auths();
+
+        client.initialize(&admin, &None);
+        client.approve_penalty_caller(&admin, &oracle);
+        assert!(client.is_penalty_only_caller(&oracle));
+
+        assert!(client.try_update_score(&oracle, &agent, &5).is_err());
+        assert_eq!(client.get_score(&agent), 50);
+        client.update_score(&oracle, &agent, &-15);
+        assert_eq!(client.get_score(&agent), 35);
+
+        // Renewing it with an expiring approval keeps the restriction
+        client.approve_caller_until(&admin, &oracle, &1_000);
+        assert!(client.is_penalty_only_caller(&oracle));
+        assert!(client.try_update_score(&oracle, &agent, &5).is_err());
+
+        // A full approval lifts the restriction
+        client.approve_caller(&admin, &oracle);
+        assert!(!client.is_penalty_only_caller(&oracle));
+        client.update_score(&oracle, &agent, &5);
+        assert_eq!(client.get_score(&agent), 40);
     }
-}
\ No newline at end of file
+}